@@ -0,0 +1,281 @@
+use crate::timer::{deserialize_duration, deserialize_time};
+
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+use serde::Deserialize;
+use serde::de::{Deserializer, Error as SeError};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest nap taken while waiting, so that clock changes and suspends are noticed.
+const MAX_NAP: Duration = Duration::from_secs(30);
+
+/// Source of the current time, so the scheduling can be driven by a fake clock.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+    fn sleep(&self, duration: Duration);
+}
+
+/// The local wall clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration)
+    }
+}
+
+/// Waits until the clock reaches `target`.
+pub fn wait_until(clock: &impl Clock, target: NaiveDateTime) {
+    loop {
+        let remaining = match (target - clock.now()).to_std() {
+            Ok(d) if !d.is_zero() => d,
+            _ => return,
+        };
+
+        clock.sleep(remaining.min(MAX_NAP));
+    }
+}
+
+/// An alarm that rings at `time` on the given days (every day if empty).
+#[derive(Deserialize, Debug, Clone)]
+pub struct AlarmTime {
+    #[serde(default, deserialize_with = "deserialize_weekdays")]
+    pub days: Vec<Weekday>,
+
+    #[serde(deserialize_with = "deserialize_time")]
+    pub time: NaiveTime,
+}
+
+impl AlarmTime {
+    pub fn new(time: NaiveTime) -> AlarmTime {
+        AlarmTime {
+            days: Vec::new(),
+            time,
+        }
+    }
+
+    /// First moment strictly after `now` in which this alarm rings.
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        (0..=7)
+            .map(|offset| (now.date() + TimeDelta::days(offset)).and_time(self.time))
+            .find(|t| *t > now && (self.days.is_empty() || self.days.contains(&t.weekday())))
+    }
+}
+
+/// The `alarm` section of the config
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct AlarmConfig {
+    /// Station to wake up to, by name
+    pub station: Option<String>,
+
+    /// Local audio file played when the station can't be reached
+    pub fallback: Option<PathBuf>,
+
+    /// How long it takes for the volume to go from silence to full volume
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub ramp: Option<Duration>,
+
+    #[serde(default)]
+    pub schedule: Vec<AlarmTime>,
+}
+
+impl AlarmConfig {
+    /// Next time any of the scheduled alarms rings.
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        self.schedule.iter().filter_map(|a| a.next_after(now)).min()
+    }
+}
+
+/// Gradually raises the volume from silence to `target`.
+#[derive(Debug, Clone, Copy)]
+pub struct VolumeRamp {
+    pub duration: Duration,
+    pub target: f64,
+}
+
+impl VolumeRamp {
+    pub fn new(duration: Duration, target: f64) -> VolumeRamp {
+        VolumeRamp { duration, target }
+    }
+
+    pub fn volume_at(&self, elapsed: Duration) -> f64 {
+        if elapsed >= self.duration || self.duration.is_zero() {
+            return self.target;
+        }
+
+        self.target * elapsed.as_secs_f64() / self.duration.as_secs_f64()
+    }
+
+    pub fn is_done(&self, elapsed: Duration) -> bool {
+        elapsed >= self.duration
+    }
+}
+
+fn deserialize_weekdays<'de, D>(deserializer: D) -> Result<Vec<Weekday>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|d| {
+            d.parse::<Weekday>()
+                .map_err(|_| SeError::custom(format!("invalid day of the week \"{}\"", d)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::cell::{Cell, RefCell};
+
+    /// A clock that only moves when slept on, noting every nap
+    struct FakeClock {
+        now: Cell<NaiveDateTime>,
+        naps: RefCell<Vec<Duration>>,
+    }
+
+    impl FakeClock {
+        fn at(now: NaiveDateTime) -> FakeClock {
+            FakeClock {
+                now: Cell::new(now),
+                naps: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> NaiveDateTime {
+            self.now.get()
+        }
+
+        fn sleep(&self, duration: Duration) {
+            self.naps.borrow_mut().push(duration);
+            self.now
+                .set(self.now.get() + TimeDelta::from_std(duration).unwrap());
+        }
+    }
+
+    /// 2024-01-01 was a Monday
+    fn monday(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn rings_later_the_same_day() {
+        let alarm = AlarmTime::new(time(7, 30));
+        assert_eq!(alarm.next_after(monday(6, 0)), Some(monday(7, 30)));
+    }
+
+    #[test]
+    fn a_time_already_past_rings_the_next_day() {
+        let alarm = AlarmTime::new(time(7, 30));
+        let tuesday = monday(7, 30) + TimeDelta::days(1);
+
+        assert_eq!(alarm.next_after(monday(7, 30)), Some(tuesday));
+        assert_eq!(alarm.next_after(monday(8, 0)), Some(tuesday));
+    }
+
+    #[test]
+    fn only_rings_on_its_days() {
+        let alarm = AlarmTime {
+            days: vec![Weekday::Wed, Weekday::Fri],
+            time: time(7, 0),
+        };
+
+        assert_eq!(
+            alarm.next_after(monday(6, 0)),
+            Some(monday(7, 0) + TimeDelta::days(2))
+        );
+        // From Friday after it rang, to Wednesday of the next week
+        assert_eq!(
+            alarm.next_after(monday(8, 0) + TimeDelta::days(4)),
+            Some(monday(7, 0) + TimeDelta::days(9))
+        );
+    }
+
+    #[test]
+    fn wraps_around_the_week() {
+        let alarm = AlarmTime {
+            days: vec![Weekday::Mon],
+            time: time(7, 0),
+        };
+
+        assert_eq!(
+            alarm.next_after(monday(7, 0)),
+            Some(monday(7, 0) + TimeDelta::days(7))
+        );
+    }
+
+    #[test]
+    fn the_schedule_rings_at_its_earliest_alarm() {
+        let config: AlarmConfig = serde_json::from_str(
+            r#"{"schedule": [
+                {"days": ["Sat", "Sun"], "time": "09:00"},
+                {"days": ["Tue"], "time": "06:45"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.next_after(monday(12, 0)),
+            Some(monday(6, 45) + TimeDelta::days(1))
+        );
+    }
+
+    #[test]
+    fn invalid_days_are_errors() {
+        let result = serde_json::from_str::<AlarmTime>(r#"{"days": ["Someday"], "time": "07:00"}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn waits_in_naps_until_the_time() {
+        let clock = FakeClock::at(monday(7, 0));
+        wait_until(&clock, monday(7, 1) + TimeDelta::seconds(10));
+
+        assert_eq!(clock.now(), monday(7, 1) + TimeDelta::seconds(10));
+        assert_eq!(
+            *clock.naps.borrow(),
+            vec![MAX_NAP, MAX_NAP, Duration::from_secs(10)]
+        );
+    }
+
+    #[test]
+    fn does_not_wait_for_the_past() {
+        let clock = FakeClock::at(monday(7, 0));
+        wait_until(&clock, monday(6, 0));
+
+        assert!(clock.naps.borrow().is_empty());
+    }
+
+    #[test]
+    fn ramps_the_volume_up() {
+        let ramp = VolumeRamp::new(Duration::from_secs(60), 80.0);
+
+        assert_eq!(ramp.volume_at(Duration::ZERO), 0.0);
+        assert_eq!(ramp.volume_at(Duration::from_secs(15)), 20.0);
+        assert_eq!(ramp.volume_at(Duration::from_secs(60)), 80.0);
+        assert_eq!(ramp.volume_at(Duration::from_secs(600)), 80.0);
+        assert!(!ramp.is_done(Duration::from_secs(59)));
+        assert!(ramp.is_done(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn an_instant_ramp_is_at_full_volume() {
+        let ramp = VolumeRamp::new(Duration::ZERO, 50.0);
+        assert_eq!(ramp.volume_at(Duration::ZERO), 50.0);
+    }
+}
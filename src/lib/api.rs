@@ -0,0 +1,362 @@
+//! HTTP/JSON API to control the player from other machines of the network:
+//!
+//! - `GET /stations`: stations of the config
+//! - `GET /search?q=<name>`: stations found in Radio Browser
+//! - `GET /status`: what's playing
+//! - `GET /history`: stations and songs played, the most recent last
+//! - `POST /play`, `/stop`, `/next`, `/previous`, `/toggle`, `/volume`: the requests of
+//!   the daemon, with the rest of their fields in a JSON body, e.g. `{"station": "Lofi"}`
+
+use crate::Config;
+use crate::browser::Browser;
+use crate::daemon::{Daemon, Request};
+use crate::player::Player;
+
+use chrono::{Local, NaiveDateTime, SubsecRound};
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::collections::VecDeque;
+use std::io::Result;
+use std::rc::Rc;
+use std::time::Duration;
+use tiny_http::{Header, Method, Server};
+
+/// How often the player is checked when there are no requests
+const TICK: Duration = Duration::from_millis(200);
+
+/// Requests of the daemon that can be posted
+const COMMANDS: [&str; 7] = [
+    "play", "stop", "next", "previous", "toggle", "volume", "status",
+];
+
+/// How many entries of the history are kept
+const HISTORY_LENGTH: usize = 100;
+
+/// A station that started playing, or a song announced by it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub time: NaiveDateTime,
+    pub station: String,
+    pub title: Option<String>,
+}
+
+/// Answers the API calls, playing through a `Daemon`
+pub struct Api<P: Player> {
+    config: Rc<Config>,
+    daemon: Daemon<P>,
+    browser: Option<Browser>,
+    history: VecDeque<HistoryEntry>,
+}
+
+impl<P: Player> Api<P> {
+    pub fn new(config: Rc<Config>, daemon: Daemon<P>) -> Api<P> {
+        Api {
+            config,
+            daemon,
+            browser: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Answers a call with its status code and JSON body.
+    /// `path` may include the query, and `body` is ignored by the `GET`s.
+    pub fn handle(&mut self, method: &Method, path: &str, body: &str) -> (u16, Value) {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+
+        let response = match (method, path.trim_end_matches('/')) {
+            (Method::Get, "/stations") => (200, json!(self.config.stations().collect::<Vec<_>>())),
+            (Method::Get, "/search") => match query_param(query, "q") {
+                Some(q) if !q.trim().is_empty() => self.search(&q),
+                _ => error(400, "Missing the query: /search?q=<name>"),
+            },
+            (Method::Get, "/status") => (200, json!(self.daemon.status())),
+            (Method::Get, "/history") => (200, json!(self.history)),
+            (Method::Post, command) => self.request(command.trim_start_matches('/'), body),
+            _ => error(404, "Not found"),
+        };
+
+        self.update_history();
+        response
+    }
+
+    fn search(&mut self, query: &str) -> (u16, Value) {
+        if self.browser.is_none() {
+            match self.daemon.directory().browser(None) {
+                Ok((b, _)) => self.browser = Some(b),
+                Err(e) => {
+                    debug!("{}", e);
+                    return error(502, "Could not connect with Radio Browser");
+                }
+            }
+        }
+
+        match &self.browser {
+            Some(browser) => (200, json!(browser.search(query))),
+            None => error(502, "Could not connect with Radio Browser"),
+        }
+    }
+
+    /// Builds a daemon request from the command in the path and the fields in the body
+    fn request(&mut self, command: &str, body: &str) -> (u16, Value) {
+        if !COMMANDS.contains(&command) {
+            return error(404, "Not found");
+        }
+
+        let mut fields = match body.trim() {
+            "" => Map::new(),
+            b => match serde_json::from_str::<Value>(b) {
+                Ok(Value::Object(m)) => m,
+                _ => return error(400, "The body must be a JSON object"),
+            },
+        };
+        fields.insert(String::from("command"), Value::from(command));
+
+        let request = match serde_json::from_value::<Request>(Value::Object(fields)) {
+            Ok(r) => r,
+            Err(e) => return error(400, &format!("Invalid request: {}", e)),
+        };
+
+        let response = self.daemon.handle(request);
+        let code = if response.ok { 200 } else { 400 };
+
+        (code, json!(response))
+    }
+
+    /// Notes the station and the song when they change
+    fn update_history(&mut self) {
+        let status = self.daemon.status();
+        let Some(station) = status.station else {
+            return;
+        };
+
+        let changed = match self.history.back() {
+            Some(last) => last.station != station || last.title != status.title,
+            None => true,
+        };
+
+        if changed {
+            self.history.push_back(HistoryEntry {
+                time: Local::now().naive_local().trunc_subsecs(0),
+                station,
+                title: status.title,
+            });
+
+            if self.history.len() > HISTORY_LENGTH {
+                self.history.pop_front();
+            }
+        }
+    }
+
+    /// Answers the calls until the server fails
+    pub fn serve(&mut self, server: &Server) -> Result<()> {
+        loop {
+            if let Some(mut request) = server.recv_timeout(TICK)? {
+                let mut body = String::new();
+                if let Err(e) = request.as_reader().read_to_string(&mut body) {
+                    debug!("Could not read the request: {}", e);
+                }
+
+                let (code, value) = self.handle(request.method(), request.url(), &body);
+                debug!("{} {} -> {}", request.method(), request.url(), code);
+
+                let response = tiny_http::Response::from_string(value.to_string())
+                    .with_status_code(code)
+                    .with_header(
+                        Header::from_bytes("Content-Type", "application/json")
+                            .expect("valid header"),
+                    );
+
+                if let Err(e) = request.respond(response) {
+                    warn!("Could not answer the request: {}", e);
+                }
+            }
+
+            self.daemon.check_player();
+            self.update_history();
+        }
+    }
+}
+
+fn error(code: u16, message: &str) -> (u16, Value) {
+    (code, json!({ "ok": false, "error": message }))
+}
+
+/// Decoded value of a parameter of the query string
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::PlayerOptions;
+    use crate::testing::{self, FakePlayer};
+    use std::sync::{Arc, Mutex};
+
+    /// The API with fake players on a local port, its address and the log of its players
+    fn start() -> (String, Arc<Mutex<Vec<String>>>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", server.server_addr());
+
+        let (spawn, log) = FakePlayer::spawner();
+        std::thread::spawn(move || {
+            let config = Rc::new(testing::config(&[
+                ("Lofi", "http://lofi.example/stream"),
+                ("Jazz", "http://jazz.example/stream"),
+            ]));
+            let daemon = Daemon::new(config.clone(), PlayerOptions::default(), Box::new(spawn));
+
+            Api::new(config, daemon).serve(&server)
+        });
+
+        (address, log)
+    }
+
+    fn get(address: &str, path: &str) -> (u16, Value) {
+        let response = reqwest::blocking::get(format!("{}{}", address, path)).unwrap();
+        (response.status().as_u16(), response.json().unwrap())
+    }
+
+    fn post(address: &str, path: &str, body: &str) -> (u16, Value) {
+        let response = reqwest::blocking::Client::new()
+            .post(format!("{}{}", address, path))
+            .body(body.to_string())
+            .send()
+            .unwrap();
+        (response.status().as_u16(), response.json().unwrap())
+    }
+
+    #[test]
+    fn lists_the_stations_of_the_config() {
+        let (address, _) = start();
+
+        let (code, stations) = get(&address, "/stations");
+        assert_eq!(code, 200);
+        assert_eq!(stations[0]["station"], "Lofi");
+        assert_eq!(stations[1]["url"], "http://jazz.example/stream");
+        assert_eq!(stations.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn plays_and_reports_the_status_and_history() {
+        let (address, log) = start();
+
+        let (code, status) = get(&address, "/status");
+        assert_eq!(code, 200);
+        assert_eq!(status["station"], Value::Null);
+
+        let (code, response) = post(&address, "/play", r#"{"station": "Jazz"}"#);
+        assert_eq!(code, 200);
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["status"]["station"], "Jazz");
+
+        let (_, status) = get(&address, "/status/");
+        assert_eq!(status["title"], "Live on Jazz");
+
+        let (code, response) = post(&address, "/volume", r#"{"delta": -10}"#);
+        assert_eq!(code, 200);
+        assert_eq!(response["status"]["volume"], 90.0);
+
+        let (_, history) = get(&address, "/history");
+        assert_eq!(history[0]["station"], "Jazz");
+        assert_eq!(history[0]["title"], "Live on Jazz");
+
+        let (code, _) = post(&address, "/stop", "");
+        assert_eq!(code, 200);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "play http://jazz.example/stream at 100",
+                "volume 90",
+                "stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_requests() {
+        let (address, log) = start();
+
+        let (code, response) = post(&address, "/play", "[1, 2]");
+        assert_eq!((code, &response["ok"]), (400, &Value::Bool(false)));
+        assert_eq!(response["error"], "The body must be a JSON object");
+
+        let (code, response) = post(&address, "/play", r#"{"name": "Lofi"}"#);
+        assert_eq!(code, 400);
+        assert!(
+            response["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid request")
+        );
+
+        let (code, response) = post(&address, "/play", r#"{"station": "x", "url": "/dev/sda"}"#);
+        assert_eq!(code, 400);
+        assert!(
+            response["error"]
+                .as_str()
+                .unwrap()
+                .contains("not an http or https stream")
+        );
+
+        let (code, _) = get(&address, "/search?q=");
+        assert_eq!(code, 400);
+
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_paths_and_methods() {
+        let (address, _) = start();
+
+        assert_eq!(get(&address, "/nowhere").0, 404);
+        assert_eq!(get(&address, "/play").0, 404);
+        assert_eq!(post(&address, "/dance", "").0, 404);
+        assert_eq!(post(&address, "/stations", "").0, 404);
+    }
+
+    #[test]
+    fn decodes_the_query() {
+        assert_eq!(
+            query_param("q=jazz+fm&x=1", "q").as_deref(),
+            Some("jazz fm")
+        );
+        assert_eq!(query_param("x=1&q=caf%C3%A9", "q").as_deref(), Some("café"));
+        assert_eq!(query_param("q=100%", "q").as_deref(), Some("100%"));
+        assert_eq!(query_param("q=%zz", "q").as_deref(), Some("%zz"));
+        assert_eq!(query_param("x=1", "q"), None);
+    }
+}
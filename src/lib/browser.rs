@@ -1,30 +1,206 @@
+use std::cell::RefCell;
 use std::error::Error;
+use std::fmt;
 use std::rc::Rc;
 
+use crate::directory::{self, StationDirectory};
+use crate::query::{self, Query};
+use crate::radio_browser::StationSearch;
 use crate::{Config, station::Station};
-use inquire::{Autocomplete, Text, error::InquireError};
-use radiobrowser::{ApiCountry, ApiStation, StationOrder, blocking::RadioBrowserAPI};
+use clap::ValueEnum;
+use inquire::{Autocomplete, Select, Text, error::InquireError};
+use log::debug;
+use radiobrowser::ApiStation;
+use serde::Deserialize;
 
-pub type StationCache = Rc<Vec<ApiStation>>;
+/// The stations listed first, to browse them again without downloading them
+#[derive(Debug, Clone, Default)]
+pub struct StationCache {
+    pub stations: Rc<Vec<ApiStation>>,
+    /// Where the page after them starts, if there are more. Not their number, as other
+    /// directories may have added stations to the first page.
+    next_offset: Option<usize>,
+}
+
+/// Suggestion that loads the next page of stations
+const MORE_STATIONS: &str = "… more stations";
+
+/// How the stations are sorted, the best first
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    /// Most listened to
+    #[default]
+    Clickcount,
+    Votes,
+    /// Most listened to lately
+    Clicktrend,
+    Bitrate,
+    Name,
+    Random,
+    /// Working in the last check
+    Lastcheckok,
+}
+
+impl Order {
+    /// How Radio Browser calls it
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Order::Clickcount => "clickcount",
+            Order::Votes => "votes",
+            Order::Clicktrend => "clicktrend",
+            Order::Bitrate => "bitrate",
+            Order::Name => "name",
+            Order::Random => "random",
+            Order::Lastcheckok => "lastcheckok",
+        }
+    }
+
+    /// Whether the API lists the best first without reversing it: names go A to Z, and a
+    /// random order is the same either way. The counts and rates need reversing.
+    fn ascending(self) -> bool {
+        matches!(self, Order::Name | Order::Random)
+    }
+}
+
+/// How the stations of Radio Browser are listed, the `search` section of the config
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub order: Order,
+
+    /// The worst first
+    pub reverse: bool,
+
+    /// Stations downloaded at a time, 0 for all of them at once
+    pub limit: usize,
+
+    /// Stations skipped from the start of the list
+    pub offset: usize,
+
+    /// Leave out the stations that didn't work in the last check
+    pub hidebroken: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            order: Order::default(),
+            reverse: false,
+            limit: 500,
+            offset: 0,
+            hidebroken: true,
+        }
+    }
+}
+
+/// A search of stations, downloaded a page at a time
+struct Pages {
+    search: StationSearch,
+    offset: usize,
+    limit: usize,
+}
+
+impl Pages {
+    /// The next page, and whether there may be more after it
+    fn next(
+        &mut self,
+        directory: &dyn StationDirectory,
+    ) -> Result<(Vec<ApiStation>, bool), Box<dyn Error>> {
+        if self.limit == 0 {
+            return Ok((directory.search(&self.search)?, false));
+        }
+
+        let search = self.search.clone().offset(self.offset).limit(self.limit);
+        let page = directory.search(&search)?;
+        self.offset += self.limit;
+
+        // Other directories may add stations to the first page
+        let more = page.len() >= self.limit;
+        Ok((page, more))
+    }
+}
+
+/// What the stations can be browsed by
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Tags,
+    Languages,
+    Codecs,
+    /// States or regions, of the configured country if there is one
+    #[value(alias = "regions")]
+    States,
+}
+
+impl Category {
+    pub const ALL: [Category; 4] = [
+        Category::Tags,
+        Category::Languages,
+        Category::Codecs,
+        Category::States,
+    ];
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Category::Tags => write!(f, "Tags"),
+            Category::Languages => write!(f, "Languages"),
+            Category::Codecs => write!(f, "Codecs"),
+            Category::States => write!(f, "States and regions"),
+        }
+    }
+}
+
+/// The stations sharing a tag, language, codec or region
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    /// Country of a state
+    #[serde(default)]
+    pub country: Option<String>,
+    pub stationcount: u32,
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if let Some(country) = &self.country {
+            write!(f, ", {}", country)?;
+        }
+
+        write!(f, " ({} stations)", self.stationcount)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Stations {
-    stations: StationCache,
+    stations: Rc<Vec<ApiStation>>,
+    /// Whether there are more stations to load
+    more: bool,
+    /// What was typed, to keep it when loading more stations
+    input: Rc<RefCell<String>>,
 }
 
 impl Autocomplete for Stations {
     fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
-        let suggestions: Vec<String> = self
+        input.clone_into(&mut self.input.borrow_mut());
+
+        // Nothing to suggest until the query makes sense
+        let Ok(query) = Query::parse(input) else {
+            return Ok(Vec::new());
+        };
+
+        let mut suggestions: Vec<String> = self
             .stations
             .iter()
-            .filter_map(|station| {
-                if station.name.to_lowercase().contains(&input.to_lowercase()) {
-                    Some(station.name.clone())
-                } else {
-                    None
-                }
-            })
+            .filter(|station| query.matches(station))
+            .map(suggestion)
             .collect();
+        if self.more {
+            suggestions.push(MORE_STATIONS.to_string());
+        }
         Ok(suggestions)
     }
 
@@ -40,113 +216,781 @@ impl Autocomplete for Stations {
     }
 }
 
+/// How a station is suggested: its name and a badge with the codec and bitrate
+fn suggestion(station: &ApiStation) -> String {
+    match query::badge(station) {
+        b if b.is_empty() => station.name.clone(),
+        b => format!("{} {}", station.name, b),
+    }
+}
+
+/// The station directories of a session (the player, the daemon, the TUI...), connected the
+/// first time they're needed and then kept. Connecting looks up the Radio Browser servers
+/// and checks them, too slow to do for every station played.
+#[derive(Clone)]
+pub struct Directory {
+    config: Rc<Config>,
+    connected: Rc<RefCell<Option<Rc<dyn StationDirectory>>>>,
+}
+
+impl Directory {
+    pub fn new(config: Rc<Config>) -> Directory {
+        Directory {
+            config,
+            connected: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    /// Already connected to `directory`
+    pub fn with(config: Rc<Config>, directory: Rc<dyn StationDirectory>) -> Directory {
+        Directory {
+            config,
+            connected: Rc::new(RefCell::new(Some(directory))),
+        }
+    }
+
+    /// The directory, connecting to it unless it was already. A failure is tried again
+    /// the next time.
+    fn get(&self) -> Result<Rc<dyn StationDirectory>, Box<dyn Error>> {
+        if let Some(directory) = self.connected.borrow().as_ref() {
+            return Ok(directory.clone());
+        }
+
+        let directory: Rc<dyn StationDirectory> = directory::connect(&self.config)?.into();
+        *self.connected.borrow_mut() = Some(directory.clone());
+
+        Ok(directory)
+    }
+
+    /// A browser of the stations, starting with `cached_stations` if there are some
+    /// (and else the first page)
+    pub fn browser(
+        &self,
+        cached_stations: Option<StationCache>,
+    ) -> Result<(Browser, StationCache), Box<dyn Error>> {
+        Ok(Browser::with_directory(
+            self.get()?,
+            self.config.clone(),
+            cached_stations,
+        ))
+    }
+
+    /// Finds a station in the config, or else in the directory
+    pub fn find_station(&self, name: &str) -> Option<Station> {
+        if let Some(station) = self.config.get_station(name) {
+            return Some(station);
+        }
+
+        // Without downloading the first page of stations, just to find one
+        let (browser, _) = self.browser(Some(StationCache::default())).ok()?;
+        browser.get_station(name.to_string()).ok()
+    }
+
+    /// Reports that `station` is played if it comes from the directory and the config allows
+    /// it, and returns it with the URL the directory gives (or as it was if that fails)
+    pub fn report_click(&self, station: Station) -> Station {
+        if !self.config.report_clicks {
+            return station;
+        }
+        let Some(uuid) = station.uuid.clone() else {
+            return station;
+        };
+
+        match self.get().and_then(|directory| directory.click(&uuid)) {
+            Ok(url) => Station { url, ..station },
+            Err(e) => {
+                debug!("Could not report the click on {}: {}", station.station, e);
+                station
+            }
+        }
+    }
+
+    /// Votes for `station` in Radio Browser. Only the stations that came from it have the id
+    /// needed: a name could be shared by several of them, and the rest aren't there at all.
+    pub fn vote(&self, station: &Station) -> Result<String, Box<dyn Error>> {
+        let uuid = match &station.uuid {
+            Some(uuid) if !station.is_local() => uuid,
+            _ => return Err("Only stations of Radio Browser can be voted for".into()),
+        };
+
+        self.get()?.vote(uuid)
+    }
+}
+
 pub struct Browser {
-    api: RadioBrowserAPI,
+    directory: Rc<dyn StationDirectory>,
     config: Rc<Config>,
-    stations: StationCache,
+    stations: Rc<Vec<ApiStation>>,
+    /// The rest of the stations, if they haven't all been downloaded
+    pages: Option<Pages>,
 }
 
 impl Browser {
-    pub fn new(
+    /// A browser of the stations of `directory`
+    pub fn with_directory(
+        directory: Rc<dyn StationDirectory>,
         config: Rc<Config>,
         cached_stations: Option<StationCache>,
-    ) -> Result<(Browser, StationCache), Box<dyn Error>> {
-        let api = match RadioBrowserAPI::new() {
-            Ok(r) => r,
-            Err(e) => return Err(e),
-        };
-
-        let stations = cached_stations.unwrap_or_else(|| {
-            Rc::new(if let Some(code) = &config.country_code {
-                match api
-                    .get_stations()
-                    .countrycode(code)
-                    .order(StationOrder::Clickcount)
-                    .send()
-                {
-                    Ok(s) => s,
-                    Err(_e) => Vec::new(),
+    ) -> (Browser, StationCache) {
+        let mut browser = Browser {
+            directory,
+            config,
+            stations: Rc::new(Vec::new()),
+            pages: None,
+        };
+
+        let mut search = browser.station_search();
+        if let Some(code) = browser.config.country() {
+            search = search.countrycode(code);
+        }
+
+        match cached_stations {
+            Some(cache) => {
+                // Carry on after them
+                if let Some(offset) = cache.next_offset {
+                    browser.pages = Some(Pages {
+                        search,
+                        offset,
+                        limit: browser.config.search.limit,
+                    });
                 }
-            } else {
-                match api.get_stations().order(StationOrder::Clickcount).send() {
-                    Ok(s) => s,
-                    Err(_e) => Vec::new(),
+                browser.stations = cache.stations;
+            }
+            None => {
+                if let Err(e) = browser.list(search) {
+                    debug!("Could not download the stations: {}", e);
                 }
-            })
+            }
+        }
+
+        let cache = StationCache {
+            stations: browser.stations.clone(),
+            next_offset: browser.pages.as_ref().map(|p| p.offset),
+        };
+        (browser, cache)
+    }
+
+    /// A search of stations sorted and filtered as the config says
+    fn station_search(&self) -> StationSearch {
+        let options = &self.config.search;
+
+        StationSearch::default()
+            .order(options.order)
+            .reverse(options.order.ascending() == options.reverse)
+            .hidebroken(options.hidebroken)
+    }
+
+    /// Replaces the stations with the first page of `search`
+    fn list(&mut self, search: StationSearch) -> Result<(), Box<dyn Error>> {
+        let options = &self.config.search;
+
+        self.stations = Rc::new(Vec::new());
+        self.pages = Some(Pages {
+            search,
+            offset: options.offset,
+            limit: options.limit,
         });
 
-        Ok((
-            Browser {
-                api,
-                config,
-                stations: stations.clone(),
-            },
-            stations,
-        ))
+        self.load_more()
     }
 
-    pub fn get_countries() -> Result<Vec<ApiCountry>, Box<dyn Error>> {
-        let api = match RadioBrowserAPI::new() {
-            Ok(r) => r,
-            Err(e) => return Err(e),
+    /// Downloads the next page of stations, if there is one
+    pub fn load_more(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(pages) = &mut self.pages else {
+            return Ok(());
         };
 
-        api.get_countries().send()
+        let (page, more) = pages.next(self.directory.as_ref())?;
+        if !more {
+            self.pages = None;
+        }
+
+        Rc::make_mut(&mut self.stations).extend(page);
+        Ok(())
+    }
+
+    /// Whether some stations haven't been downloaded yet
+    pub fn has_more(&self) -> bool {
+        self.pages.is_some()
     }
 
+    /// Tags, languages, codecs or states, the ones with more stations first
+    pub fn groups(&mut self, category: Category) -> Result<Vec<Group>, Box<dyn Error>> {
+        let country = match category {
+            Category::States => self.country_name()?,
+            _ => None,
+        };
+        let mut groups =
+            self.directory
+                .groups(category, country.as_deref(), self.config.search.hidebroken)?;
+
+        groups.retain(|g| !g.name.trim().is_empty() && g.stationcount > 0);
+        groups.sort_by_key(|g| std::cmp::Reverse(g.stationcount));
+
+        Ok(groups)
+    }
+
+    /// Searches among the stations of a tag, language, codec or state
+    /// instead of the stations of the country
+    pub fn list_group(&mut self, category: Category, group: &Group) -> Result<(), Box<dyn Error>> {
+        let search = self.station_search();
+
+        let search = match category {
+            Category::Tags => search.tag(&group.name).tag_exact(true),
+            Category::Languages => search.language(&group.name).language_exact(true),
+            Category::Codecs => search.codec(&group.name),
+            Category::States => {
+                let search = search.state(&group.name).state_exact(true);
+
+                match &group.country {
+                    Some(country) => search.country(country).country_exact(true),
+                    None => search,
+                }
+            }
+        };
+
+        self.list(search)
+    }
+
+    /// Name of the configured country, as Radio Browser calls it
+    fn country_name(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let Some(code) = self.config.country() else {
+            return Ok(None);
+        };
+
+        Ok(self
+            .directory
+            .countries()?
+            .into_iter()
+            .find(|c| c.iso_3166_1.eq_ignore_ascii_case(code))
+            .map(|c| c.name))
+    }
+
+    /// Stations called `name`, or else whose name contains it, the best first
+    fn find_stations(&self, name: &str) -> Result<Vec<ApiStation>, InquireError> {
+        let mut search = self.station_search().name(name);
+        if let Some(code) = self.config.country() {
+            search = search.countrycode(code);
+        }
+
+        let found = match self.directory.search(&search) {
+            Ok(s) => s,
+            Err(_e) => return Err(InquireError::OperationInterrupted),
+        };
+
+        let exact: Vec<ApiStation> = found
+            .iter()
+            .filter(|s| s.name.trim().eq_ignore_ascii_case(name.trim()))
+            .cloned()
+            .collect();
+
+        match exact.is_empty() {
+            true => Ok(found.into_iter().take(1).collect()),
+            false => Ok(exact),
+        }
+    }
+
+    /// The best station called `name`, without asking
     pub fn get_station(&self, name: String) -> Result<Station, InquireError> {
-        if let Some(code) = self.config.country_code.clone() {
-            return match self.api.get_stations().name(name).countrycode(code).send() {
-                Ok(s) => match s.get(0) {
-                    Some(x) => Ok(Station {
-                        station: x.name.clone(),
-                        url: x.url.clone(),
-                    }),
-                    None => Err(InquireError::InvalidConfiguration(
-                        "Radio station does not exist".to_string(),
-                    )),
-                },
-                Err(_e) => Err(InquireError::OperationInterrupted),
-            };
-        } else {
-            return match self.api.get_stations().name(name).send() {
-                Ok(s) => match s.get(0) {
-                    Some(x) => Ok(Station {
-                        station: x.name.clone(),
-                        url: x.url.clone(),
-                    }),
-                    None => Err(InquireError::InvalidConfiguration(
-                        "Radio station does not exist".to_string(),
-                    )),
-                },
-                Err(_e) => Err(InquireError::OperationInterrupted),
-            };
-        }
-    }
-
-    fn search_station(&self, message: &str, placeholder: &str) -> Result<String, InquireError> {
+        match self.find_stations(&name)?.first() {
+            Some(x) => Ok(to_station(x)),
+            None => Err(InquireError::InvalidConfiguration(
+                "Radio station does not exist".to_string(),
+            )),
+        }
+    }
+
+    /// The station called `name`, asking which one if several are
+    pub fn choose_station(&self, name: String) -> Result<Station, InquireError> {
+        let found = self.find_stations(&name)?;
+        choose(found).map(|x| to_station(&x))
+    }
+
+    /// Stations matching `query` (see `Query`) of the downloaded list, or else of
+    /// Radio Browser when not all of them were downloaded
+    pub fn search(&self, query: &str) -> Vec<Station> {
+        let Ok(query) = Query::parse(query) else {
+            return Vec::new();
+        };
+
+        let mut found: Vec<ApiStation> = self
+            .stations
+            .iter()
+            .filter(|s| query.matches(s))
+            .cloned()
+            .collect();
+        if found.is_empty() && self.has_more() {
+            found = self.search_api(&query).unwrap_or_default();
+        }
+
+        found.iter().map(to_station).collect()
+    }
+
+    fn search_station(
+        &self,
+        message: &str,
+        placeholder: &str,
+        input: Rc<RefCell<String>>,
+    ) -> Result<String, InquireError> {
         let max_lines = match self.config.max_lines {
             Some(x) => x,
             None => Text::DEFAULT_PAGE_SIZE,
         };
 
+        let initial = input.borrow().clone();
         Text::new(message)
+            .with_initial_value(&initial)
             .with_placeholder(placeholder)
+            .with_help_message("Filters: codec:aac bitrate>=128 lang:english tag:jazz country:es")
             // Deprecated: need to change to `with_autosuggester`
             // But for that, ApiStation needs to implement the Clone trait
             .with_autocomplete(Stations {
                 stations: self.stations.clone(),
+                more: self.has_more(),
+                input,
             })
             .with_page_size(max_lines)
             .prompt()
     }
 
-    pub fn prompt(self) -> Result<Station, InquireError> {
-        let station = self.search_station("Search for a station: ", "Names or keywords");
+    pub fn prompt(mut self) -> Result<Station, InquireError> {
+        let input = Rc::new(RefCell::new(String::new()));
+        let answer = loop {
+            let answer =
+                self.search_station("Search for a station: ", "Names or keywords", input.clone())?;
+            if answer != MORE_STATIONS {
+                break answer;
+            }
+
+            if let Err(e) = self.load_more() {
+                debug!("{}", e);
+                return Err(InquireError::OperationInterrupted);
+            }
+        };
+
+        // A suggestion, or else the best match of the query: the stations browsed may not
+        // be of the configured country, so they are taken from the list if possible
+        let mut chosen: Vec<ApiStation> = self
+            .stations
+            .iter()
+            .filter(|x| suggestion(x) == answer)
+            .cloned()
+            .collect();
+        if chosen.is_empty() {
+            chosen = self
+                .stations
+                .iter()
+                .filter(|x| x.name == answer)
+                .cloned()
+                .collect();
+        }
+
+        if chosen.is_empty() {
+            let query = Query::parse(&answer)
+                .map_err(|e| InquireError::InvalidConfiguration(e.to_string()))?;
+
+            match self.stations.iter().find(|x| query.matches(x)) {
+                Some(x) => chosen.push(x.clone()),
+                None if query.has_filters() => {
+                    chosen.extend(self.search_api(&query)?.into_iter().take(1))
+                }
+                None => return self.choose_station(answer),
+            }
+        }
+
+        choose(chosen).map(|x| to_station(&x))
+    }
+
+    /// Stations of Radio Browser matching `query`, in the configured country
+    /// unless the query says otherwise
+    pub fn search_api(&self, query: &Query) -> Result<Vec<ApiStation>, InquireError> {
+        let mut search = self.station_search();
+        if self.config.search.limit > 0 {
+            search = search.limit(self.config.search.limit);
+        }
+
+        if let Some(code) = self.config.country()
+            && query.country.is_none()
+        {
+            search = search.countrycode(code);
+        }
+
+        match self.directory.search(&query.apply(search)) {
+            Ok(stations) => Ok(stations.into_iter().filter(|s| query.matches(s)).collect()),
+            Err(_e) => Err(InquireError::OperationInterrupted),
+        }
+    }
+
+    /// Tells the directory that the station with id `uuid` is being played,
+    /// and returns the URL it should be played at
+    pub fn click(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        self.directory.click(uuid)
+    }
+
+    /// Votes for the station with id `uuid`, returning what the directory answers
+    pub fn vote(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        self.directory.vote(uuid)
+    }
+}
+
+fn to_station(station: &ApiStation) -> Station {
+    Station {
+        station: station.name.clone(),
+        url: station.url.clone(),
+        uuid: Some(station.stationuuid.clone()),
+        ..Default::default()
+    }
+}
+
+/// One of several stations with the same name
+struct Candidate(ApiStation);
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let station = &self.0;
+        write!(f, "{}", station.name)?;
+
+        for detail in [
+            station.country.clone(),
+            query::badge(station),
+            station.homepage.clone(),
+        ] {
+            if !detail.trim().is_empty() {
+                write!(f, " · {}", detail.trim())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The only station, or the one the user chooses when several have the same name
+fn choose(mut stations: Vec<ApiStation>) -> Result<ApiStation, InquireError> {
+    if stations.len() <= 1 {
+        return stations.pop().ok_or(InquireError::InvalidConfiguration(
+            "Radio station does not exist".to_string(),
+        ));
+    }
+
+    let message = format!(
+        "{} stations are called \"{}\", which one?",
+        stations.len(),
+        stations[0].name
+    );
+    let candidates: Vec<Candidate> = stations.into_iter().map(Candidate).collect();
+
+    match Select::new(&message, candidates).prompt() {
+        Ok(c) => Ok(c.0),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directory::{MemoryDirectory, MergedDirectory, new_station};
+    use crate::station::StationKind;
+    use crate::testing::{self, TestServer, api_station, fixture, json, response};
+    use serde_json::json;
+
+    /// A browser of the stations of the fixture, in `country` and `limit` at a time
+    fn offline(country: Option<&str>, limit: usize) -> Browser {
+        let mut config = testing::config(&[]);
+        config.country_code = country.map(str::to_string);
+        config.search.limit = limit;
+
+        let stations = MemoryDirectory::load(&fixture("stations.json")).unwrap();
+        Browser::with_directory(Rc::new(stations), Rc::new(config), None).0
+    }
+
+    fn names(stations: Vec<Station>) -> Vec<String> {
+        stations.into_iter().map(|s| s.station).collect()
+    }
+
+    #[test]
+    fn lists_the_best_working_stations_first() {
+        let browser = offline(None, 500);
+
+        let uuids: Vec<&str> = browser
+            .stations
+            .iter()
+            .map(|s| s.stationuuid.as_str())
+            .collect();
+        assert_eq!(uuids, ["antenne", "smooth", "jazz-fm", "clasica", "fip"]);
+        assert!(!browser.has_more());
+
+        let (browser, stations) = Browser::with_directory(
+            Rc::new(MemoryDirectory::default()),
+            Rc::new(testing::config(&[])),
+            Some(StationCache {
+                stations: browser.stations.clone(),
+                next_offset: None,
+            }),
+        );
+        assert_eq!(stations.stations.len(), 5);
+        assert_eq!(browser.stations.len(), 5);
+    }
+
+    #[test]
+    fn carries_on_after_the_cached_stations() {
+        let mut config = testing::config(&[]);
+        config.country_code = None;
+        config.search.limit = 2;
+        let icecast = MemoryDirectory::new(vec![
+            new_station("icecast:rock", "Rock", "http://rock.example/live"),
+            new_station("icecast:pop", "Pop", "http://pop.example/live"),
+        ]);
+        let directory: Rc<dyn StationDirectory> = Rc::new(MergedDirectory::new(vec![
+            (
+                "radio-browser",
+                Box::new(MemoryDirectory::load(&fixture("stations.json")).unwrap()),
+            ),
+            ("icecast", Box::new(icecast)),
+        ]));
+
+        let (browser, cache) =
+            Browser::with_directory(directory.clone(), Rc::new(config.clone()), None);
+        assert_eq!(browser.stations.len(), 4);
+        assert_eq!(cache.next_offset, Some(2));
+
+        let (mut browser, _) = Browser::with_directory(directory, Rc::new(config), Some(cache));
+        assert!(browser.has_more());
+        browser.load_more().unwrap();
+        let uuids: Vec<&str> = browser
+            .stations
+            .iter()
+            .map(|s| s.stationuuid.as_str())
+            .collect();
+        assert_eq!(
+            uuids,
+            [
+                "antenne",
+                "smooth",
+                "icecast:pop",
+                "icecast:rock",
+                "jazz-fm",
+                "clasica"
+            ]
+        );
+    }
+
+    #[test]
+    fn searches_the_stations_listed() {
+        let browser = offline(None, 500);
+
+        assert_eq!(
+            names(browser.search("jazz")),
+            ["Smooth Jazz Global", "Jazz FM", "jazz radio"]
+        );
+        assert_eq!(
+            names(browser.search("codec:aac")),
+            ["Smooth Jazz Global", "jazz radio"]
+        );
+        assert_eq!(
+            names(browser.search("jazz bitrate>=128")),
+            ["Smooth Jazz Global", "Jazz FM"]
+        );
+        assert!(browser.search("broken").is_empty());
+        assert!(browser.search("bitrate>=fast").is_empty());
+    }
+
+    #[test]
+    fn searches_the_directory_beyond_the_first_page() {
+        let mut browser = offline(None, 2);
+        assert_eq!(browser.stations.len(), 2);
+        assert!(browser.has_more());
+
+        assert_eq!(
+            names(browser.search("radio")),
+            ["Radio Clásica", "jazz radio"]
+        );
+
+        browser.load_more().unwrap();
+        browser.load_more().unwrap();
+        assert_eq!(browser.stations.len(), 5);
+        assert!(!browser.has_more());
+    }
+
+    #[test]
+    fn gets_stations_by_name() {
+        let browser = offline(None, 500);
+
+        // The exact name, whatever the case
+        let station = browser.get_station(String::from("JAZZ RADIO")).unwrap();
+        assert_eq!(station.station, "jazz radio");
+        assert_eq!(station.uuid.as_deref(), Some("fip"));
+
+        // Else the most listened to
+        let station = browser.get_station(String::from("jazz")).unwrap();
+        assert_eq!(station.station, "Smooth Jazz Global");
+
+        assert!(browser.get_station(String::from("Nothing")).is_err());
+        // Not working in the last check
+        assert!(browser.get_station(String::from("Broken Jazz")).is_err());
+    }
+
+    #[test]
+    fn gets_stations_of_the_configured_country() {
+        let browser = offline(Some("FR"), 500);
+        assert_eq!(browser.stations.len(), 1);
+
+        let station = browser.get_station(String::from("jazz")).unwrap();
+        assert_eq!(station.station, "jazz radio");
+        assert!(browser.get_station(String::from("Rock Antenne")).is_err());
+    }
+
+    #[test]
+    fn plays_stations_of_a_memory_directory() {
+        let config = Rc::new(testing::config(&[("Mine", "http://mine.example/live")]));
+        let stations = MemoryDirectory::load(&fixture("stations.json")).unwrap();
+        let directory = Directory::with(config, Rc::new(stations));
+
+        assert_eq!(directory.find_station("Mine").unwrap().uuid, None);
+        assert!(directory.find_station("Jazz FM").is_none());
+
+        let mut config = testing::config(&[]);
+        config.country_code = None;
+        let stations = MemoryDirectory::load(&fixture("stations.json")).unwrap();
+        let directory = Directory::with(Rc::new(config), Rc::new(stations));
+
+        let jazz = directory.find_station("Jazz FM").unwrap();
+        assert_eq!(
+            directory.report_click(jazz).url,
+            "http://jazzfm.example/live"
+        );
+    }
+
+    /// Radio Browser as far as the browser goes: a few stations, their URLs and votes
+    fn radio_browser() -> TestServer {
+        TestServer::start(|_, request| {
+            let path = request.path.as_str();
+
+            match path {
+                "/json/stats" => json(&json!({"stations": 3})),
+                "/json/stations/search" => json(&json!([
+                    api_station("1", "Jazz FM", "http://jazz.example/old"),
+                    api_station("2", "Smooth Jazz", "http://smooth.example/live"),
+                    api_station("3", "Rock Radio", "http://rock.example/live"),
+                ])),
+                _ if path.starts_with("/json/url/") => json(&json!({
+                    "ok": true,
+                    "message": "retrieved station url",
+                    "url": format!("http://resolved.example/{}", &path[10..]),
+                })),
+                _ if path.starts_with("/json/vote/") => json(&json!({
+                    "ok": true,
+                    "message": "voted for station successfully",
+                })),
+                _ => response("404 Not Found", &[], b""),
+            }
+        })
+    }
+
+    fn directory(server: &TestServer, report_clicks: bool) -> Directory {
+        let mut config = testing::config(&[("Mine", "http://mine.example/live")]);
+        config.api_servers = vec![server.url.clone()];
+        config.report_clicks = report_clicks;
+
+        Directory::new(Rc::new(config))
+    }
+
+    #[test]
+    fn connects_once_for_the_whole_session() {
+        let server = radio_browser();
+        let directory = directory(&server, true);
+        assert!(server.requests().is_empty());
+
+        let (browser, cache) = directory.browser(None).unwrap();
+        assert_eq!(cache.stations.len(), 3);
+        let names: Vec<String> = browser
+            .search("jazz")
+            .into_iter()
+            .map(|s| s.station)
+            .collect();
+        assert_eq!(names, ["Jazz FM", "Smooth Jazz"]);
+
+        let jazz = directory.find_station("Jazz FM").unwrap();
+        assert_eq!(jazz.uuid.as_deref(), Some("1"));
+        let mine = directory.find_station("Mine").unwrap();
+        assert_eq!(mine.uuid, None);
+
+        let playing = directory.report_click(jazz.clone());
+        assert_eq!(playing.url, "http://resolved.example/1");
+        assert_eq!(directory.report_click(mine).url, "http://mine.example/live");
+
+        assert_eq!(
+            directory.vote(&jazz).unwrap(),
+            "voted for station successfully"
+        );
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "/json/stats",
+                "/json/stations/search",
+                "/json/stations/search",
+                "/json/url/1",
+                "/json/vote/1",
+            ]
+        );
+    }
+
+    #[test]
+    fn clicks_are_only_reported_if_the_config_says_so() {
+        let server = radio_browser();
+        let directory = directory(&server, false);
+        let station = Station {
+            station: String::from("Jazz FM"),
+            url: String::from("http://jazz.example/old"),
+            uuid: Some(String::from("1")),
+            ..Default::default()
+        };
+
+        assert_eq!(directory.report_click(station.clone()), station);
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn connecting_is_tried_again_after_failing() {
+        let server = TestServer::scripted(vec![
+            response("503 Service Unavailable", &[], b""),
+            json(&json!({"stations": 3})),
+            json(&json!([api_station(
+                "1",
+                "Jazz FM",
+                "http://jazz.example/old"
+            )])),
+        ]);
+        let directory = directory(&server, true);
+
+        assert!(directory.browser(None).is_err());
+        let (_, cache) = directory.browser(None).unwrap();
+        assert_eq!(cache.stations[0].name, "Jazz FM");
+    }
+
+    #[test]
+    fn only_votes_for_stations_of_radio_browser() {
+        let directory = Directory::new(Rc::new(testing::config(&[])));
+        let podcast = Station {
+            station: String::from("Show: Episode 1"),
+            url: String::from("https://example.com/episode1.mp3"),
+            ..Default::default()
+        };
+        let music = Station {
+            station: String::from("My music"),
+            url: String::from("~/Music"),
+            kind: StationKind::Directory,
+            uuid: Some(String::from("960e57c5-0601-11e8-ae97-52543be04c81")),
+            ..Default::default()
+        };
 
-        match station {
-            Ok(s) => self.get_station(s.to_string()),
-            Err(e) => Err(e),
+        for station in [podcast, music] {
+            let e = directory.vote(&station).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "Only stations of Radio Browser can be voted for"
+            );
         }
     }
 }
@@ -1,14 +1,23 @@
-use clap::Parser;
+use crate::browser::{Category, Order};
+use crate::player::parse_volume;
+use crate::timer::{parse_duration, parse_time_of_day};
+use chrono::NaiveTime;
+use clap::{Args, Parser, Subcommand};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug, Clone)]
 #[clap(
     author,
     version,
     about,
-    long_about = "Note: When playing, all the keybindings of mpv can be used, and `q` is reserved for exiting the program"
+    long_about = "Note: When playing, `n`/`p` switch to the next/previous station, `-`/`+` change the volume, `m` mutes, `space` pauses, `f` saves the station to the config, `u` votes for it in Radio Browser, `i` shows what's playing, `z` cycles the sleep timer and `q` goes back to the menu"
 )]
 pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Commands>,
+
     /// Option: -u --url <URL>: Specifies an url to be played.
     #[clap(short, long, help = "Specifies an url to be played.")]
     pub url: Option<String>,
@@ -44,10 +53,18 @@ pub struct Cli {
     )]
     pub country_code: Option<String>,
 
+    /// Option: --country <NAME>: Search the stations of the country with (about) this name.
+    #[clap(
+        long,
+        conflicts_with = "country_code",
+        help = "Search the stations of this country, by its name (e.g. \"Spain\")."
+    )]
+    pub country: Option<String>,
+
     /// Flag: --list-countries: List all the available countries and country codes to put in the config.
     #[clap(
         long = "list-countries",
-        help = "List all the available countries and country codes to put in the config."
+        help = "List all the available countries, their codes to put in the config and how many stations they have."
     )]
     pub list_countries: bool,
 
@@ -58,6 +75,116 @@ pub struct Cli {
     )]
     pub no_station_cache: bool,
 
+    /// Option: --order <ORDER>: How to sort the stations found on the internet.
+    #[clap(
+        long,
+        value_enum,
+        help = "How to sort the stations found on the internet, the best first."
+    )]
+    pub order: Option<Order>,
+
+    /// Flag: --reverse: The worst stations first.
+    #[clap(
+        long,
+        help = "Sort the stations found on the internet the worst first."
+    )]
+    pub reverse: bool,
+
+    /// Option: --limit <N>: Stations downloaded at a time.
+    #[clap(
+        long,
+        help = "Stations downloaded at a time, 0 for all of them (500 by default)."
+    )]
+    pub limit: Option<usize>,
+
+    /// Option: --offset <N>: Stations skipped from the start of the list.
+    #[clap(long, help = "Stations skipped from the start of the list.")]
+    pub offset: Option<usize>,
+
+    /// Option: --hidebroken <BOOL>: Leave out the stations that didn't work in the last check.
+    #[clap(
+        long,
+        value_name = "BOOL",
+        help = "Leave out the stations that didn't work in the last check (true by default)."
+    )]
+    pub hidebroken: Option<bool>,
+
+    /// Option: --api-server <URL>: Radio Browser server to use, instead of the ones of the config.
+    #[clap(
+        long = "api-server",
+        value_name = "URL",
+        help = "Radio Browser server to use (e.g. https://de1.api.radio-browser.info), instead of the ones of the config. Can be repeated, the next ones being used if the first one fails."
+    )]
+    pub api_server: Vec<String>,
+
+    /// Option: --sleep <DURATION>: Stop playing after the given time (e.g. 30m, 1h30m).
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        conflicts_with = "until",
+        help = "Stop playing after the given time (e.g. 30m, 1h30m, 90s)."
+    )]
+    pub sleep: Option<Duration>,
+
+    /// Option: --until <HH:MM>: Stop playing at the given time of the day.
+    #[clap(
+        long,
+        value_parser = parse_time_of_day,
+        help = "Stop playing at the given time of the day (e.g. 23:30)."
+    )]
+    pub until: Option<NaiveTime>,
+
+    /// Option: --fade <DURATION>: Fade the volume out during the last part of the sleep timer.
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Fade the volume out during the last part of the sleep timer (e.g. 2m)."
+    )]
+    pub fade: Option<Duration>,
+
+    /// Option: --volume <0-100>: Volume to start playing at.
+    #[clap(
+        long,
+        value_parser = parse_volume,
+        help = "Volume to start playing at, from 0 to 100. Defaults to the last one used."
+    )]
+    pub volume: Option<f64>,
+
+    /// Flag: --normalize: Even out the loudness of the stations.
+    #[clap(
+        long,
+        help = "Even out the loudness of the stations (mpv's loudnorm filter)."
+    )]
+    pub normalize: bool,
+
+    /// Option: --preset <NAME>: Audio filter preset to play every station with.
+    #[clap(
+        long,
+        help = "Audio filter preset to play with (e.g. bass-boost, speech, night), instead of the stations' own."
+    )]
+    pub preset: Option<String>,
+
+    /// Option: --audio-device <DEVICE>: Audio output to play on.
+    #[clap(
+        long = "audio-device",
+        help = "Audio output to play on, instead of the configured one (see the devices command)."
+    )]
+    pub audio_device: Option<String>,
+
+    /// Flag: --tui: Full-screen interface.
+    #[clap(
+        long,
+        help = "Full-screen interface with the stations, search and what's playing."
+    )]
+    pub tui: bool,
+
+    /// Option: --log-titles <FILE>: Append the songs announced by the station to a file.
+    #[clap(
+        long = "log-titles",
+        help = "Append the song titles announced by the stations, with the time, to a file."
+    )]
+    pub log_titles: Option<PathBuf>,
+
     /// Show extra info
     #[clap(flatten)]
     pub verbose: clap_verbosity_flag::Verbosity,
@@ -66,3 +193,289 @@ pub struct Cli {
     #[structopt(short, long)]
     pub debug: bool,
 }
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Wait until the given time and start playing, like an alarm clock
+    Alarm(AlarmArgs),
+
+    /// Save the stream of a station to disk
+    Record(RecordArgs),
+
+    /// Stay in the foreground performing the recordings of the `schedule` in the config
+    Scheduler(SchedulerArgs),
+
+    /// Play stations in the background, controlled through a local socket (see `ctl`)
+    Daemon(DaemonArgs),
+
+    /// Send a command to a running daemon
+    Ctl(CtlArgs),
+
+    /// Play stations controlled through an HTTP/JSON API
+    Serve(ServeArgs),
+
+    /// Pull a station once and serve it to several players of the network
+    Relay(RelayArgs),
+
+    /// List the audio outputs, or pick the one to play on
+    Devices(DevicesArgs),
+
+    /// Browse the stations of Radio Browser by tag, language, codec or region
+    Browse(BrowseArgs),
+
+    /// Vote for a station in Radio Browser
+    Vote(VoteArgs),
+
+    /// List the episodes of a podcast of the config, or play one from where it was left
+    Podcast(PodcastArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AlarmArgs {
+    /// Argument: <HH:MM>: Time at which the alarm rings.
+    #[clap(
+        value_parser = parse_time_of_day,
+        help = "Time at which the alarm rings (e.g. 07:00). If not present, the schedule in the config is used."
+    )]
+    pub time: Option<NaiveTime>,
+
+    /// Option: -s --station <station name>: Station to wake up to.
+    #[clap(short, long, help = "Specifies the name of the station to wake up to.")]
+    pub station: Option<String>,
+
+    /// Option: --ramp <DURATION>: Raise the volume gradually during this time.
+    #[clap(
+        long,
+        value_parser = parse_duration,
+        help = "Raise the volume gradually from silence during this time (e.g. 5m)."
+    )]
+    pub ramp: Option<Duration>,
+
+    /// Option: --fallback <FILE>: Local file played if the station can't be reached.
+    #[clap(
+        long,
+        help = "Local audio file to play if the station can't be reached."
+    )]
+    pub fallback: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RecordArgs {
+    /// Option: -s --station <station name>: Station to record.
+    #[clap(short, long, help = "Specifies the name of the station to record.")]
+    pub station: Option<String>,
+
+    /// Option: -u --url <URL>: Stream to record.
+    #[clap(
+        short,
+        long,
+        conflicts_with = "station",
+        help = "Specifies an url to be recorded."
+    )]
+    pub url: Option<String>,
+
+    /// Option: -d --duration <DURATION>: How long to record.
+    #[clap(
+        short,
+        long,
+        value_parser = parse_duration,
+        help = "How long to record (e.g. 2h, 45m). If not present, records until stopped."
+    )]
+    pub duration: Option<Duration>,
+
+    /// Option: -o --output <FILE>: Where to save the recording.
+    #[clap(
+        short,
+        long,
+        help = "Where to save the recording. The extension is added from the stream type if missing."
+    )]
+    pub output: Option<PathBuf>,
+
+    /// Flag: --split: Start a new file every time the song changes.
+    #[clap(
+        long,
+        help = "Start a new file every time the stream announces a new title."
+    )]
+    pub split: bool,
+
+    /// Flag: --play: Listen to the station while recording it.
+    #[clap(long, help = "Listen to the station while recording it.")]
+    pub play: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SchedulerArgs {
+    /// Option: --summary <FILE>: Where to append the outcome of each recording.
+    #[clap(
+        long,
+        help = "Where to append the outcome of each recording. Defaults to $XDG_DATA_HOME/radio-cli/scheduler.log"
+    )]
+    pub summary: Option<PathBuf>,
+
+    /// Flag: --list: Show the upcoming recordings and exit.
+    #[clap(long, help = "Show the upcoming recordings and exit.")]
+    pub list: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DaemonArgs {
+    /// Option: --socket <FILE>: Where to listen for commands.
+    #[clap(
+        long,
+        help = "Where to listen for commands. Defaults to $XDG_RUNTIME_DIR/radio-cli.sock"
+    )]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// Option: --bind <ADDRESS>: Address and port to listen on.
+    #[clap(
+        long,
+        default_value = "127.0.0.1:7700",
+        help = "Address and port to listen on. Use 0.0.0.0 to accept other machines of the network."
+    )]
+    pub bind: SocketAddr,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RelayArgs {
+    /// Option: -s --station <station name>: Station to relay.
+    #[clap(short, long, help = "Specifies the name of the station to relay.")]
+    pub station: Option<String>,
+
+    /// Option: -u --url <URL>: Stream to relay.
+    #[clap(
+        short,
+        long,
+        conflicts_with = "station",
+        help = "Specifies an url to be relayed."
+    )]
+    pub url: Option<String>,
+
+    /// Option: --bind <ADDRESS>: Address and port to listen on.
+    #[clap(
+        long,
+        default_value = "0.0.0.0:8000",
+        help = "Address and port to listen on."
+    )]
+    pub bind: SocketAddr,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct BrowseArgs {
+    /// Argument: <CATEGORY>: What to browse by.
+    #[clap(value_enum, help = "What to browse the stations by.")]
+    pub category: Category,
+
+    /// Argument: <NAME>: Tag, language, codec or region to choose a station of.
+    #[clap(help = "Tag, language, codec or region to choose a station of (e.g. jazz).")]
+    pub name: Option<String>,
+
+    /// Flag: --list: Only list them with their number of stations.
+    #[clap(
+        long,
+        conflicts_with = "name",
+        help = "Only list them, with their number of stations."
+    )]
+    pub list: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DevicesArgs {
+    /// Flag: --pick: Choose the audio output and save it in the config.
+    #[clap(long, help = "Choose the audio output and save it in the config.")]
+    pub pick: bool,
+
+    /// Option: -s --station <station name>: Station to show or choose the output of.
+    #[clap(
+        short,
+        long,
+        help = "Show or choose the output of this station of the config only."
+    )]
+    pub station: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct VoteArgs {
+    /// Option: -s --station <station name>: Station to vote for.
+    #[clap(
+        short,
+        long,
+        help = "Specifies the name of the station to vote for. If not present, you'll be asked."
+    )]
+    pub station: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PodcastArgs {
+    /// Argument: <podcast name>: Podcast of the config.
+    #[clap(help = "Name of the podcast, as in the config. If not present, you'll be asked.")]
+    pub name: Option<String>,
+
+    /// Flag: --list: List the episodes.
+    #[clap(long, help = "List the episodes, newest first, and exit")]
+    pub list: bool,
+
+    /// Flag: --latest: Play the newest episode.
+    #[clap(long, conflicts_with = "episode", help = "Play the newest episode")]
+    pub latest: bool,
+
+    /// Option: -e --episode <N>: Episode to play.
+    #[clap(
+        short,
+        long,
+        value_name = "N",
+        help = "Play the episode with this number in the list (1 is the newest)"
+    )]
+    pub episode: Option<usize>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CtlArgs {
+    /// Option: --socket <FILE>: Socket of the daemon.
+    #[clap(
+        long,
+        help = "Socket of the daemon. Defaults to $XDG_RUNTIME_DIR/radio-cli.sock"
+    )]
+    pub socket: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    pub command: CtlCommand,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CtlCommand {
+    /// Play a station of the config, or one found in Radio Browser
+    Play {
+        /// Argument: <station name>: Station to play.
+        #[clap(required = true, help = "Name of the station to play.")]
+        station: Vec<String>,
+    },
+
+    /// Stop playing
+    Stop,
+
+    /// Play the next station of the config
+    Next,
+
+    /// Play the previous station of the config
+    #[clap(alias = "prev")]
+    Previous,
+
+    /// Stop if playing, otherwise play the last station again
+    Toggle,
+
+    /// Show or change the volume
+    Volume {
+        /// Argument: <VOLUME>: New volume, or a change of it.
+        #[clap(
+            allow_hyphen_values = true,
+            help = "New volume from 0 to 100, or a change of it (+5, -5). If not present, the volume is shown."
+        )]
+        value: Option<String>,
+    },
+
+    /// Show what is playing
+    Status,
+}
@@ -1,17 +1,29 @@
 extern crate xdg;
 
+use crate::alarm::AlarmConfig;
+use crate::browser::SearchOptions;
+use crate::directory::DirectoryOptions;
 use crate::errors::{ConfigError, ConfigErrorCode};
 use crate::perror;
-use crate::station::Station;
+use crate::player::BUILTIN_PRESETS;
+use crate::podcast::Podcast;
+use crate::scheduler::ScheduledRecording;
+use crate::station::{self, Station};
+use crate::timer::deserialize_duration;
 use crate::version::Version;
 
 use colored::*;
-use serde::Deserialize;
+use log::warn;
 use serde::de::{Deserializer, Error as SeError, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::ser::PrettyFormatter;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
 use std::fmt::{Formatter, Result as ResultFmt};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::time::Duration;
 
 const _CONFIG_URL: &str = "https://raw.githubusercontent.com/margual56/radio-cli/main/config.json";
 
@@ -25,6 +37,60 @@ pub struct Config {
     pub country_code: Option<String>,
 
     pub data: Vec<Station>,
+
+    /// Volume to play at when none was used before
+    pub volume: Option<f64>,
+
+    /// Start at the volume used last time
+    #[serde(default = "default_true")]
+    pub remember_volume: bool,
+
+    /// Even out the loudness of the stations
+    #[serde(default)]
+    pub normalize: bool,
+
+    /// Audio output to play on, as listed by `radio-cli devices`
+    pub audio_device: Option<String>,
+
+    /// Audio filter presets (mpv `--af` chains) by name, on top of the built-in ones
+    #[serde(default)]
+    pub presets: HashMap<String, String>,
+
+    /// How the stations of Radio Browser are sorted and paged
+    #[serde(default)]
+    pub search: SearchOptions,
+
+    /// Directories searched for stations
+    #[serde(default)]
+    pub directories: DirectoryOptions,
+
+    /// Radio Browser servers, the first one that answers is used.
+    /// When empty, they are looked up in the DNS of radio-browser.info.
+    #[serde(default)]
+    pub api_servers: Vec<String>,
+
+    /// How long a Radio Browser server has to answer
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub api_timeout: Option<Duration>,
+
+    /// Tell Radio Browser when one of its stations is played (and get its current URL).
+    /// Off keeps what you listen to to yourself.
+    #[serde(default = "default_true")]
+    pub report_clicks: bool,
+
+    #[serde(default)]
+    pub alarm: AlarmConfig,
+
+    #[serde(default)]
+    pub schedule: Vec<ScheduledRecording>,
+
+    /// Podcasts, by their RSS or Atom feeds
+    #[serde(default)]
+    pub podcasts: Vec<Podcast>,
+
+    /// File the config was loaded from
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
 }
 
 impl Config {
@@ -65,11 +131,13 @@ impl Config {
             }
         }
 
-        let data: Config = match serde_json::from_str::<Config>(&config) {
+        let mut data: Config = match serde_json::from_str::<Config>(&config) {
             Ok(mut x) => {
+                x.path = Some(file.clone());
                 x.data.push(Station {
                     station: "Other".to_string(),
                     url: "".to_string(),
+                    ..Default::default()
                 });
 
                 x
@@ -83,6 +151,34 @@ impl Config {
             }
         };
 
+        if let Some(station) = data
+            .data
+            .iter()
+            .find(|s| s.preset.as_ref().is_some_and(|p| !data.has_preset(p)))
+        {
+            return Err(ConfigError {
+                code: ConfigErrorCode::ParseError,
+                message: format!("The station \"{}\" uses an unknown preset", station.station),
+                extra: format!(
+                    "{:?} is not one of {:?}",
+                    station.preset,
+                    data.preset_names()
+                ),
+            });
+        }
+
+        // A file or folder that is gone (an unplugged disk...) only leaves its station out
+        data.data.retain(|station| match station.check() {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "Leaving out \"{}\", it can't be played: {}",
+                    station.station, e
+                );
+                false
+            }
+        });
+
         Ok(data)
     }
 
@@ -131,6 +227,210 @@ impl Config {
         }
     }
 
+    /// Changes the config file on disk, keeping whatever radio-cli doesn't know about.
+    /// The loaded config is not updated.
+    pub fn edit_file<F>(&self, edit: F) -> Result<(), ConfigError>
+    where
+        F: FnOnce(&mut Map<String, Value>),
+    {
+        let path = match &self.path {
+            Some(p) => p,
+            None => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::OpenError,
+                    message: "The config was not loaded from a file".to_string(),
+                    extra: String::new(),
+                });
+            }
+        };
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(error) => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::ReadError,
+                    message: format!("Couldn't read the file {:?}", path),
+                    extra: format!("{:?}", error),
+                });
+            }
+        };
+
+        let mut json = match serde_json::from_str::<Value>(&contents) {
+            Ok(Value::Object(x)) => x,
+            Ok(_) => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::ParseError,
+                    message: "The config is not a JSON object".to_string(),
+                    extra: String::new(),
+                });
+            }
+            Err(error) => {
+                return Err(ConfigError {
+                    code: ConfigErrorCode::ParseError,
+                    message: "Couldn't parse config".to_string(),
+                    extra: format!("{:?}", error),
+                });
+            }
+        };
+
+        edit(&mut json);
+
+        // Same indentation as the default config
+        let mut out = Vec::new();
+        let mut serializer =
+            serde_json::Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(b"\t"));
+        if let Err(error) = Value::Object(json).serialize(&mut serializer) {
+            return Err(ConfigError {
+                code: ConfigErrorCode::WriteError,
+                message: "Couldn't serialize the config".to_string(),
+                extra: format!("{:?}", error),
+            });
+        }
+        out.push(b'\n');
+
+        match std::fs::write(path, out) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(ConfigError {
+                code: ConfigErrorCode::WriteError,
+                message: format!("Couldn't write the file {:?}", path),
+                extra: format!("{:?}", error),
+            }),
+        }
+    }
+
+    /// Adds a station to the list in the config file
+    pub fn add_station(&self, station: &Station) -> Result<(), ConfigError> {
+        self.edit_file(|json| {
+            let mut entry = json!({ "station": station.station, "url": station.url });
+            if let Some(uuid) = &station.uuid {
+                entry["uuid"] = Value::from(uuid.as_str());
+            }
+
+            match json.get_mut("data").and_then(Value::as_array_mut) {
+                Some(data) => data.push(entry),
+                None => {
+                    json.insert("data".to_string(), Value::Array(vec![entry]));
+                }
+            }
+        })
+    }
+
+    /// Country to search stations in, if any
+    pub fn country(&self) -> Option<&str> {
+        self.country_code.as_deref().filter(|c| !c.is_empty())
+    }
+
+    /// Saves the country to search stations in, in the config file.
+    /// An empty code means any country, without asking again.
+    pub fn set_country(&self, code: &str) -> Result<(), ConfigError> {
+        self.edit_file(|json| {
+            // Both names are accepted, but only one can be there
+            json.remove("country_code");
+            json.insert("country".to_string(), Value::from(code));
+        })
+    }
+
+    /// Sets the audio output of the whole config, or of one of its stations, in the config file.
+    /// `None` goes back to the default output.
+    pub fn set_audio_device(
+        &self,
+        station: Option<&str>,
+        device: Option<&str>,
+    ) -> Result<(), ConfigError> {
+        if let Some(name) = station
+            && self.get_station(name).is_none()
+        {
+            return Err(ConfigError {
+                code: ConfigErrorCode::WriteError,
+                message: format!("There is no station \"{}\" in the config", name),
+                extra: String::new(),
+            });
+        }
+
+        self.edit_file(|json| {
+            let target = match station {
+                Some(name) => json
+                    .get_mut("data")
+                    .and_then(Value::as_array_mut)
+                    .and_then(|data| {
+                        data.iter_mut()
+                            .find(|s| s.get("station").and_then(Value::as_str) == Some(name))
+                    })
+                    .and_then(Value::as_object_mut),
+                None => Some(json),
+            };
+
+            if let Some(target) = target {
+                match device {
+                    Some(d) => {
+                        target.insert("audio_device".to_string(), Value::from(d));
+                    }
+                    None => {
+                        target.remove("audio_device");
+                    }
+                }
+            }
+        })
+    }
+
+    /// Adds a station to the loaded list, before "Other". The file is not changed.
+    pub fn insert_station(&mut self, station: Station) {
+        let at = match self.data.last() {
+            Some(s) if s.station == "Other" => self.data.len() - 1,
+            _ => self.data.len(),
+        };
+
+        self.data.insert(at, station);
+    }
+
+    /// Stations of the list that can be played, without "Other"
+    pub fn stations(&self) -> impl Iterator<Item = &Station> {
+        self.data.iter().filter(|s| s.station != "Other")
+    }
+
+    /// Station of the list `step` places away from the one with this URL (see `station_near`)
+    pub fn station_near(&self, url: &str, step: isize) -> Option<Station> {
+        let stations: Vec<&Station> = self.stations().collect();
+        station::station_near(&stations, url, step)
+    }
+
+    /// The built-in presets, replaced or extended by those of the config
+    pub fn presets(&self) -> HashMap<String, String> {
+        let mut presets: HashMap<String, String> = BUILTIN_PRESETS
+            .iter()
+            .map(|(name, chain)| (name.to_string(), chain.to_string()))
+            .collect();
+        presets.extend(self.presets.clone());
+
+        presets
+    }
+
+    pub fn has_preset(&self, name: &str) -> bool {
+        self.presets.contains_key(name) || BUILTIN_PRESETS.iter().any(|(n, _)| *n == name)
+    }
+
+    /// Names of the presets, sorted
+    pub fn preset_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.presets().into_keys().collect();
+        names.sort();
+
+        names
+    }
+
+    /// The podcast called `name`, whatever its case
+    pub fn get_podcast(&self, name: &str) -> Option<&Podcast> {
+        self.podcasts
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_station(&self, station_name: &str) -> Option<Station> {
+        self.data
+            .iter()
+            .find(|s| s.station.eq(station_name))
+            .cloned()
+    }
+
     pub fn get_url_for(&self, station_name: &str) -> Option<String> {
         for s in self.data.iter() {
             if s.station.eq(station_name) {
@@ -152,6 +452,10 @@ impl Config {
     }
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn deserialize_version<'de, D>(deserializer: D) -> Result<Version, D::Error>
 where
     D: Deserializer<'de>,
@@ -181,3 +485,84 @@ where
     // use our visitor to deserialize an `ActualValue`
     deserializer.deserialize_any(JsonStringVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_config(dir: &std::path::Path, json: serde_json::Value) -> PathBuf {
+        let file = dir.join("config.json");
+        fs::write(&file, json.to_string()).unwrap();
+        file
+    }
+
+    #[test]
+    fn adds_its_presets_to_the_built_in_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_config(
+            dir.path(),
+            serde_json::json!({
+                "config_version": "2.3.0",
+                "presets": {"night": "volume=0.5", "warm": "lowpass=f=8000"},
+                "data": [{"station": "Radio", "url": "http://radio.example/live", "preset": "warm"}],
+            }),
+        );
+        let config = Config::load_from_file(file).unwrap();
+
+        assert_eq!(config.presets()["night"], "volume=0.5");
+        assert_eq!(config.presets()["bass-boost"], "bass=g=8");
+        assert!(config.has_preset("warm"));
+        assert!(config.has_preset("speech"));
+        assert!(!config.has_preset("loud"));
+        assert_eq!(
+            config.preset_names(),
+            ["bass-boost", "night", "speech", "warm"]
+        );
+    }
+
+    #[test]
+    fn stations_with_unknown_presets_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write_config(
+            dir.path(),
+            serde_json::json!({
+                "config_version": "2.3.0",
+                "data": [{"station": "Radio", "url": "http://radio.example/live", "preset": "loud"}],
+            }),
+        );
+
+        let error = Config::load_from_file(file).unwrap_err();
+        assert_eq!(
+            error.message,
+            "The station \"Radio\" uses an unknown preset"
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_stations_on_disk_that_are_gone() {
+        let dir = tempfile::tempdir().unwrap();
+        let music = dir.path().join("Music");
+        fs::create_dir(&music).unwrap();
+        let rain = dir.path().join("rain.ogg");
+        fs::write(&rain, b"").unwrap();
+
+        let file = write_config(
+            dir.path(),
+            serde_json::json!({
+            "config_version": "2.3.0",
+            "data": [
+                {"station": "Radio", "url": "http://radio.example/live"},
+                {"station": "My music", "url": music, "type": "directory"},
+                {"station": "Rain", "url": rain, "type": "file"},
+                {"station": "Unplugged", "url": dir.path().join("gone"), "type": "directory"},
+                {"station": "Not a file", "url": music, "type": "file"},
+            ],
+            }),
+        );
+
+        let config = Config::load_from_file(file).unwrap();
+        let names: Vec<&str> = config.stations().map(|s| s.station.as_str()).collect();
+        assert_eq!(names, ["Radio", "My music", "Rain"]);
+    }
+}
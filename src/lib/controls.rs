@@ -0,0 +1,164 @@
+//! Keys understood while a station is playing in the classic (non-TUI) mode.
+//!
+//! radio-cli reads the keyboard itself and forwards the commands to the player,
+//! so the same keys work whatever plays the station.
+
+use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal;
+use std::io::IsTerminal;
+use std::thread;
+use std::time::Duration;
+
+/// How much the volume changes with each key press
+pub const VOLUME_STEP: f64 = 5.0;
+
+/// Shown when a station starts playing
+pub const HELP: &str = "n/p next/previous station · -/+ volume · m mute · space pause · f favorite · u upvote · i info · z sleep timer · q menu";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Pause,
+    Favorite,
+    /// Vote for the station in Radio Browser
+    Upvote,
+    Info,
+    Sleep,
+    /// Stop playing and go back to the station selector
+    Menu,
+    /// Exit radio-cli
+    Quit,
+}
+
+impl Control {
+    pub fn from_key(key: KeyEvent) -> Option<Control> {
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('c') | KeyCode::Char('d') => Some(Control::Quit),
+                _ => None,
+            };
+        }
+
+        match key.code {
+            KeyCode::Char('n') | KeyCode::Right => Some(Control::Next),
+            KeyCode::Char('p') | KeyCode::Left => Some(Control::Previous),
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('0') | KeyCode::Up => {
+                Some(Control::VolumeUp)
+            }
+            KeyCode::Char('-') | KeyCode::Char('9') | KeyCode::Down => Some(Control::VolumeDown),
+            KeyCode::Char('m') => Some(Control::Mute),
+            KeyCode::Char(' ') => Some(Control::Pause),
+            KeyCode::Char('f') => Some(Control::Favorite),
+            KeyCode::Char('u') => Some(Control::Upvote),
+            KeyCode::Char('i') => Some(Control::Info),
+            KeyCode::Char('z') => Some(Control::Sleep),
+            KeyCode::Char('q') | KeyCode::Esc => Some(Control::Menu),
+            _ => None,
+        }
+    }
+}
+
+/// Reads single key presses from the terminal while it exists.
+///
+/// The terminal is in raw mode meanwhile, so lines printed in the
+/// meantime need an explicit `\r` (see `Keyboard::print`).
+pub struct Keyboard {
+    raw: bool,
+}
+
+impl Keyboard {
+    /// Takes over the terminal, if there is one. Without a terminal, no key is ever pressed.
+    pub fn new() -> Keyboard {
+        let raw = std::io::stdin().is_terminal() && terminal::enable_raw_mode().is_ok();
+
+        Keyboard { raw }
+    }
+
+    /// Waits up to `timeout` for a key press.
+    pub fn poll(&self, timeout: Duration) -> Option<Control> {
+        if !self.raw {
+            thread::sleep(timeout);
+            return None;
+        }
+
+        match event::poll(timeout) {
+            Ok(true) => match event::read() {
+                Ok(event::Event::Key(key)) => Control::from_key(key),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Prints a line, whether the terminal is in raw mode or not
+    pub fn print(&self, text: &str) {
+        print!("\r{}\r\n", text);
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Keyboard {
+        Keyboard::new()
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        if self.raw {
+            let _ = terminal::disable_raw_mode();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode) -> Option<Control> {
+        Control::from_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(c: char) -> Option<Control> {
+        Control::from_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    #[test]
+    fn maps_keys_to_controls() {
+        assert_eq!(press(KeyCode::Char('n')), Some(Control::Next));
+        assert_eq!(press(KeyCode::Right), Some(Control::Next));
+        assert_eq!(press(KeyCode::Left), Some(Control::Previous));
+        assert_eq!(press(KeyCode::Up), Some(Control::VolumeUp));
+        assert_eq!(press(KeyCode::Down), Some(Control::VolumeDown));
+        // Like mpv does
+        assert_eq!(press(KeyCode::Char('0')), Some(Control::VolumeUp));
+        assert_eq!(press(KeyCode::Char('9')), Some(Control::VolumeDown));
+        assert_eq!(press(KeyCode::Char(' ')), Some(Control::Pause));
+        assert_eq!(press(KeyCode::Esc), Some(Control::Menu));
+        assert_eq!(press(KeyCode::Char('x')), None);
+    }
+
+    #[test]
+    fn quits_with_ctrl_c_and_ctrl_d() {
+        assert_eq!(ctrl('c'), Some(Control::Quit));
+        assert_eq!(ctrl('d'), Some(Control::Quit));
+        // Not the control of another key
+        assert_eq!(ctrl('n'), None);
+        assert_eq!(press(KeyCode::Char('c')), None);
+    }
+
+    #[test]
+    fn only_presses_count() {
+        for kind in [KeyEventKind::Release, KeyEventKind::Repeat] {
+            let key = KeyEvent::new_with_kind(KeyCode::Char('n'), KeyModifiers::NONE, kind);
+            assert_eq!(Control::from_key(key), None);
+        }
+    }
+}
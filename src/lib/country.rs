@@ -0,0 +1,235 @@
+//! Finding countries by (roughly) their name, so nobody has to know ISO 3166-1 codes.
+
+use radiobrowser::ApiCountry;
+use std::fmt;
+
+/// How well a country matches what was typed, the best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    Code,
+    Name,
+    Prefix,
+    WordPrefix,
+    Contains,
+    Typo,
+}
+
+/// Countries matching `query`, the best matches first and the ones with more stations
+/// first among equals. A code (`es`) or the full name (`spain`) only returns that country.
+pub fn find_countries<'a>(countries: &'a [ApiCountry], query: &str) -> Vec<&'a ApiCountry> {
+    // "The Netherlands" like the names, that are compared without the "the"
+    let query = normalize(query);
+    let query = query.strip_prefix("the ").unwrap_or(&query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut found: Vec<(Match, &ApiCountry)> = countries
+        .iter()
+        .filter_map(|c| rank(c, query).map(|m| (m, c)))
+        .collect();
+    found.sort_by(|(m1, c1), (m2, c2)| m1.cmp(m2).then(c2.stationcount.cmp(&c1.stationcount)));
+
+    match found.first() {
+        Some((m, _)) if *m <= Match::Name => {
+            let best = *m;
+            found
+                .into_iter()
+                .take_while(|(m, _)| *m == best)
+                .map(|(_, c)| c)
+                .collect()
+        }
+        _ => found.into_iter().map(|(_, c)| c).collect(),
+    }
+}
+
+fn rank(country: &ApiCountry, query: &str) -> Option<Match> {
+    // Radio Browser calls some of them "The United States Of America" and such
+    let name = normalize(&country.name);
+    let name = name.strip_prefix("the ").unwrap_or(&name);
+
+    if country.iso_3166_1.eq_ignore_ascii_case(query) {
+        Some(Match::Code)
+    } else if name == query {
+        Some(Match::Name)
+    } else if name.starts_with(query) {
+        Some(Match::Prefix)
+    } else if name.split(' ').any(|word| word.starts_with(query)) {
+        Some(Match::WordPrefix)
+    } else if name.contains(query) {
+        Some(Match::Contains)
+    } else if is_typo(name, query) {
+        Some(Match::Typo)
+    } else {
+        None
+    }
+}
+
+/// Whether `query` is a word (or the start of one) of `name` with a couple of letters wrong
+fn is_typo(name: &str, query: &str) -> bool {
+    let query: Vec<char> = query.chars().collect();
+    if query.len() <= 3 {
+        return false;
+    }
+    let allowed = (query.len() / 4).clamp(1, 2);
+
+    name.split(' ').any(|word| {
+        let word: Vec<char> = word.chars().collect();
+
+        // Also compare with the start of the word, so half a name can have typos
+        let start = &word[..word.len().min(query.len())];
+        distance(&word, &query) <= allowed || distance(start, &query) <= allowed
+    })
+}
+
+/// Edit distance between two words, a swap of two letters counting as one edit
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j - 1] + cost)
+                .min(d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Lowercase, without accents on the most common letters and single spaces
+fn normalize(s: &str) -> String {
+    let s: String = s
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            'ñ' => 'n',
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// An entry of the country picker: a country, or no filter at all
+#[derive(Debug, Clone)]
+pub struct CountryChoice(pub Option<ApiCountry>);
+
+impl fmt::Display for CountryChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(c) => write!(
+                f,
+                "{} ({}): {} stations",
+                c.name, c.iso_3166_1, c.stationcount
+            ),
+            None => write!(f, "Any country"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn countries() -> Vec<ApiCountry> {
+        [
+            ("Spain", "ES", 1500),
+            ("The Netherlands", "NL", 900),
+            ("The United States Of America", "US", 6000),
+            ("United Kingdom", "GB", 2500),
+            ("Austria", "AT", 400),
+            ("Australia", "AU", 700),
+            ("Côte D'Ivoire", "CI", 20),
+            ("Réunion", "RE", 15),
+        ]
+        .into_iter()
+        .map(|(name, code, stationcount)| ApiCountry {
+            name: name.to_string(),
+            iso_3166_1: code.to_string(),
+            stationcount,
+        })
+        .collect()
+    }
+
+    fn find(query: &str) -> Vec<String> {
+        find_countries(&countries(), query)
+            .into_iter()
+            .map(|c| c.iso_3166_1.clone())
+            .collect()
+    }
+
+    #[test]
+    fn a_code_or_a_name_is_that_country() {
+        assert_eq!(find("es"), ["ES"]);
+        assert_eq!(find("GB"), ["GB"]);
+        assert_eq!(find("Spain"), ["ES"]);
+        assert_eq!(find("  SPAIN "), ["ES"]);
+        assert!(find("").is_empty());
+        assert!(find("?!").is_empty());
+    }
+
+    #[test]
+    fn with_or_without_the() {
+        assert_eq!(find("netherlands"), ["NL"]);
+        assert_eq!(find("The Netherlands"), ["NL"]);
+        assert_eq!(find("the united states of america"), ["US"]);
+    }
+
+    #[test]
+    fn starts_of_names_and_words_the_biggest_first() {
+        assert_eq!(find("aus"), ["AU", "AT"]);
+        // The start of the name before that of another word, then the names with it inside
+        assert_eq!(find("uni"), ["US", "GB", "RE"]);
+        assert_eq!(find("kingdom"), ["GB"]);
+        assert_eq!(find("lands"), ["NL"]);
+    }
+
+    #[test]
+    fn accents_are_optional() {
+        assert_eq!(find("reunion"), ["RE"]);
+        assert_eq!(find("cote d ivoire"), ["CI"]);
+        assert_eq!(find("Réunion"), ["RE"]);
+    }
+
+    #[test]
+    fn forgives_typos() {
+        // Two letters swapped are one mistake
+        assert_eq!(find("sapin"), ["ES"]);
+        assert_eq!(find("nehterlands"), ["NL"]);
+        assert_eq!(find("untied"), ["US", "GB"]);
+        // Not in short words, that would match anything
+        assert!(find("spq").is_empty());
+        assert!(find("xyz").is_empty());
+        assert!(find("portugal").is_empty());
+    }
+
+    #[test]
+    fn measures_edits() {
+        let chars = |s: &str| s.chars().collect::<Vec<char>>();
+        assert_eq!(distance(&chars("spain"), &chars("spain")), 0);
+        assert_eq!(distance(&chars("spain"), &chars("sapin")), 1);
+        assert_eq!(distance(&chars("spain"), &chars("span")), 1);
+        assert_eq!(distance(&chars("spain"), &chars("spaine")), 1);
+        assert_eq!(distance(&chars("spain"), &chars("")), 5);
+        assert_eq!(normalize("  Côte  D'Ivoire "), "cote d ivoire");
+    }
+}
@@ -0,0 +1,630 @@
+//! Background player controlled through a Unix socket.
+//!
+//! The protocol is line-delimited JSON: each line sent by a client is a `Request`,
+//! and the daemon answers each one with a single `Response` line, e.g.
+//!
+//! ```text
+//! -> {"command":"play","station":"Lofi"}
+//! <- {"ok":true,"status":{"station":"Lofi","url":"http://...","title":null,"volume":100.0,"muted":false}}
+//! ```
+
+use crate::Config;
+use crate::browser::Directory;
+use crate::player::{Player, PlayerOptions};
+use crate::station::Station;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+/// How often the player is checked when no client is connected
+const TICK: Duration = Duration::from_millis(200);
+
+/// A client that doesn't send a full line in this time is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Request {
+    /// Play a station of the config, or one found in Radio Browser.
+    /// With a `url` (http or https only), that stream is played under the given name.
+    Play {
+        station: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    Stop,
+    /// Play the next station of the config
+    Next,
+    /// Play the previous station of the config
+    Previous,
+    /// Stop if playing, otherwise play the last station again
+    Toggle,
+    /// Set the volume to `level`, or change it by `delta`. Without either, just report it.
+    Volume {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        level: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        delta: Option<f64>,
+    },
+    Status,
+}
+
+impl Request {
+    /// Volume request from the command line: `50` sets it, `+5` and `-5` change it.
+    pub fn volume(value: Option<&str>) -> Option<Request> {
+        let Some(value) = value.map(str::trim) else {
+            return Some(Request::Volume {
+                level: None,
+                delta: None,
+            });
+        };
+
+        let number = value.parse::<f64>().ok().filter(|v| v.is_finite())?;
+
+        Some(if value.starts_with('+') || value.starts_with('-') {
+            Request::Volume {
+                level: None,
+                delta: Some(number),
+            }
+        } else {
+            Request::Volume {
+                level: Some(number),
+                delta: None,
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    fn error(message: String) -> Response {
+        Response {
+            ok: false,
+            error: Some(message),
+            status: None,
+        }
+    }
+}
+
+/// What the daemon is doing. `station` is `None` when nothing is playing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Status {
+    pub station: Option<String>,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub volume: f64,
+    pub muted: bool,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.station {
+            Some(station) => write!(f, "Playing {}", station)?,
+            None => write!(f, "Stopped")?,
+        }
+
+        if let Some(title) = &self.title {
+            write!(f, ": {}", title)?;
+        }
+
+        if self.muted {
+            write!(f, " (muted)")
+        } else {
+            write!(f, " (volume {:.0}%)", self.volume)
+        }
+    }
+}
+
+/// Another way of controlling the daemon besides the socket, e.g. MPRIS
+pub trait Remote {
+    /// Requests received since the last call
+    fn requests(&mut self) -> Vec<Request>;
+
+    /// Called regularly with what the daemon is doing
+    fn publish(&mut self, status: &Status);
+}
+
+/// Starts a player for a station
+pub type Spawner<P> = Box<dyn FnMut(&Station, &PlayerOptions) -> Result<P>>;
+
+/// Plays one station at a time, as the requests say.
+pub struct Daemon<P: Player> {
+    config: Rc<Config>,
+    directory: Directory,
+    options: PlayerOptions,
+    spawn: Spawner<P>,
+    playing: Option<(Station, P)>,
+    /// Last station played, to resume it
+    last: Option<Station>,
+    /// Kept across stations, before the adjustment of each one
+    volume: f64,
+    remotes: Vec<Box<dyn Remote>>,
+}
+
+impl<P: Player> Daemon<P> {
+    pub fn new(config: Rc<Config>, options: PlayerOptions, spawn: Spawner<P>) -> Daemon<P> {
+        let volume = options.volume.unwrap_or(100.0);
+
+        Daemon {
+            directory: Directory::new(config.clone()),
+            config,
+            options,
+            spawn,
+            playing: None,
+            last: None,
+            volume,
+            remotes: Vec::new(),
+        }
+    }
+
+    /// The station directories, connected once for all the requests
+    pub fn directory(&self) -> &Directory {
+        &self.directory
+    }
+
+    pub fn add_remote(&mut self, remote: Box<dyn Remote>) {
+        self.remotes.push(remote);
+    }
+
+    pub fn handle(&mut self, request: Request) -> Response {
+        debug!("Daemon request: {:?}", request);
+
+        let result = match request {
+            // Only internet streams, not whatever mpv can open (files, devices...)
+            Request::Play { url: Some(url), .. } if !is_stream_url(&url) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not an http or https stream", url),
+            )),
+            Request::Play {
+                station,
+                url: Some(url),
+            } => self.play(Station {
+                station,
+                url,
+                ..Default::default()
+            }),
+            Request::Play { station, url: None } => match self.directory.find_station(&station) {
+                Some(s) => self.play(s),
+                None => Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("Station \"{}\" not found", station),
+                )),
+            },
+            Request::Stop => {
+                self.stop();
+                Ok(())
+            }
+            Request::Next => self.skip(1),
+            Request::Previous => self.skip(-1),
+            Request::Toggle => match (&self.playing, self.last.clone()) {
+                (Some(_), _) => {
+                    self.stop();
+                    Ok(())
+                }
+                (None, Some(s)) => self.play(s),
+                (None, None) => self.skip(1),
+            },
+            Request::Volume { level, delta } => self.change_volume(level, delta),
+            Request::Status => Ok(()),
+        };
+
+        match result {
+            Ok(()) => Response {
+                ok: true,
+                error: None,
+                status: Some(self.status()),
+            },
+            Err(e) => Response::error(e.to_string()),
+        }
+    }
+
+    pub fn status(&mut self) -> Status {
+        match &mut self.playing {
+            Some((station, player)) => Status {
+                station: Some(station.station.clone()),
+                url: Some(station.url.clone()),
+                title: player.title(),
+                volume: self.volume,
+                muted: player.muted().unwrap_or(false),
+            },
+            None => Status {
+                station: None,
+                url: None,
+                title: None,
+                volume: self.volume,
+                muted: false,
+            },
+        }
+    }
+
+    /// Forgets the player if it stopped by itself (e.g. the stream ended)
+    pub fn check_player(&mut self) {
+        let Some((station, player)) = &mut self.playing else {
+            return;
+        };
+
+        match player.try_wait() {
+            Ok(None) => {}
+            Ok(Some(status)) => {
+                info!("{} stopped ({})", station.station, status);
+                self.playing = None;
+            }
+            Err(e) => {
+                warn!("Lost track of the player: {}", e);
+                self.playing = None;
+            }
+        }
+    }
+
+    fn play(&mut self, station: Station) -> Result<()> {
+        self.stop();
+
+        let options = PlayerOptions {
+            volume: Some(station.adjusted_volume(self.volume)),
+            ..self.options.clone()
+        };
+
+        let stream = self.directory.report_click(station.clone());
+        let player = (self.spawn)(&stream, &options)?;
+        info!("Playing {}", station.station);
+        self.last = Some(station.clone());
+        self.playing = Some((station, player));
+
+        Ok(())
+    }
+
+    /// Plays the station of the config `step` places away from the current one
+    fn skip(&mut self, step: isize) -> Result<()> {
+        let url = match &self.playing {
+            Some((s, _)) => s.url.as_str(),
+            None => "",
+        };
+
+        match self.config.station_near(url, step) {
+            Some(s) => self.play(s),
+            None => Err(Error::other("There are no stations in the config")),
+        }
+    }
+
+    /// Handles the requests of the remotes and tells them what's going on
+    fn check_remotes(&mut self) {
+        if self.remotes.is_empty() {
+            return;
+        }
+
+        let requests: Vec<Request> = self.remotes.iter_mut().flat_map(|r| r.requests()).collect();
+        for request in requests {
+            let response = self.handle(request);
+            if let Some(e) = response.error {
+                warn!("{}", e);
+            }
+        }
+
+        let status = self.status();
+        for remote in self.remotes.iter_mut() {
+            remote.publish(&status);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some((station, mut player)) = self.playing.take() {
+            info!("Stopping {}", station.station);
+
+            if let Err(e) = player.stop() {
+                debug!("{}", e);
+            }
+        }
+    }
+
+    fn change_volume(&mut self, level: Option<f64>, delta: Option<f64>) -> Result<()> {
+        let volume = match (level, delta) {
+            (Some(l), _) => l,
+            (None, Some(d)) => self.volume + d,
+            (None, None) => return Ok(()),
+        };
+        self.volume = volume.clamp(0.0, 100.0);
+
+        match &mut self.playing {
+            Some((station, player)) => player.set_volume(station.adjusted_volume(self.volume)),
+            None => Ok(()),
+        }
+    }
+
+    /// Answers the clients of the socket until it fails, one client at a time.
+    pub fn serve(&mut self, listener: &UnixListener) -> Result<()> {
+        listener.set_nonblocking(true)?;
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = self.serve_client(stream) {
+                        debug!("Client error: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(TICK),
+                Err(e) => return Err(e),
+            }
+
+            self.check_player();
+            self.check_remotes();
+        }
+    }
+
+    fn serve_client(&mut self, stream: UnixStream) -> Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => self.handle(request),
+                Err(e) => Response::error(format!("Invalid request: {}", e)),
+            };
+
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Player> Drop for Daemon<P> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn is_stream_url(url: &str) -> bool {
+    let scheme = url.split_once("://").map(|(s, _)| s.to_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https"))
+}
+
+/// Binds the control socket, replacing a stale one. Fails if a daemon is already listening.
+pub fn bind(path: &Path) -> Result<UnixListener> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AddrInUse,
+            format!("A daemon is already listening on {}", path.display()),
+        ));
+    }
+
+    let _ = std::fs::remove_file(path);
+    UnixListener::bind(path)
+}
+
+/// Sends one request to the daemon and waits for its response
+pub fn send(path: &Path, request: &Request) -> Result<Response> {
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+
+    serde_json::from_str(&line).map_err(Error::other)
+}
+
+/// Default location of the socket: `$XDG_RUNTIME_DIR/radio-cli.sock`,
+/// or a per-user file in the temporary directory
+pub fn default_socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("radio-cli.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_else(|_| String::from("user"));
+            std::env::temp_dir().join(format!("radio-cli-{}.sock", user))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakePlayer};
+    use std::sync::{Arc, Mutex};
+
+    /// A daemon with fake players listening in a temporary folder, and the log of its players
+    fn start() -> (tempfile::TempDir, PathBuf, Arc<Mutex<Vec<String>>>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.sock");
+        let listener = bind(&path).unwrap();
+
+        let (spawn, log) = FakePlayer::spawner();
+        thread::spawn(move || {
+            let config = testing::config(&[
+                ("Lofi", "http://lofi.example/stream"),
+                ("Jazz", "http://jazz.example/stream"),
+            ]);
+            let options = PlayerOptions {
+                volume: Some(50.0),
+                ..Default::default()
+            };
+
+            let mut daemon = Daemon::new(Rc::new(config), options, Box::new(spawn));
+            daemon.serve(&listener)
+        });
+
+        (dir, path, log)
+    }
+
+    fn play(station: &str) -> Request {
+        Request::Play {
+            station: station.to_string(),
+            url: None,
+        }
+    }
+
+    #[test]
+    fn volume_requests_from_the_command_line() {
+        let volume = |level, delta| Some(Request::Volume { level, delta });
+
+        assert_eq!(Request::volume(None), volume(None, None));
+        assert_eq!(Request::volume(Some("50")), volume(Some(50.0), None));
+        assert_eq!(Request::volume(Some("+5")), volume(None, Some(5.0)));
+        assert_eq!(Request::volume(Some(" -5 ")), volume(None, Some(-5.0)));
+        assert_eq!(Request::volume(Some("loud")), None);
+        assert_eq!(Request::volume(Some("inf")), None);
+    }
+
+    #[test]
+    fn plays_and_stops_through_the_socket() {
+        let (_dir, path, log) = start();
+
+        let response = send(&path, &play("Lofi")).unwrap();
+        assert!(response.ok, "{:?}", response.error);
+        let status = response.status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Lofi"));
+        assert_eq!(status.url.as_deref(), Some("http://lofi.example/stream"));
+        assert_eq!(status.title.as_deref(), Some("Live on Lofi"));
+        assert_eq!(status.volume, 50.0);
+
+        let status = send(&path, &Request::volume(Some("+5")).unwrap())
+            .unwrap()
+            .status
+            .unwrap();
+        assert_eq!(status.volume, 55.0);
+
+        let status = send(&path, &Request::Status).unwrap().status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Lofi"));
+        assert_eq!(status.volume, 55.0);
+
+        let status = send(&path, &Request::Stop).unwrap().status.unwrap();
+        assert_eq!(
+            status,
+            Status {
+                volume: 55.0,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["play http://lofi.example/stream at 50", "volume 55", "stop",]
+        );
+    }
+
+    #[test]
+    fn next_toggle_and_the_volume_limits() {
+        let (_dir, path, _) = start();
+
+        let status = send(&path, &Request::Next).unwrap().status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Lofi"));
+        let status = send(&path, &Request::Next).unwrap().status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Jazz"));
+        let status = send(&path, &Request::Previous).unwrap().status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Lofi"));
+
+        let status = send(&path, &Request::Toggle).unwrap().status.unwrap();
+        assert_eq!(status.station, None);
+        let status = send(&path, &Request::Toggle).unwrap().status.unwrap();
+        assert_eq!(status.station.as_deref(), Some("Lofi"));
+
+        let volume = |level, delta| Request::Volume { level, delta };
+        let status = send(&path, &volume(Some(150.0), None)).unwrap().status;
+        assert_eq!(status.unwrap().volume, 100.0);
+        let status = send(&path, &volume(None, Some(-300.0))).unwrap().status;
+        assert_eq!(status.unwrap().volume, 0.0);
+    }
+
+    #[test]
+    fn plays_a_given_url_under_a_name() {
+        let (_dir, path, log) = start();
+
+        let request = Request::Play {
+            station: String::from("Somewhere"),
+            url: Some(String::from("https://somewhere.example/live")),
+        };
+        let status = send(&path, &request).unwrap().status.unwrap();
+
+        assert_eq!(status.station.as_deref(), Some("Somewhere"));
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["play https://somewhere.example/live at 50"]
+        );
+    }
+
+    #[test]
+    fn only_plays_urls_of_internet_streams() {
+        let (_dir, path, log) = start();
+
+        for url in [
+            "/etc/passwd",
+            "file:///etc/passwd",
+            "av://v4l2:/dev/video0",
+            "ftp://x/y",
+        ] {
+            let request = Request::Play {
+                station: String::from("Sneaky"),
+                url: Some(url.to_string()),
+            };
+            let response = send(&path, &request).unwrap();
+
+            assert!(!response.ok, "{} was played", url);
+            assert!(
+                response
+                    .error
+                    .unwrap()
+                    .contains("not an http or https stream")
+            );
+        }
+
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn answers_invalid_requests_with_an_error() {
+        let (_dir, path, log) = start();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "{{\"command\":\"dance\"}}").unwrap();
+        writeln!(stream, "not json").unwrap();
+        writeln!(stream).unwrap();
+        writeln!(stream, "{{\"command\":\"status\"}}").unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let responses: Vec<Response> = BufReader::new(stream)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(responses.len(), 3);
+        for response in &responses[..2] {
+            assert!(!response.ok);
+            assert!(
+                response
+                    .error
+                    .as_ref()
+                    .unwrap()
+                    .starts_with("Invalid request")
+            );
+        }
+        assert!(responses[2].ok);
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuses_a_second_daemon_on_the_same_socket() {
+        let (_dir, path, _) = start();
+
+        let e = bind(&path).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AddrInUse);
+    }
+}
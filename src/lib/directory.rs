@@ -0,0 +1,839 @@
+//! Where the stations that aren't in the config are found.
+//!
+//! Radio Browser is the directory used by default. The Icecast directory, Shoutcast and
+//! OPML directories can be searched along with it (see `DirectoryOptions`), and
+//! `MemoryDirectory` serves a fixed list of stations (loaded from a file, for example),
+//! so the browser can be used offline.
+
+use clap::ValueEnum;
+use log::warn;
+use radiobrowser::{ApiCountry, ApiStation};
+use serde::Deserialize;
+use serde_json::json;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::Config;
+use crate::browser::{Category, Group, Order};
+use crate::icecast::{ICECAST_URL, IcecastDirectory};
+use crate::opml::OpmlDirectory;
+use crate::radio_browser::{RadioBrowser, StationSearch, http_client};
+use crate::shoutcast::ShoutcastDirectory;
+
+pub trait StationDirectory {
+    /// Stations matching `search`, sorted and paged as it says
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>>;
+
+    /// The station with id `uuid`, if there is one
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>>;
+
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>>;
+
+    /// Tags, languages, codecs or states (only of `country`, by name, if given)
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>>;
+
+    /// Tells the directory that the station with id `uuid` is being played,
+    /// and returns the URL it should be played at
+    fn click(&self, _uuid: &str) -> Result<String, Box<dyn Error>> {
+        Err("This directory does not count clicks".into())
+    }
+
+    /// Votes for the station with id `uuid`, returning what the directory answers
+    fn vote(&self, _uuid: &str) -> Result<String, Box<dyn Error>> {
+        Err("This directory does not take votes".into())
+    }
+}
+
+/// Which directories are searched, the `directories` section of the config
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DirectoryOptions {
+    pub radio_browser: bool,
+
+    /// The listing of dir.xiph.org
+    pub icecast: bool,
+
+    /// Developer key of the Shoutcast API
+    pub shoutcast: Option<String>,
+
+    /// OPML directories, `{query}` being replaced by what is searched
+    pub opml: Vec<String>,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        DirectoryOptions {
+            radio_browser: true,
+            icecast: false,
+            shoutcast: None,
+            opml: Vec::new(),
+        }
+    }
+}
+
+/// The directories the config says, together
+pub fn connect(config: &Config) -> Result<Box<dyn StationDirectory>, Box<dyn Error>> {
+    let options = &config.directories;
+    let others = options.icecast || options.shoutcast.is_some() || !options.opml.is_empty();
+
+    let mut directories: Vec<(&'static str, Box<dyn StationDirectory>)> = Vec::new();
+    if options.radio_browser {
+        match RadioBrowser::new(config) {
+            Ok(d) => directories.push(("radio-browser", Box::new(d))),
+            // The others may still work
+            Err(e) if others => warn!("Radio Browser can't be used: {}", e),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let client = http_client(config)?;
+    if options.icecast {
+        let icecast = IcecastDirectory::new(client.clone(), ICECAST_URL);
+        directories.push(("icecast", Box::new(icecast)));
+    }
+    if let Some(key) = &options.shoutcast {
+        let shoutcast = ShoutcastDirectory::new(client.clone(), key);
+        directories.push(("shoutcast", Box::new(shoutcast)));
+    }
+    for url in &options.opml {
+        directories.push(("opml", Box::new(OpmlDirectory::new(client.clone(), url))));
+    }
+
+    match directories.len() {
+        0 => Err("No station directory can be used, see `directories` in the config".into()),
+        1 => Ok(directories.pop().unwrap().1),
+        _ => Ok(Box::new(MergedDirectory::new(directories))),
+    }
+}
+
+/// A station of a directory other than Radio Browser, with nothing but a name and a URL
+pub fn new_station(uuid: &str, name: &str, url: &str) -> ApiStation {
+    let station = json!({
+        "changeuuid": "",
+        "stationuuid": uuid,
+        "name": name.trim(),
+        "url": url,
+        "url_resolved": url,
+        "homepage": "",
+        "favicon": "",
+        "tags": "",
+        "country": "",
+        "countrycode": "",
+        "state": "",
+        "language": "",
+        "votes": 0,
+        "codec": "",
+        "bitrate": 0,
+        "hls": 0,
+        "lastcheckok": 1,
+        "clickcount": 0,
+        "clicktrend": 0,
+    });
+
+    serde_json::from_value(station).expect("Every field of a station is set")
+}
+
+/// Several directories searched together. The first one is paged, the rest only add
+/// their stations to the first page, unless a station with the same URL is there already.
+pub struct MergedDirectory {
+    /// With the kind of directory, the start of the ids of its stations
+    directories: Vec<(&'static str, Box<dyn StationDirectory>)>,
+}
+
+impl MergedDirectory {
+    /// `directories` with their kind (`radio-browser`, `icecast`...), the paged one first
+    pub fn new(directories: Vec<(&'static str, Box<dyn StationDirectory>)>) -> MergedDirectory {
+        MergedDirectory { directories }
+    }
+
+    /// The directories the station with id `uuid` may come from
+    fn owners(&self, uuid: &str) -> impl Iterator<Item = &dyn StationDirectory> {
+        let kind = match uuid.split_once(':') {
+            Some((kind @ ("icecast" | "shoutcast" | "opml"), _)) => kind,
+            _ => "radio-browser",
+        };
+
+        self.directories
+            .iter()
+            .filter(move |(k, _)| *k == kind)
+            .map(|(_, d)| d.as_ref())
+    }
+}
+
+impl StationDirectory for MergedDirectory {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        let first_page = search.get("offset").is_none_or(|o| o == "0");
+
+        let mut found: Vec<ApiStation> = Vec::new();
+        let mut urls: HashSet<String> = HashSet::new();
+        let mut failed: Option<Box<dyn Error>> = None;
+
+        for (i, (kind, directory)) in self.directories.iter().enumerate() {
+            if i > 0 && !first_page {
+                break;
+            }
+
+            let stations = match directory.search(search) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Could not search {}: {}", kind, e);
+                    failed = Some(e);
+                    continue;
+                }
+            };
+
+            for station in stations {
+                if urls.insert(stream_key(&station.url)) || i == 0 {
+                    found.push(station);
+                }
+            }
+        }
+
+        match (found.is_empty(), failed) {
+            (true, Some(e)) => Err(e),
+            _ => Ok(found),
+        }
+    }
+
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        for directory in self.owners(uuid) {
+            if let Some(station) = directory.station(uuid)? {
+                return Ok(Some(station));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Those of the first directory that knows where its stations are
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        for (kind, directory) in &self.directories {
+            match directory.countries() {
+                Ok(countries) if !countries.is_empty() => return Ok(countries),
+                Ok(_) => {}
+                Err(e) => warn!("Could not get the countries of {}: {}", kind, e),
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// The groups of all the directories, adding up the stations of those with the same name
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        let mut groups: Vec<Group> = Vec::new();
+
+        for (kind, directory) in &self.directories {
+            let found = match directory.groups(category, country, hidebroken) {
+                Ok(g) => g,
+                Err(e) => {
+                    warn!("Could not browse {}: {}", kind, e);
+                    continue;
+                }
+            };
+
+            for group in found {
+                match groups.iter_mut().find(|g| {
+                    g.name.eq_ignore_ascii_case(&group.name) && g.country == group.country
+                }) {
+                    Some(g) => g.stationcount += group.stationcount,
+                    None => groups.push(group),
+                }
+            }
+        }
+
+        Ok(groups)
+    }
+
+    fn click(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        match self.owners(uuid).next() {
+            Some(directory) => directory.click(uuid),
+            None => Err(format!("No directory has the station {}", uuid).into()),
+        }
+    }
+
+    fn vote(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        match self.owners(uuid).next() {
+            Some(directory) => directory.vote(uuid),
+            None => Err(format!("No directory has the station {}", uuid).into()),
+        }
+    }
+}
+
+/// A stream URL without the differences that don't matter, to find duplicates
+fn stream_key(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url
+        .strip_prefix("https://")
+        .or(url.strip_prefix("http://"))
+        .unwrap_or(&url);
+
+    url.trim_end_matches('/').to_string()
+}
+
+/// A fixed list of stations, in the format of Radio Browser
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct MemoryDirectory {
+    #[serde(default)]
+    pub stations: Vec<ApiStation>,
+
+    /// Taken from the stations when empty
+    #[serde(default)]
+    pub countries: Vec<ApiCountry>,
+}
+
+impl MemoryDirectory {
+    pub fn new(stations: Vec<ApiStation>) -> MemoryDirectory {
+        MemoryDirectory {
+            stations,
+            countries: Vec::new(),
+        }
+    }
+
+    /// Loads `{"stations": [...], "countries": [...]}` from a JSON file
+    pub fn load(path: &Path) -> Result<MemoryDirectory, Box<dyn Error>> {
+        let file = File::open(path)?;
+
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+impl StationDirectory for MemoryDirectory {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        let mut found: Vec<ApiStation> = self
+            .stations
+            .iter()
+            .filter(|s| matches(search, s))
+            .cloned()
+            .collect();
+
+        if let Some(order) = search.get("order").and_then(order_named) {
+            found.sort_by(|a, b| compare(order, a, b));
+        }
+        if search.get("reverse") == Some("true") {
+            found.reverse();
+        }
+
+        let offset = search.get("offset").and_then(|o| o.parse().ok());
+        let limit = search.get("limit").and_then(|l| l.parse().ok());
+
+        Ok(found
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.filter(|l| *l > 0).unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        Ok(self
+            .stations
+            .iter()
+            .find(|s| s.stationuuid == uuid)
+            .cloned())
+    }
+
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        if !self.countries.is_empty() {
+            return Ok(self.countries.clone());
+        }
+
+        let mut countries: Vec<ApiCountry> = Vec::new();
+        for station in self.stations.iter().filter(|s| !s.countrycode.is_empty()) {
+            match countries
+                .iter_mut()
+                .find(|c| c.iso_3166_1.eq_ignore_ascii_case(&station.countrycode))
+            {
+                Some(country) => country.stationcount += 1,
+                None => countries.push(ApiCountry {
+                    name: station.country.clone(),
+                    iso_3166_1: station.countrycode.clone(),
+                    stationcount: 1,
+                }),
+            }
+        }
+
+        Ok(countries)
+    }
+
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        let mut counts: HashMap<(String, Option<String>), u32> = HashMap::new();
+
+        let stations = self
+            .stations
+            .iter()
+            .filter(|s| !hidebroken || s.lastcheckok == 1);
+        for station in stations {
+            let names: Vec<&str> = match category {
+                Category::Tags => station.tags.split(',').collect(),
+                Category::Languages => station.language.split(',').collect(),
+                Category::Codecs => vec![station.codec.as_str()],
+                Category::States => {
+                    if country.is_some_and(|c| !station.country.eq_ignore_ascii_case(c)) {
+                        continue;
+                    }
+                    vec![station.state.as_str()]
+                }
+            };
+
+            let country = match category {
+                Category::States => Some(station.country.clone()),
+                _ => None,
+            };
+            for name in names.into_iter().map(str::trim).filter(|n| !n.is_empty()) {
+                *counts
+                    .entry((name.to_string(), country.clone()))
+                    .or_default() += 1;
+            }
+        }
+
+        Ok(counts
+            .into_iter()
+            .map(|((name, country), stationcount)| Group {
+                name,
+                country,
+                stationcount,
+            })
+            .collect())
+    }
+
+    fn click(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        match self.station(uuid)? {
+            Some(s) if !s.url_resolved.is_empty() => Ok(s.url_resolved),
+            Some(s) => Ok(s.url),
+            None => Err(format!("There is no station {}", uuid).into()),
+        }
+    }
+
+    fn vote(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        match self.station(uuid)? {
+            Some(_) => Ok(String::from("voted for station successfully")),
+            None => Err(format!("There is no station {}", uuid).into()),
+        }
+    }
+}
+
+/// Whether `station` is one of the results of `search`, as Radio Browser would tell
+fn matches(search: &StationSearch, station: &ApiStation) -> bool {
+    let exact = |key: &str| search.get(&format!("{}Exact", key)) == Some("true");
+
+    // A field, or each item of a comma-separated list, equal to or containing the value
+    let check = |key: &str, value: &str, list: bool| {
+        let Some(wanted) = search.get(key) else {
+            return true;
+        };
+        let wanted = wanted.to_lowercase();
+
+        let mut items: Vec<&str> = vec![value];
+        if list {
+            items = value.split(',').map(str::trim).collect();
+        }
+        items.iter().any(|item| match exact(key) {
+            true => item.to_lowercase() == wanted,
+            false => item.to_lowercase().contains(&wanted),
+        })
+    };
+
+    let bitrate = |key: &str| search.get(key).and_then(|b| b.parse::<u32>().ok());
+    let tag_list = search.get("tagList").map(|tags| {
+        tags.split(',')
+            .map(|t| t.trim().to_lowercase())
+            .collect::<Vec<String>>()
+    });
+
+    check("name", &station.name, false)
+        && check("tag", &station.tags, true)
+        && check("language", &station.language, true)
+        && check("country", &station.country, false)
+        && check("state", &station.state, false)
+        && search
+            .get("codec")
+            .is_none_or(|c| station.codec.eq_ignore_ascii_case(c))
+        // Stations of unknown countries aren't left out
+        && search.get("countrycode").is_none_or(|c| {
+            station.countrycode.is_empty() || station.countrycode.eq_ignore_ascii_case(c)
+        })
+        && bitrate("bitrateMin").is_none_or(|b| station.bitrate >= b)
+        && bitrate("bitrateMax").is_none_or(|b| station.bitrate <= b)
+        && tag_list.is_none_or(|wanted| {
+            let tags: Vec<String> = station
+                .tags
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .collect();
+            wanted.iter().all(|t| tags.contains(t))
+        })
+        && (search.get("hidebroken") != Some("true") || station.lastcheckok == 1)
+}
+
+fn order_named(name: &str) -> Option<Order> {
+    Order::value_variants()
+        .iter()
+        .copied()
+        .find(|o| o.name() == name)
+}
+
+/// Radio Browser's order, the lowest first
+fn compare(order: Order, a: &ApiStation, b: &ApiStation) -> Ordering {
+    match order {
+        Order::Clickcount => a.clickcount.cmp(&b.clickcount),
+        Order::Votes => a.votes.cmp(&b.votes),
+        Order::Clicktrend => a.clicktrend.cmp(&b.clicktrend),
+        Order::Bitrate => a.bitrate.cmp(&b.bitrate),
+        Order::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        Order::Random => Ordering::Equal,
+        Order::Lastcheckok => a.lastcheckok.cmp(&b.lastcheckok),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    fn stations() -> MemoryDirectory {
+        MemoryDirectory::load(&fixture("stations.json")).unwrap()
+    }
+
+    fn search(directory: &dyn StationDirectory, search: StationSearch) -> Vec<String> {
+        directory
+            .search(&search)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.stationuuid)
+            .collect()
+    }
+
+    #[test]
+    fn loads_stations_from_a_file() {
+        let directory = stations();
+        assert_eq!(directory.stations.len(), 6);
+        assert_eq!(directory.stations[2].name, "Radio Clásica");
+
+        let countries = directory.countries().unwrap();
+        let gb = countries.iter().find(|c| c.iso_3166_1 == "GB").unwrap();
+        assert_eq!(gb.stationcount, 3);
+        assert_eq!(countries.len(), 4);
+
+        assert!(MemoryDirectory::load(&fixture("missing.json")).is_err());
+    }
+
+    #[test]
+    fn sorts_the_lowest_first_unless_reversed() {
+        let directory = stations();
+        let by = |order| StationSearch::default().order(order);
+
+        assert_eq!(
+            search(&directory, by(Order::Clickcount)),
+            ["fip", "broken", "clasica", "jazz-fm", "smooth", "antenne"]
+        );
+        assert_eq!(
+            search(&directory, by(Order::Votes).reverse(true)),
+            ["clasica", "antenne", "jazz-fm", "smooth", "fip", "broken"]
+        );
+        // Whatever the case
+        assert_eq!(
+            search(&directory, by(Order::Name)),
+            ["broken", "jazz-fm", "fip", "clasica", "antenne", "smooth"]
+        );
+        // As listed
+        assert_eq!(
+            search(&directory, StationSearch::default()),
+            ["jazz-fm", "smooth", "clasica", "antenne", "broken", "fip"]
+        );
+    }
+
+    #[test]
+    fn pages_with_offset_and_limit() {
+        let directory = stations();
+        let best = StationSearch::default()
+            .order(Order::Clickcount)
+            .reverse(true);
+
+        assert_eq!(
+            search(&directory, best.clone().offset(0).limit(2)),
+            ["antenne", "smooth"]
+        );
+        assert_eq!(
+            search(&directory, best.clone().offset(2).limit(2)),
+            ["jazz-fm", "clasica"]
+        );
+        assert_eq!(search(&directory, best.clone().offset(5).limit(2)), ["fip"]);
+        assert!(search(&directory, best.clone().offset(6).limit(2)).is_empty());
+        // No limit
+        assert_eq!(
+            search(&directory, best.offset(4).limit(0)),
+            ["broken", "fip"]
+        );
+    }
+
+    #[test]
+    fn hides_broken_stations() {
+        let directory = stations();
+        let jazz = StationSearch::default().tag("jazz").tag_exact(true);
+
+        assert_eq!(
+            search(&directory, jazz.clone()),
+            ["jazz-fm", "smooth", "broken", "fip"]
+        );
+        assert_eq!(
+            search(&directory, jazz.clone().hidebroken(true)),
+            ["jazz-fm", "smooth", "fip"]
+        );
+        assert_eq!(search(&directory, jazz.hidebroken(false)).len(), 4);
+    }
+
+    #[test]
+    fn exact_searches_match_whole_values() {
+        let directory = stations();
+        let tag = |exact| StationSearch::default().tag("classic").tag_exact(exact);
+        let country = |exact| {
+            StationSearch::default()
+                .country("kingdom")
+                .country_exact(exact)
+        };
+
+        assert_eq!(search(&directory, tag(false)), ["clasica", "antenne"]);
+        assert!(search(&directory, tag(true)).is_empty());
+        assert_eq!(
+            search(&directory, tag(true).tag("Classic Rock")),
+            ["antenne"]
+        );
+
+        assert_eq!(search(&directory, country(false)).len(), 3);
+        assert!(search(&directory, country(true)).is_empty());
+
+        let english = StationSearch::default()
+            .language("English")
+            .language_exact(true);
+        assert_eq!(search(&directory, english).len(), 3);
+        let madrid = StationSearch::default().state("madrid").state_exact(true);
+        assert_eq!(search(&directory, madrid), ["clasica"]);
+    }
+
+    #[test]
+    fn filters_by_name_codec_bitrate_and_country() {
+        let directory = stations();
+
+        assert_eq!(
+            search(&directory, StationSearch::default().name("JAZZ")),
+            ["jazz-fm", "smooth", "broken", "fip"]
+        );
+        assert_eq!(
+            search(&directory, StationSearch::default().codec("aac")),
+            ["smooth", "fip"]
+        );
+        let bitrates = StationSearch::default().bitrate_min(100).bitrate_max(192);
+        assert_eq!(
+            search(&directory, bitrates),
+            ["jazz-fm", "smooth", "broken"]
+        );
+        assert_eq!(
+            search(&directory, StationSearch::default().countrycode("es")),
+            ["clasica"]
+        );
+        let tags = [String::from("jazz"), String::from("lounge")];
+        assert_eq!(
+            search(&directory, StationSearch::default().tag_list(&tags)),
+            ["smooth"]
+        );
+    }
+
+    #[test]
+    fn finds_clicks_and_votes_by_id() {
+        let directory = stations();
+
+        assert_eq!(
+            directory.station("fip").unwrap().unwrap().name,
+            "jazz radio"
+        );
+        assert!(directory.station("nope").unwrap().is_none());
+
+        // The resolved URL, not the playlist
+        assert_eq!(
+            directory.click("jazz-fm").unwrap(),
+            "http://jazzfm.example/live"
+        );
+        assert!(directory.click("nope").is_err());
+        assert!(directory.vote("clasica").is_ok());
+        assert!(directory.vote("nope").is_err());
+    }
+
+    #[test]
+    fn counts_the_groups() {
+        let directory = stations();
+        let count = |groups: Vec<Group>, name: &str| {
+            groups
+                .into_iter()
+                .find(|g| g.name == name)
+                .map(|g| g.stationcount)
+        };
+
+        let tags = directory.groups(Category::Tags, None, true).unwrap();
+        assert_eq!(count(tags, "jazz"), Some(3));
+        let tags = directory.groups(Category::Tags, None, false).unwrap();
+        assert_eq!(count(tags, "jazz"), Some(4));
+
+        let states = directory
+            .groups(Category::States, Some("Spain"), true)
+            .unwrap();
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].name, "Madrid");
+        assert_eq!(states[0].country.as_deref(), Some("Spain"));
+    }
+
+    /// A directory that can't be reached
+    struct Unreachable;
+
+    impl StationDirectory for Unreachable {
+        fn search(&self, _search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+            Err("Unreachable".into())
+        }
+
+        fn station(&self, _uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+            Err("Unreachable".into())
+        }
+
+        fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+            Err("Unreachable".into())
+        }
+
+        fn groups(
+            &self,
+            _category: Category,
+            _country: Option<&str>,
+            _hidebroken: bool,
+        ) -> Result<Vec<Group>, Box<dyn Error>> {
+            Err("Unreachable".into())
+        }
+    }
+
+    fn listing(stations: &[(&str, &str)]) -> Box<dyn StationDirectory> {
+        Box::new(MemoryDirectory::new(
+            stations
+                .iter()
+                .map(|(uuid, url)| new_station(uuid, uuid, url))
+                .collect(),
+        ))
+    }
+
+    fn merged() -> MergedDirectory {
+        MergedDirectory {
+            directories: vec![
+                (
+                    "radio-browser",
+                    listing(&[
+                        ("a", "http://a.example/live"),
+                        ("b", "http://b.example/live"),
+                        ("b-again", "http://b.example/live"),
+                    ]),
+                ),
+                (
+                    "icecast",
+                    listing(&[
+                        ("icecast:a", "https://a.example/live"),
+                        ("icecast:b", "http://B.example/live/"),
+                        ("icecast:c", "http://c.example/live"),
+                        ("icecast:c-again", "https://c.example/live/"),
+                    ]),
+                ),
+                ("opml", listing(&[("opml:d", "http://d.example/live")])),
+            ],
+        }
+    }
+
+    #[test]
+    fn compares_stream_urls_without_scheme_case_or_trailing_slash() {
+        assert_eq!(
+            stream_key(" HTTPS://Radio.example/Live/ "),
+            "radio.example/live"
+        );
+        assert_eq!(
+            stream_key("http://radio.example/live"),
+            stream_key("https://radio.example/live/")
+        );
+        assert_ne!(
+            stream_key("http://radio.example/live"),
+            stream_key("http://radio.example/live.mp3")
+        );
+    }
+
+    #[test]
+    fn merges_the_other_directories_into_the_first_page() {
+        let directory = merged();
+
+        // The first directory keeps its own duplicates, the others only add new streams
+        assert_eq!(
+            search(&directory, StationSearch::default()),
+            ["a", "b", "b-again", "icecast:c", "opml:d"]
+        );
+        // The limit is that of each directory, and both stations Icecast gives are there
+        assert_eq!(
+            search(&directory, StationSearch::default().offset(0).limit(2)),
+            ["a", "b", "opml:d"]
+        );
+
+        // Later pages are those of the first directory alone
+        assert_eq!(
+            search(&directory, StationSearch::default().offset(2).limit(2)),
+            ["b-again"]
+        );
+        assert!(search(&directory, StationSearch::default().offset(5)).is_empty());
+    }
+
+    #[test]
+    fn searches_past_directories_that_fail() {
+        let directory = MergedDirectory {
+            directories: vec![
+                ("radio-browser", Box::new(Unreachable)),
+                (
+                    "icecast",
+                    listing(&[("icecast:a", "http://a.example/live")]),
+                ),
+            ],
+        };
+        assert_eq!(search(&directory, StationSearch::default()), ["icecast:a"]);
+
+        // An error only when nothing was found
+        let directory = MergedDirectory {
+            directories: vec![
+                ("radio-browser", Box::new(Unreachable)),
+                ("icecast", listing(&[])),
+            ],
+        };
+        assert!(directory.search(&StationSearch::default()).is_err());
+    }
+
+    #[test]
+    fn sends_clicks_and_votes_to_the_directory_of_the_station() {
+        let directory = merged();
+
+        assert_eq!(
+            directory.click("icecast:c").unwrap(),
+            "http://c.example/live"
+        );
+        assert_eq!(directory.click("a").unwrap(), "http://a.example/live");
+        // Not looked for in the other directories
+        assert!(directory.click("opml:a").is_err());
+        assert!(directory.station("icecast:a").unwrap().is_some());
+        assert!(directory.station("opml:a").unwrap().is_none());
+        assert!(directory.vote("shoutcast:1").is_err());
+    }
+}
@@ -6,6 +6,7 @@ pub enum ConfigErrorCode {
     ReadError,
     CloseError,
     ParseError,
+    WriteError,
 }
 
 pub struct ConfigError {
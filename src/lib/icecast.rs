@@ -0,0 +1,177 @@
+//! The Icecast directory (<https://dir.xiph.org>), from its XML "yellow pages" listing.
+//!
+//! The whole listing is downloaded the first time it's needed and searched locally.
+
+use radiobrowser::{ApiCountry, ApiStation};
+use reqwest::blocking::Client;
+use std::cell::OnceCell;
+use std::error::Error;
+
+use crate::browser::{Category, Group};
+use crate::directory::{MemoryDirectory, StationDirectory, new_station};
+use crate::radio_browser::StationSearch;
+use crate::xml;
+
+pub const ICECAST_URL: &str = "http://dir.xiph.org/yp.xml";
+
+pub struct IcecastDirectory {
+    client: Client,
+    url: String,
+    listing: OnceCell<MemoryDirectory>,
+}
+
+impl IcecastDirectory {
+    pub fn new(client: Client, url: &str) -> IcecastDirectory {
+        IcecastDirectory {
+            client,
+            url: url.to_string(),
+            listing: OnceCell::new(),
+        }
+    }
+
+    fn listing(&self) -> Result<&MemoryDirectory, Box<dyn Error>> {
+        if let Some(listing) = self.listing.get() {
+            return Ok(listing);
+        }
+
+        let body = self
+            .client
+            .get(&self.url)
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let listing = MemoryDirectory::new(parse_listing(&body)?);
+
+        Ok(self.listing.get_or_init(|| listing))
+    }
+}
+
+impl StationDirectory for IcecastDirectory {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        self.listing()?.search(search)
+    }
+
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        self.listing()?.station(uuid)
+    }
+
+    /// The listing doesn't say where the stations are
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        self.listing()?.groups(category, country, hidebroken)
+    }
+}
+
+/// The stations of a `yp.xml` listing
+pub fn parse_listing(body: &str) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+    let root = xml::parse(body)?;
+
+    Ok(root
+        .children
+        .iter()
+        .filter(|e| e.name == "entry")
+        .filter_map(|entry| {
+            let name = entry.child_text("server_name")?;
+            let url = entry.child_text("listen_url")?;
+
+            let mut station = new_station(&format!("icecast:{}", url), name, url);
+            station.codec = codec(entry.child_text("server_type").unwrap_or("")).to_string();
+            station.bitrate = entry
+                .child_text("bitrate")
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0);
+            station.tags = entry
+                .child_text("genre")
+                .unwrap_or("")
+                .split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(",");
+
+            Some(station)
+        })
+        .collect())
+}
+
+/// Codec of a MIME type, as Radio Browser calls it
+fn codec(server_type: &str) -> &str {
+    match server_type {
+        "audio/mpeg" => "MP3",
+        "audio/aac" => "AAC",
+        "audio/aacp" => "AAC+",
+        "application/ogg" | "audio/ogg" => "OGG",
+        "audio/opus" => "OPUS",
+        "audio/flac" => "FLAC",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestServer, fixture, response};
+    use std::fs;
+
+    #[test]
+    fn reads_the_entries_of_a_listing() {
+        let stations = parse_listing(&fs::read_to_string(fixture("yp.xml")).unwrap()).unwrap();
+
+        let names: Vec<&str> = stations.iter().map(|s| s.name.as_str()).collect();
+        // The one without an address is left out
+        assert_eq!(
+            names,
+            ["Radio & Jazz", "Ogg Classics", "Rock <Live>", "Mystery"]
+        );
+
+        let jazz = &stations[0];
+        assert_eq!(jazz.stationuuid, "icecast:http://jazz.example:8000/stream");
+        assert_eq!(jazz.url, "http://jazz.example:8000/stream");
+        assert_eq!(jazz.codec, "MP3");
+        assert_eq!(jazz.bitrate, 128);
+        assert_eq!(jazz.tags, "jazz,smooth");
+
+        assert_eq!(stations[1].codec, "OGG");
+        assert_eq!(stations[1].bitrate, 0);
+        assert_eq!(stations[1].tags, "");
+        assert_eq!(stations[2].codec, "AAC+");
+        assert_eq!(stations[2].tags, "rock,metal");
+        assert_eq!(stations[3].codec, "");
+    }
+
+    #[test]
+    fn rejects_what_is_not_xml() {
+        assert!(parse_listing("").is_err());
+        assert!(parse_listing("<directory><entry>").is_err());
+        assert!(parse_listing("<html></html>").unwrap().is_empty());
+    }
+
+    #[test]
+    fn downloads_the_listing_once() {
+        let body = fs::read(fixture("yp.xml")).unwrap();
+        let server = TestServer::start(move |_, _| {
+            response("200 OK", &[("Content-Type", "text/xml")], &body)
+        });
+        let directory = IcecastDirectory::new(Client::new(), &format!("{}/yp.xml", server.url));
+
+        let jazz = directory
+            .search(&StationSearch::default().tag("jazz"))
+            .unwrap();
+        assert_eq!(jazz.len(), 1);
+        let rock = directory
+            .station("icecast:http://rock.example/live")
+            .unwrap()
+            .unwrap();
+        assert_eq!(rock.name, "Rock <Live>");
+        assert!(directory.countries().unwrap().is_empty());
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["/yp.xml"]);
+    }
+}
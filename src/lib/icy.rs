@@ -0,0 +1,834 @@
+use crate::station::Station;
+
+use chrono::Local;
+use log::{debug, warn};
+use reqwest::blocking::{Client, Response};
+use reqwest::header::CONTENT_TYPE;
+use std::fs::OpenOptions;
+use std::io::{Error, Read, Result, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A read that doesn't return anything for this long is considered a dropped connection.
+const STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many playlists (.m3u, .pls) are followed before giving up.
+const MAX_PLAYLIST_DEPTH: usize = 3;
+
+/// Longest wait between two reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Wait before the first reconnection attempt, doubled after each failure.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+
+/// An open stream, with the metadata blocks already stripped from the audio
+pub struct IcyStream {
+    pub reader: IcyReader<Response>,
+    /// Lowercase `Content-Type` of the audio
+    pub content_type: String,
+    /// Name of the station, as announced by the server
+    pub name: Option<String>,
+}
+
+/// HTTP client suited for long-lived streams
+pub fn client() -> Result<Client> {
+    Client::builder()
+        .timeout(STALL_TIMEOUT)
+        .user_agent(concat!("radio-cli/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(Error::other)
+}
+
+/// Reads the audio of a Shoutcast/Icecast stream, stripping the ICY metadata blocks.
+///
+/// When the server is asked for `Icy-MetaData: 1`, it interleaves a metadata block every
+/// `icy-metaint` bytes of audio. The first byte of the block is its length divided by 16,
+/// and the block itself looks like `StreamTitle='Artist - Song';StreamUrl='';`.
+pub struct IcyReader<R> {
+    inner: R,
+    metaint: Option<usize>,
+    /// Audio bytes left until the next metadata block
+    until_meta: usize,
+    title: Option<String>,
+    changed: bool,
+}
+
+impl<R: Read> IcyReader<R> {
+    /// `metaint` is the value of the `icy-metaint` response header, if any.
+    pub fn new(inner: R, metaint: Option<usize>) -> IcyReader<R> {
+        IcyReader {
+            inner,
+            metaint: metaint.filter(|m| *m > 0),
+            until_meta: metaint.unwrap_or(0),
+            title: None,
+            changed: false,
+        }
+    }
+
+    /// Last title announced by the stream
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Returns the new title if it changed since the last call.
+    /// The audio returned by the last `read` already belongs to the new title.
+    pub fn take_title_change(&mut self) -> Option<String> {
+        if !self.changed {
+            return None;
+        }
+
+        self.changed = false;
+        self.title.clone()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_metadata(&mut self) -> Result<()> {
+        let mut length = [0u8; 1];
+        self.inner.read_exact(&mut length)?;
+
+        let mut block = vec![0u8; length[0] as usize * 16];
+        self.inner.read_exact(&mut block)?;
+
+        if let Some(title) = parse_stream_title(&block)
+            && self.title.as_deref() != Some(title.as_str())
+        {
+            self.title = Some(title);
+            self.changed = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let Some(metaint) = self.metaint else {
+            return self.inner.read(buf);
+        };
+
+        if self.until_meta == 0 {
+            self.read_metadata()?;
+            self.until_meta = metaint;
+        }
+
+        let max = buf.len().min(self.until_meta);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.until_meta -= n;
+
+        Ok(n)
+    }
+}
+
+/// Writes audio interleaving ICY metadata blocks, the other side of `IcyReader`.
+///
+/// A block with the title is sent when it changes, and empty blocks otherwise.
+pub struct IcyWriter<W> {
+    inner: W,
+    metaint: Option<usize>,
+    /// Audio bytes left until the next metadata block
+    until_meta: usize,
+    title: Option<String>,
+    sent: Option<String>,
+}
+
+impl<W: Write> IcyWriter<W> {
+    /// Without a `metaint` the audio is written as is, for clients that didn't ask for metadata.
+    pub fn new(inner: W, metaint: Option<usize>) -> IcyWriter<W> {
+        IcyWriter {
+            inner,
+            metaint: metaint.filter(|m| *m > 0),
+            until_meta: metaint.unwrap_or(0),
+            title: None,
+            sent: None,
+        }
+    }
+
+    /// Title sent in the next metadata block
+    pub fn set_title(&mut self, title: Option<&str>) {
+        if self.title.as_deref() != title {
+            self.title = title.map(str::to_string);
+        }
+    }
+
+    fn write_metadata(&mut self) -> Result<()> {
+        if self.title == self.sent {
+            return self.inner.write_all(&[0]);
+        }
+
+        self.sent = self.title.clone();
+        self.inner
+            .write_all(&metadata_block(self.title.as_deref().unwrap_or("")))
+    }
+}
+
+impl<W: Write> Write for IcyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let Some(metaint) = self.metaint else {
+            return self.inner.write(buf);
+        };
+
+        if self.until_meta == 0 {
+            self.write_metadata()?;
+            self.until_meta = metaint;
+        }
+
+        let max = buf.len().min(self.until_meta);
+        let n = self.inner.write(&buf[..max])?;
+        self.until_meta -= n;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Metadata block announcing a title: its length divided by 16, then the text padded with zeros
+pub fn metadata_block(title: &str) -> Vec<u8> {
+    // The length has to fit in a byte once divided by 16
+    let mut text = format!("StreamTitle='{}';", title).into_bytes();
+    text.truncate(255 * 16);
+
+    let blocks = text.len().div_ceil(16);
+    text.resize(blocks * 16, 0);
+    text.insert(0, blocks as u8);
+
+    text
+}
+
+/// Opens the stream asking for ICY metadata, following playlists if needed.
+pub fn connect(client: &Client, url: &str) -> Result<IcyStream> {
+    let mut url = url.to_string();
+
+    for _ in 0..=MAX_PLAYLIST_DEPTH {
+        debug!("Connecting to {}", url);
+
+        let response = client
+            .get(&url)
+            .header("Icy-MetaData", "1")
+            .send()
+            .and_then(Response::error_for_status)
+            .map_err(Error::other)?;
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_lowercase();
+
+        if is_playlist(&content_type, &url) {
+            let body = response.text().map_err(Error::other)?;
+            url = match first_playlist_entry(&body) {
+                Some(u) => u,
+                None => return Err(Error::other(format!("{} has no playable entries", url))),
+            };
+            continue;
+        }
+
+        let name = response
+            .headers()
+            .get("icy-name")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let metaint = response
+            .headers()
+            .get("icy-metaint")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<usize>().ok());
+
+        return Ok(IcyStream {
+            reader: IcyReader::new(response, metaint),
+            content_type,
+            name,
+        });
+    }
+
+    Err(Error::other("Too many nested playlists"))
+}
+
+fn is_playlist(content_type: &str, url: &str) -> bool {
+    let path = url.split('?').next().unwrap_or(url).to_lowercase();
+
+    content_type.contains("mpegurl")
+        || content_type.contains("scpls")
+        || path.ends_with(".m3u")
+        || path.ends_with(".m3u8")
+        || path.ends_with(".pls")
+}
+
+/// First URL of an .m3u or .pls playlist. HLS playlists are not supported.
+fn first_playlist_entry(body: &str) -> Option<String> {
+    if body.contains("#EXT-X-") {
+        warn!("HLS streams are not supported");
+        return None;
+    }
+
+    body.lines()
+        .map(str::trim)
+        .map(|l| match l.split_once('=') {
+            // .pls: File1=http://...
+            Some((key, value)) if key.to_lowercase().starts_with("file") => value.trim(),
+            _ => l,
+        })
+        .find(|l| l.starts_with("http://") || l.starts_with("https://"))
+        .map(str::to_string)
+}
+
+/// A station read for as long as needed, reconnecting whenever the connection drops.
+///
+/// Reads only end (return 0) once the deadline is past. Until then, a dropped connection
+/// or a failed attempt to reconnect is followed by a wait that doubles every time.
+pub struct ReconnectingStream {
+    client: Client,
+    station: Station,
+    stream: Option<IcyStream>,
+    deadline: Option<Instant>,
+    backoff: Duration,
+    title: Option<String>,
+    title_changed: bool,
+    /// Lowercase `Content-Type` of the audio, from the first connection
+    pub content_type: String,
+    /// Name of the station, as announced by the server
+    pub name: Option<String>,
+    /// Times the connection dropped
+    pub reconnects: u32,
+}
+
+impl ReconnectingStream {
+    /// Connects to the station, failing if it can't be reached this first time
+    pub fn connect(station: &Station, deadline: Option<Instant>) -> Result<ReconnectingStream> {
+        let client = client()?;
+        let stream = connect(&client, &station.url)?;
+
+        Ok(ReconnectingStream {
+            client,
+            station: station.clone(),
+            content_type: stream.content_type.clone(),
+            name: stream.name.clone(),
+            stream: Some(stream),
+            deadline,
+            backoff: FIRST_BACKOFF,
+            title: None,
+            title_changed: false,
+            reconnects: 0,
+        })
+    }
+
+    /// Title currently playing, if the stream has announced any
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The title, if it changed since the last call. Reconnecting doesn't count as a change.
+    pub fn take_title_change(&mut self) -> Option<String> {
+        match std::mem::take(&mut self.title_changed) {
+            true => self.title.clone(),
+            false => None,
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Waits before the next attempt, but not past the deadline
+    fn back_off(&mut self) {
+        let wait = match self.deadline {
+            Some(d) => self
+                .backoff
+                .min(d.saturating_duration_since(Instant::now())),
+            None => self.backoff,
+        };
+
+        thread::sleep(wait);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+impl Read for ReconnectingStream {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        while !self.is_over() {
+            let Some(stream) = &mut self.stream else {
+                match connect(&self.client, &self.station.url) {
+                    Ok(s) => self.stream = Some(s),
+                    Err(e) => {
+                        warn!("Could not reconnect to {}: {}", self.station.station, e);
+                        self.back_off();
+                    }
+                }
+                continue;
+            };
+
+            match stream.reader.read(buf) {
+                Ok(0) => warn!("The stream of {} ended", self.station.station),
+                Ok(n) => {
+                    // We got data, so the connection is healthy again
+                    self.backoff = FIRST_BACKOFF;
+
+                    if let Some(title) = stream.reader.take_title_change()
+                        && self.title.as_deref() != Some(title.as_str())
+                    {
+                        self.title = Some(title);
+                        self.title_changed = true;
+                    }
+
+                    return Ok(n);
+                }
+                Err(e) => warn!("Lost the connection to {}: {}", self.station.station, e),
+            }
+
+            self.stream = None;
+            if !self.is_over() {
+                self.reconnects += 1;
+                self.back_off();
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+/// Follows the titles of a stream in the background, reconnecting when needed.
+///
+/// The stream is only read to find the titles, the audio is thrown away.
+/// It stops when dropped.
+pub struct TitleWatcher {
+    titles: Receiver<String>,
+    stop: Arc<AtomicBool>,
+}
+
+impl TitleWatcher {
+    pub fn new(url: &str) -> Result<TitleWatcher> {
+        let client = client()?;
+        let url = url.to_string();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, titles) = channel();
+
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 8 * 1024];
+            let mut backoff = FIRST_BACKOFF;
+            let mut last: Option<String> = None;
+
+            while !stopped.load(Ordering::Relaxed) {
+                let mut stream = match connect(&client, &url) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        debug!("Could not follow the titles of {}: {}", url, e);
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                        continue;
+                    }
+                };
+
+                if stream.reader.metaint.is_none() {
+                    debug!("{} does not send titles", url);
+                    return;
+                }
+
+                while !stopped.load(Ordering::Relaxed) {
+                    match stream.reader.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => backoff = FIRST_BACKOFF,
+                    }
+
+                    // Reconnecting announces the same title again
+                    if let Some(title) = stream.reader.take_title_change()
+                        && last.as_deref() != Some(title.as_str())
+                    {
+                        last = Some(title.clone());
+                        if tx.send(title).is_err() {
+                            return;
+                        }
+                    }
+                }
+
+                thread::sleep(backoff);
+            }
+        });
+
+        Ok(TitleWatcher { titles, stop })
+    }
+
+    /// Next title change, if there was one. `None` also when the stream has no titles.
+    pub fn try_next(&self) -> Option<String> {
+        self.titles.try_recv().ok()
+    }
+}
+
+impl Drop for TitleWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Appends `<date>\t<station>\t<title>` lines to a file
+pub struct TitleLog {
+    path: PathBuf,
+}
+
+impl TitleLog {
+    pub fn new(path: PathBuf) -> TitleLog {
+        TitleLog { path }
+    }
+
+    pub fn append(&self, station: &str, title: &str) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        writeln!(
+            file,
+            "{}\t{}\t{}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            station,
+            title
+        )
+    }
+}
+
+/// Extracts the `StreamTitle` of a metadata block, `None` if it is missing or empty.
+pub fn parse_stream_title(block: &[u8]) -> Option<String> {
+    let end = block.iter().position(|b| *b == 0).unwrap_or(block.len());
+    let text = String::from_utf8_lossy(&block[..end]);
+
+    let start = text.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &text[start..];
+
+    // The title itself may contain quotes, so look for the field terminator
+    let title = match rest.find("';") {
+        Some(i) => &rest[..i],
+        None => rest.trim_end_matches('\''),
+    };
+
+    let title = title.trim();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestServer, icy_stream, response};
+    use std::io::Cursor;
+
+    /// Hands out at most `size` bytes per read, like a slow network
+    struct Trickle<R> {
+        inner: R,
+        size: usize,
+    }
+
+    impl<R: Read> Read for Trickle<R> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let max = buf.len().min(self.size);
+            self.inner.read(&mut buf[..max])
+        }
+    }
+
+    /// All the audio of a reader, with the titles announced along the way
+    fn read_all<R: Read>(reader: &mut IcyReader<R>) -> (Vec<u8>, Vec<String>) {
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        let mut buf = [0u8; 7];
+
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            if let Some(title) = reader.take_title_change() {
+                titles.push(title);
+            }
+            audio.extend_from_slice(&buf[..n]);
+        }
+
+        (audio, titles)
+    }
+
+    /// `metaint` bytes of audio and a metadata block, over and over
+    fn fixture(metaint: usize, parts: &[(&[u8], &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (audio, block) in parts {
+            assert_eq!(audio.len(), metaint);
+            bytes.extend_from_slice(audio);
+            bytes.extend_from_slice(block);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_stream_titles() {
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Artist - Song';StreamUrl='';\0\0\0"),
+            Some(String::from("Artist - Song"))
+        );
+        assert_eq!(parse_stream_title(b"StreamTitle='';\0"), None);
+        assert_eq!(parse_stream_title(b"StreamUrl='http://x';"), None);
+        assert_eq!(parse_stream_title(b""), None);
+    }
+
+    #[test]
+    fn titles_can_have_quotes() {
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Guns N' Roses - Don't Cry';StreamUrl='';"),
+            Some(String::from("Guns N' Roses - Don't Cry"))
+        );
+        // Without the terminator
+        assert_eq!(
+            parse_stream_title(b"StreamTitle='Rock 'n' Roll'\0\0"),
+            Some(String::from("Rock 'n' Roll"))
+        );
+    }
+
+    #[test]
+    fn strips_the_metadata_from_the_audio() {
+        let bytes = fixture(
+            4,
+            &[
+                (b"aaaa", &metadata_block("First")),
+                (b"bbbb", &[0]),
+                (b"cccc", &metadata_block("Second")),
+                (b"dddd", &[0]),
+            ],
+        );
+
+        let mut reader = IcyReader::new(Cursor::new(bytes), Some(4));
+        let (audio, titles) = read_all(&mut reader);
+
+        assert_eq!(audio, b"aaaabbbbccccdddd");
+        assert_eq!(titles, vec!["First", "Second"]);
+        assert_eq!(reader.title(), Some("Second"));
+    }
+
+    #[test]
+    fn empty_blocks_keep_the_title() {
+        let bytes = fixture(
+            2,
+            &[
+                (b"aa", &metadata_block("Song")),
+                (b"bb", &[0]),
+                (b"cc", &[0]),
+                (b"dd", &metadata_block("Song")),
+            ],
+        );
+
+        let mut reader = IcyReader::new(Cursor::new(bytes), Some(2));
+        let (audio, titles) = read_all(&mut reader);
+
+        assert_eq!(audio, b"aabbccdd");
+        // The same title again is not a change
+        assert_eq!(titles, vec!["Song"]);
+    }
+
+    #[test]
+    fn metadata_can_arrive_split_across_reads() {
+        let bytes = fixture(
+            5,
+            &[
+                (
+                    b"aaaaa",
+                    &metadata_block("A rather long title, in several reads"),
+                ),
+                (b"bbbbb", &[0]),
+            ],
+        );
+
+        let trickle = Trickle {
+            inner: Cursor::new(bytes),
+            size: 3,
+        };
+        let mut reader = IcyReader::new(trickle, Some(5));
+        let (audio, titles) = read_all(&mut reader);
+
+        assert_eq!(audio, b"aaaaabbbbb");
+        assert_eq!(titles, vec!["A rather long title, in several reads"]);
+    }
+
+    #[test]
+    fn without_metaint_everything_is_audio() {
+        let bytes = b"StreamTitle='Not a title';".to_vec();
+
+        for metaint in [None, Some(0)] {
+            let mut reader = IcyReader::new(Cursor::new(bytes.clone()), metaint);
+            let (audio, titles) = read_all(&mut reader);
+
+            assert_eq!(audio, bytes);
+            assert!(titles.is_empty());
+        }
+    }
+
+    #[test]
+    fn a_cut_block_is_an_error() {
+        let mut bytes = b"aaaa".to_vec();
+        bytes.extend_from_slice(&metadata_block("Cut")[..5]);
+
+        let mut reader = IcyReader::new(Cursor::new(bytes), Some(4));
+        let mut buf = [0u8; 16];
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert!(reader.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn metadata_blocks_are_padded() {
+        let block = metadata_block("Song");
+
+        assert_eq!(block[0], 2);
+        assert_eq!(block.len(), 1 + 32);
+        assert_eq!(&block[1..19], b"StreamTitle='Song'");
+        assert!(block[20..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn what_is_written_reads_back() {
+        let mut bytes = Vec::new();
+        let mut writer = IcyWriter::new(&mut bytes, Some(8));
+        writer.set_title(Some("It's the first"));
+        writer.write_all(&[1; 20]).unwrap();
+        writer.set_title(Some("Second"));
+        writer.write_all(&[2; 20]).unwrap();
+        writer.set_title(None);
+        writer.write_all(&[3; 20]).unwrap();
+
+        let mut reader = IcyReader::new(Cursor::new(bytes), Some(8));
+        let (audio, titles) = read_all(&mut reader);
+
+        let mut expected = vec![1; 20];
+        expected.extend_from_slice(&[2; 20]);
+        expected.extend_from_slice(&[3; 20]);
+        assert_eq!(audio, expected);
+        assert_eq!(titles, vec!["It's the first", "Second"]);
+    }
+
+    #[test]
+    fn writes_plain_audio_without_metaint() {
+        let mut bytes = Vec::new();
+        let mut writer = IcyWriter::new(&mut bytes, None);
+        writer.set_title(Some("Song"));
+        writer.write_all(b"audio").unwrap();
+
+        assert_eq!(bytes, b"audio");
+    }
+
+    #[test]
+    fn finds_the_stream_of_playlists() {
+        assert_eq!(
+            first_playlist_entry("#EXTM3U\n#EXTINF:-1,Radio\nhttp://radio/stream\n"),
+            Some(String::from("http://radio/stream"))
+        );
+        assert_eq!(
+            first_playlist_entry("[playlist]\nNumberOfEntries=1\nFile1=https://radio/live\n"),
+            Some(String::from("https://radio/live"))
+        );
+        assert_eq!(
+            first_playlist_entry("#EXTM3U\n#EXT-X-VERSION:3\nx.ts"),
+            None
+        );
+        assert!(is_playlist("audio/x-scpls", "http://radio/listen"));
+        assert!(is_playlist("", "http://radio/listen.m3u?id=1"));
+        assert!(!is_playlist("audio/mpeg", "http://radio/stream"));
+    }
+
+    #[test]
+    fn connects_to_streams_without_metaint() {
+        let server = TestServer::scripted(vec![response(
+            "200 OK",
+            &[("Content-Type", "Audio/MPEG")],
+            b"StreamTitle='x';",
+        )]);
+
+        let mut stream = connect(&client().unwrap(), &server.url).unwrap();
+        let mut audio = Vec::new();
+        stream.reader.read_to_end(&mut audio).unwrap();
+
+        assert_eq!(stream.content_type, "audio/mpeg");
+        assert_eq!(stream.name, None);
+        assert_eq!(audio, b"StreamTitle='x';");
+        assert_eq!(stream.reader.title(), None);
+    }
+
+    #[test]
+    fn follows_playlists_to_the_stream() {
+        let stream = TestServer::scripted(vec![icy_stream(4, &[(Some("Song"), b"audio")])]);
+        let playlist = format!("[playlist]\nFile1={}/live\n", stream.url);
+        let server = TestServer::scripted(vec![response(
+            "200 OK",
+            &[("Content-Type", "audio/x-scpls")],
+            playlist.as_bytes(),
+        )]);
+
+        let mut stream = connect(&client().unwrap(), &server.url).unwrap();
+        let mut audio = Vec::new();
+        stream.reader.read_to_end(&mut audio).unwrap();
+
+        assert_eq!(stream.name.as_deref(), Some("Fixture FM"));
+        assert_eq!(audio, b"audio");
+        assert_eq!(stream.reader.title(), Some("Song"));
+    }
+
+    #[test]
+    fn reconnects_until_the_deadline_announcing_only_new_titles() {
+        let server = TestServer::scripted(vec![
+            icy_stream(4, &[(Some("Song"), b"first")]),
+            icy_stream(4, &[(Some("Song"), b"second")]),
+            icy_stream(4, &[(Some("Next song"), b"third")]),
+        ]);
+        let station = Station {
+            station: String::from("Fixture FM"),
+            url: server.url.clone(),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(2500);
+
+        let mut stream = ReconnectingStream::connect(&station, Some(deadline)).unwrap();
+        let mut audio = Vec::new();
+        let mut titles = Vec::new();
+        let mut buf = [0u8; 64];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            audio.extend_from_slice(&buf[..n]);
+            titles.extend(stream.take_title_change());
+        }
+
+        assert_eq!(audio, b"firstsecondthird");
+        assert_eq!(titles, ["Song", "Next song"]);
+        assert_eq!(stream.title(), Some("Next song"));
+        assert_eq!(stream.reconnects, 3);
+        assert_eq!(stream.name.as_deref(), Some("Fixture FM"));
+        assert_eq!(stream.content_type, "audio/mpeg");
+
+        // The waits are cut short by the deadline
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(2500), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(4000), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn unreachable_streams_fail_at_once() {
+        let server = TestServer::scripted(vec![]);
+        let station = Station {
+            station: String::from("Fixture FM"),
+            url: server.url.clone(),
+            ..Default::default()
+        };
+
+        assert!(ReconnectingStream::connect(&station, None).is_err());
+    }
+}
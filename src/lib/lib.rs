@@ -1,14 +1,43 @@
+pub mod alarm;
+pub mod api;
 pub mod browser;
 mod cli_args;
 mod config;
+pub mod controls;
+pub mod country;
+pub mod daemon;
+pub mod directory;
 mod errors;
+pub mod icecast;
+pub mod icy;
+pub mod local;
+#[cfg(feature = "mpris")]
+pub mod mpris;
+pub mod opml;
+pub mod player;
+pub mod podcast;
+pub mod query;
+pub mod radio_browser;
+pub mod record;
+pub mod relay;
+pub mod scheduler;
+pub mod shoutcast;
+pub mod state;
 mod station;
+#[cfg(test)]
+mod testing;
+pub mod timer;
+pub mod tui;
 mod version;
+pub mod xml;
 
-pub use cli_args::Cli;
+pub use cli_args::{
+    AlarmArgs, BrowseArgs, Cli, Commands, CtlArgs, CtlCommand, DaemonArgs, DevicesArgs,
+    PodcastArgs, RecordArgs, RelayArgs, SchedulerArgs, ServeArgs, VoteArgs,
+};
 pub use config::Config;
 pub use errors::{ConfigError, ConfigErrorCode};
-pub use station::Station;
+pub use station::{Station, StationKind, station_near};
 pub use version::Version;
 
 use colored::*;
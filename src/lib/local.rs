@@ -0,0 +1,46 @@
+//! Stations on disk: a single file, or a folder of music played shuffled.
+
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files played from a folder, the rest (covers, playlists...) is skipped
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "aiff", "alac", "ape", "flac", "m4a", "mka", "mp3", "oga", "ogg", "opus", "wav", "wma",
+    "wv",
+];
+
+/// `path` with a leading `~` replaced by the home folder
+pub fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME");
+
+    match (path.strip_prefix("~"), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn is_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// The music in a folder and the ones inside it, sorted
+pub fn audio_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            files.extend(audio_files(&path)?);
+        } else if is_audio(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,449 @@
+//! MPRIS2 interface of the daemon, so media keys, `playerctl` and desktop widgets
+//! can see what's playing and control it.
+//!
+//! The D-Bus calls are answered in zbus' own thread, which only forwards them
+//! to the daemon as `Request`s and reads the last published `Status`.
+
+use crate::daemon::{Remote, Request, Status};
+
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::fdo;
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.radio_cli";
+
+/// A stream has no tracks, so all of its songs share this id
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// `org.mpris.MediaPlayer2`: the application itself
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "radio-cli"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`: what's playing and the controls
+struct PlayerInterface {
+    requests: Sender<Request>,
+    status: Status,
+}
+
+impl PlayerInterface {
+    fn send(&self, request: Request) {
+        if self.requests.send(request).is_err() {
+            debug!("The daemon is gone, MPRIS request dropped");
+        }
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl PlayerInterface {
+    fn next(&self) {
+        self.send(Request::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Request::Previous);
+    }
+
+    /// A live stream can't be paused, so it stops
+    fn pause(&self) {
+        self.send(Request::Stop);
+    }
+
+    fn play_pause(&self) {
+        self.send(Request::Toggle);
+    }
+
+    fn stop(&self) {
+        self.send(Request::Stop);
+    }
+
+    fn play(&self) {
+        if self.status.station.is_none() {
+            self.send(Request::Toggle);
+        }
+    }
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(String::from(
+            "Stations are played by name",
+        )))
+    }
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match self.status.station {
+            Some(_) => "Playing",
+            None => "Stopped",
+        }
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let mut insert = |key: &str, value: Value| {
+            if let Ok(v) = value.try_into_owned() {
+                metadata.insert(key.to_string(), v);
+            }
+        };
+
+        insert(
+            "mpris:trackid",
+            ObjectPath::from_static_str_unchecked(NO_TRACK).into(),
+        );
+
+        if let Some(station) = &self.status.station {
+            let title = self.status.title.as_ref().unwrap_or(station);
+
+            insert("xesam:title", title.clone().into());
+            insert("xesam:album", station.clone().into());
+        }
+
+        if let Some(url) = &self.status.url {
+            insert("xesam:url", url.clone().into());
+        }
+
+        metadata
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.status.volume / 100.0
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.send(Request::Volume {
+            level: Some(volume * 100.0),
+            delta: None,
+        });
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// The daemon as seen on D-Bus
+pub struct Mpris {
+    connection: Connection,
+    requests: Receiver<Request>,
+    status: Status,
+}
+
+impl Mpris {
+    /// Registers on the session bus
+    pub fn new() -> zbus::Result<Mpris> {
+        Mpris::register(Builder::session()?)
+    }
+
+    /// Registers on the bus at `address`, e.g. a private one
+    pub fn with_address(address: &str) -> zbus::Result<Mpris> {
+        Mpris::register(Builder::address(address)?)
+    }
+
+    fn register(builder: Builder) -> zbus::Result<Mpris> {
+        let (tx, requests) = channel();
+
+        let connection = builder
+            .name(BUS_NAME)?
+            .serve_at(PATH, Root)?
+            .serve_at(
+                PATH,
+                PlayerInterface {
+                    requests: tx,
+                    status: Status::default(),
+                },
+            )?
+            .build()?;
+
+        Ok(Mpris {
+            connection,
+            requests,
+            status: Status::default(),
+        })
+    }
+
+    fn update(&self, status: &Status) -> zbus::Result<()> {
+        let iface_ref = self
+            .connection
+            .object_server()
+            .interface::<_, PlayerInterface>(PATH)?;
+        let mut iface = iface_ref.get_mut();
+        iface.status = status.clone();
+
+        let emitter = iface_ref.signal_emitter();
+        zbus::block_on(async {
+            if status.station != self.status.station {
+                iface.playback_status_changed(emitter).await?;
+            }
+            if status.station != self.status.station
+                || status.title != self.status.title
+                || status.url != self.status.url
+            {
+                iface.metadata_changed(emitter).await?;
+            }
+            if status.volume != self.status.volume {
+                iface.volume_changed(emitter).await?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+impl Remote for Mpris {
+    fn requests(&mut self) -> Vec<Request> {
+        self.requests.try_iter().collect()
+    }
+
+    fn publish(&mut self, status: &Status) {
+        if *status == self.status {
+            return;
+        }
+
+        if let Err(e) = self.update(status) {
+            warn!("Could not update the MPRIS status: {}", e);
+        }
+        self.status = status.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::proxy::CacheProperties;
+
+    #[zbus::proxy(
+        interface = "org.mpris.MediaPlayer2.Player",
+        default_service = "org.mpris.MediaPlayer2.radio_cli",
+        default_path = "/org/mpris/MediaPlayer2"
+    )]
+    trait MediaPlayer {
+        fn play_pause(&self) -> zbus::Result<()>;
+        fn next(&self) -> zbus::Result<()>;
+        fn open_uri(&self, uri: &str) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> zbus::Result<String>;
+        #[zbus(property)]
+        fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+        #[zbus(property)]
+        fn volume(&self) -> zbus::Result<f64>;
+        #[zbus(property)]
+        fn set_volume(&self, volume: f64) -> zbus::Result<()>;
+    }
+
+    /// A private bus, stopped when dropped
+    struct Bus {
+        daemon: Child,
+        address: String,
+        _dir: tempfile::TempDir,
+    }
+
+    impl Bus {
+        /// `None` when `dbus-daemon` is not installed
+        fn start() -> Option<Bus> {
+            let dir = tempfile::tempdir().unwrap();
+            let mut daemon = Command::new("dbus-daemon")
+                .arg("--session")
+                .arg("--nofork")
+                .arg("--print-address=1")
+                .arg(format!(
+                    "--address=unix:path={}",
+                    dir.path().join("bus").display()
+                ))
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+
+            // It's listening once it tells where
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+
+            Some(Bus {
+                daemon,
+                address: address.trim().to_string(),
+                _dir: dir,
+            })
+        }
+
+        fn player(&self) -> MediaPlayerProxyBlocking<'static> {
+            let connection = Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap();
+
+            MediaPlayerProxyBlocking::builder(&connection)
+                .cache_properties(CacheProperties::No)
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    fn text(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+        metadata
+            .get(key)
+            .and_then(|v| String::try_from(v.clone()).ok())
+    }
+
+    #[test]
+    fn controls_and_status_on_a_private_bus() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping the MPRIS test");
+            return;
+        };
+
+        let mut mpris = Mpris::with_address(&bus.address).unwrap();
+        let player = bus.player();
+
+        assert_eq!(player.playback_status().unwrap(), "Stopped");
+        let metadata = player.metadata().unwrap();
+        assert_eq!(text(&metadata, "xesam:title"), None);
+        assert!(metadata.contains_key("mpris:trackid"));
+
+        player.play_pause().unwrap();
+        player.next().unwrap();
+        player.set_volume(0.25).unwrap();
+        assert!(player.open_uri("http://example.com/stream").is_err());
+
+        assert_eq!(
+            mpris.requests(),
+            [
+                Request::Toggle,
+                Request::Next,
+                Request::Volume {
+                    level: Some(25.0),
+                    delta: None
+                },
+            ]
+        );
+        assert!(mpris.requests().is_empty());
+
+        mpris.publish(&Status {
+            station: Some(String::from("Lofi")),
+            url: Some(String::from("http://lofi.example/stream")),
+            title: Some(String::from("Chill beats")),
+            volume: 40.0,
+            muted: false,
+        });
+
+        assert_eq!(player.playback_status().unwrap(), "Playing");
+        assert_eq!(player.volume().unwrap(), 0.4);
+        let metadata = player.metadata().unwrap();
+        assert_eq!(
+            text(&metadata, "xesam:title").as_deref(),
+            Some("Chill beats")
+        );
+        assert_eq!(text(&metadata, "xesam:album").as_deref(), Some("Lofi"));
+        assert_eq!(
+            text(&metadata, "xesam:url").as_deref(),
+            Some("http://lofi.example/stream")
+        );
+
+        mpris.publish(&Status::default());
+        assert_eq!(player.playback_status().unwrap(), "Stopped");
+    }
+}
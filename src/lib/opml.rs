@@ -0,0 +1,234 @@
+//! Directories published as OPML, like TuneIn's (`http://opml.radiotime.com`).
+//!
+//! A URL with `{query}` in it is a search, asked again for every query (e.g.
+//! `http://opml.radiotime.com/Search.ashx?query={query}`). Any other URL is a fixed list of
+//! stations, downloaded once and searched locally.
+
+use radiobrowser::{ApiCountry, ApiStation};
+use reqwest::blocking::Client;
+use std::cell::OnceCell;
+use std::error::Error;
+
+use crate::browser::{Category, Group};
+use crate::directory::{MemoryDirectory, StationDirectory, new_station};
+use crate::radio_browser::StationSearch;
+use crate::xml;
+
+pub struct OpmlDirectory {
+    client: Client,
+    url: String,
+    /// The stations of a fixed list
+    listing: OnceCell<MemoryDirectory>,
+}
+
+impl OpmlDirectory {
+    pub fn new(client: Client, url: &str) -> OpmlDirectory {
+        OpmlDirectory {
+            client,
+            url: url.to_string(),
+            listing: OnceCell::new(),
+        }
+    }
+
+    fn is_search(&self) -> bool {
+        self.url.contains("{query}")
+    }
+
+    fn download(&self, url: &str) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        let body = self.client.get(url).send()?.error_for_status()?.text()?;
+        parse_outlines(&body)
+    }
+
+    fn listing(&self) -> Result<&MemoryDirectory, Box<dyn Error>> {
+        if let Some(listing) = self.listing.get() {
+            return Ok(listing);
+        }
+
+        let listing = MemoryDirectory::new(self.download(&self.url)?);
+        Ok(self.listing.get_or_init(|| listing))
+    }
+}
+
+impl StationDirectory for OpmlDirectory {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        if !self.is_search() {
+            return self.listing()?.search(search);
+        }
+
+        // Searches need words, and give a single page
+        let query = search.get("name").or(search.get("tag")).unwrap_or("");
+        if query.is_empty() || search.get("offset").is_some_and(|o| o != "0") {
+            return Ok(Vec::new());
+        }
+
+        let stations = self.download(&self.url.replace("{query}", &percent_encode(query)))?;
+
+        // Found by the directory, which may look at more than the names
+        let search = search
+            .clone()
+            .without("name")
+            .without("tag")
+            .offset(0)
+            .limit(0);
+        MemoryDirectory::new(stations).search(&search)
+    }
+
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        match self.is_search() {
+            true => Ok(None),
+            false => self.listing()?.station(uuid),
+        }
+    }
+
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        match self.is_search() {
+            true => Ok(Vec::new()),
+            false => self.listing()?.groups(category, country, hidebroken),
+        }
+    }
+}
+
+/// The stations (`<outline type="audio" URL="...">`) of an OPML document, at any depth
+pub fn parse_outlines(body: &str) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+    let root = xml::parse(body)?;
+
+    Ok(root
+        .descendants()
+        .into_iter()
+        .filter(|e| e.name == "outline")
+        .filter(|e| e.attr("type").is_none_or(|t| t == "audio"))
+        .filter_map(|outline| {
+            let name = outline.attr("text").or(outline.attr("title"))?;
+            let url = outline.attr("URL")?;
+
+            let id = outline.attr("guide_id").unwrap_or(url);
+            let mut station = new_station(&format!("opml:{}", id), name, url);
+            station.codec = outline.attr("formats").unwrap_or("").to_uppercase();
+            station.bitrate = outline
+                .attr("bitrate")
+                .and_then(|b| b.parse().ok())
+                .unwrap_or(0);
+            station.tags = outline.attr("genre").unwrap_or("").to_string();
+            station.favicon = outline.attr("image").unwrap_or("").to_string();
+
+            Some(station)
+        })
+        .collect())
+}
+
+/// `value` ready to go in a query string
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b' ' => String::from("+"),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestServer, fixture, response};
+    use std::fs;
+
+    fn serve_fixture() -> TestServer {
+        let body = fs::read(fixture("tunein.opml")).unwrap();
+        TestServer::start(move |_, _| response("200 OK", &[("Content-Type", "text/x-opml")], &body))
+    }
+
+    #[test]
+    fn reads_the_audio_outlines_at_any_depth() {
+        let body = fs::read_to_string(fixture("tunein.opml")).unwrap();
+        let stations = parse_outlines(&body).unwrap();
+
+        let names: Vec<&str> = stations.iter().map(|s| s.name.as_str()).collect();
+        // Not the links to other lists, nor the outlines without a URL
+        assert_eq!(names, ["KJAZZ 88.1 (Jazz)", "Jazz24", "Plain Stream"]);
+
+        let kjazz = &stations[0];
+        assert_eq!(kjazz.stationuuid, "opml:s34682");
+        assert_eq!(kjazz.url, "http://opml.radiotime.com/Tune.ashx?id=s34682");
+        assert_eq!(kjazz.codec, "MP3");
+        assert_eq!(kjazz.bitrate, 128);
+        assert_eq!(kjazz.favicon, "http://cdn.example/s34682q.png");
+
+        assert_eq!(
+            stations[1].url,
+            "http://opml.radiotime.com/Tune.ashx?id=s129&partnerId=x"
+        );
+        assert_eq!(stations[1].codec, "AAC");
+        assert_eq!(stations[1].tags, "jazz");
+
+        // Without a guide id, the URL is the id
+        assert_eq!(stations[2].stationuuid, "opml:http://plain.example/live");
+        assert_eq!(stations[2].bitrate, 0);
+    }
+
+    #[test]
+    fn encodes_queries() {
+        assert_eq!(percent_encode("smooth jazz"), "smooth+jazz");
+        assert_eq!(percent_encode("a&b=c/d"), "a%26b%3Dc%2Fd");
+        assert_eq!(percent_encode("clásica"), "cl%C3%A1sica");
+        assert_eq!(percent_encode("A-z_0.9~"), "A-z_0.9~");
+    }
+
+    #[test]
+    fn searches_with_the_query_in_the_url() {
+        let server = serve_fixture();
+        let directory = OpmlDirectory::new(
+            Client::new(),
+            &format!("{}/Search.ashx?query={{query}}", server.url),
+        );
+
+        let found = directory
+            .search(&StationSearch::default().name("smooth jazz"))
+            .unwrap();
+        // All of them, as the directory found them by more than their names
+        assert_eq!(found.len(), 3);
+
+        // Nothing to look for, or a page the search doesn't have
+        assert!(
+            directory
+                .search(&StationSearch::default())
+                .unwrap()
+                .is_empty()
+        );
+        let next = StationSearch::default().name("jazz").offset(2);
+        assert!(directory.search(&next).unwrap().is_empty());
+        assert!(directory.station("opml:s129").unwrap().is_none());
+
+        let paths: Vec<String> = server.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["/Search.ashx?query=smooth+jazz"]);
+    }
+
+    #[test]
+    fn downloads_a_fixed_list_once() {
+        let server = serve_fixture();
+        let directory = OpmlDirectory::new(Client::new(), &format!("{}/jazz.opml", server.url));
+
+        let found = directory
+            .search(&StationSearch::default().name("jazz"))
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            directory.station("opml:s129").unwrap().unwrap().name,
+            "Jazz24"
+        );
+
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,604 @@
+use crate::local;
+use crate::station::{Station, StationKind};
+
+use log::{debug, log_enabled, trace};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Result, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::{NamedTempFile, TempDir};
+
+/// How long to wait for mpv to create its IPC socket.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for mpv to answer a command, so a stuck mpv doesn't block the controls
+const IPC_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Presets available without configuring anything, as mpv `--af` chains.
+/// The `presets` of the config can replace them.
+pub const BUILTIN_PRESETS: [(&str, &str); 3] = [
+    ("bass-boost", "bass=g=8"),
+    ("speech", "highpass=f=120,equalizer=f=3000:t=q:w=1:g=5"),
+    ("night", "acompressor=threshold=0.1:ratio=6:makeup=4"),
+];
+
+/// How mpv should be started
+#[derive(Debug, Clone, Default)]
+pub struct PlayerOptions {
+    pub show_video: bool,
+
+    /// Initial volume, from 0 to 100
+    pub volume: Option<f64>,
+
+    /// Play the same file over and over instead of exiting at the end
+    pub loop_file: bool,
+
+    /// Seconds into the file to start at, e.g. to resume an episode
+    pub start: Option<f64>,
+
+    /// mpv audio filters (`--af`), e.g. `loudnorm`
+    pub audio_filters: Vec<String>,
+
+    /// Filter presets by name, as mpv `--af` chains
+    pub presets: HashMap<String, String>,
+
+    /// Preset used for every station, instead of their own
+    pub preset: Option<String>,
+
+    /// Output device for every station, instead of their own
+    pub audio_device: Option<String>,
+
+    /// Output device of the stations that don't have their own
+    pub default_audio_device: Option<String>,
+
+    /// Keep mpv away from the terminal, for when radio-cli draws on it
+    pub no_terminal: bool,
+}
+
+impl PlayerOptions {
+    /// Name of the preset to play a station with, if any
+    pub fn preset_for<'a>(&'a self, station: &'a Station) -> Option<&'a str> {
+        self.preset.as_deref().or(station.preset.as_deref())
+    }
+
+    /// Output device to play a station on, if not the default one
+    pub fn audio_device_for<'a>(&'a self, station: &'a Station) -> Option<&'a str> {
+        self.audio_device
+            .as_deref()
+            .or(station.audio_device.as_deref())
+            .or(self.default_audio_device.as_deref())
+    }
+
+    /// All the audio filters to play a station with, its preset last
+    pub fn filters_for(&self, station: &Station) -> Result<Vec<String>> {
+        let mut filters = self.audio_filters.clone();
+
+        if let Some(name) = self.preset_for(station) {
+            match self.presets.get(name) {
+                Some(chain) => filters.push(chain.clone()),
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Unknown preset \"{}\"", name),
+                    ));
+                }
+            }
+        }
+
+        Ok(filters)
+    }
+}
+
+/// An audio output the player can play on
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioDevice {
+    /// What the player calls it, e.g. `alsa/hdmi:CARD=PCH,DEV=0`
+    pub name: String,
+    pub description: String,
+}
+
+impl std::fmt::Display for AudioDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.description, self.name)
+    }
+}
+
+/// Reads the devices listed by `mpv --audio-device=help`, one per line:
+/// `  'pulse/alsa_output.usb-DAC' (USB DAC Analog Stereo)`
+pub fn parse_audio_devices(output: &str) -> Vec<AudioDevice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().strip_prefix('\'')?;
+            let (name, rest) = line.split_once('\'')?;
+            let rest = rest.trim();
+            let description = rest
+                .strip_prefix('(')
+                .and_then(|r| r.strip_suffix(')'))
+                .unwrap_or(rest);
+
+            Some(AudioDevice {
+                name: name.to_string(),
+                description: description.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses a volume from 0 to 100
+pub fn parse_volume(s: &str) -> std::result::Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(v) if (0.0..=100.0).contains(&v) => Ok(v),
+        _ => Err(format!("\"{}\" is not a volume from 0 to 100", s)),
+    }
+}
+
+/// Controls for something that is playing, whatever plays it
+pub trait Player {
+    fn volume(&mut self) -> Result<f64>;
+    fn set_volume(&mut self, volume: f64) -> Result<()>;
+    fn paused(&mut self) -> Result<bool>;
+    fn set_paused(&mut self, paused: bool) -> Result<()>;
+    fn muted(&mut self) -> Result<bool>;
+    fn set_muted(&mut self, muted: bool) -> Result<()>;
+
+    /// Title announced by the stream (ICY metadata), if any
+    fn title(&mut self) -> Option<String>;
+
+    /// `Some` once the player has stopped by itself
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>>;
+    fn stop(&mut self) -> Result<()>;
+}
+
+/// A running mpv process, controlled through its JSON IPC socket.
+///
+/// radio-cli owns the keyboard and supervises the process, sending it commands
+/// (volume, pause, quit...) through the socket.
+pub struct Mpv {
+    child: Child,
+    /// Private folder of the IPC socket, removed with the player
+    _socket_dir: TempDir,
+    ipc: BufReader<UnixStream>,
+    request_id: u64,
+    /// Temporary playlist of a folder, removed with the player
+    _playlist: Option<NamedTempFile>,
+}
+
+impl Mpv {
+    /// Audio outputs mpv can play on
+    pub fn audio_devices() -> Result<Vec<AudioDevice>> {
+        let output = Command::new("mpv")
+            .arg("--audio-device=help")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+
+        if !output.status.success() {
+            return Err(Error::other(format!(
+                "mpv --audio-device=help {}",
+                output.status
+            )));
+        }
+
+        Ok(parse_audio_devices(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    pub fn spawn(station: &Station, options: &PlayerOptions) -> Result<Mpv> {
+        // Only this user can get to the socket, in a folder nobody else could have made
+        let socket_dir = tempfile::Builder::new().prefix("radio-cli-").tempdir_in(
+            std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from),
+        )?;
+        let socket = socket_dir.path().join("mpv.sock");
+
+        // The music of a folder is handed to mpv as a playlist
+        let mut playlist: Option<NamedTempFile> = None;
+        let target = match (station.kind, station.path()) {
+            (StationKind::Directory, Some(dir)) => {
+                let file = Mpv::write_playlist(&dir)?;
+                let arg = format!("--playlist={}", file.path().display());
+                playlist = Some(file);
+                arg
+            }
+            (_, Some(file)) => file.display().to_string(),
+            (_, None) => station.url.clone(),
+        };
+
+        let mut mpv_args: Vec<String> = vec![
+            target,
+            format!("--input-ipc-server={}", socket.display()),
+            String::from("--input-terminal=no"),
+        ];
+
+        if !options.show_video {
+            mpv_args.push(String::from("--no-video"));
+        }
+
+        if let Some(volume) = options.volume {
+            mpv_args.push(format!("--volume={}", volume));
+        }
+
+        if options.loop_file {
+            mpv_args.push(String::from("--loop-file=inf"));
+        }
+
+        // A folder plays like a station: in any order, and without an end
+        if playlist.is_some() {
+            mpv_args.push(String::from("--shuffle"));
+            mpv_args.push(String::from("--loop-playlist=inf"));
+        }
+
+        if let Some(start) = options.start {
+            mpv_args.push(format!("--start={}", start));
+        }
+
+        if let Some(device) = options.audio_device_for(station) {
+            mpv_args.push(format!("--audio-device={}", device));
+        }
+
+        let filters = options.filters_for(station)?;
+        if !filters.is_empty() {
+            mpv_args.push(format!("--af={}", filters.join(",")));
+        }
+
+        if options.no_terminal {
+            mpv_args.push(String::from("--no-terminal"));
+        } else if !log_enabled!(log::Level::Info) {
+            mpv_args.push(String::from("--really-quiet"));
+        }
+
+        debug!("Running mpv {:?}", mpv_args);
+
+        let (stdout, stderr) = if options.no_terminal {
+            (Stdio::null(), Stdio::null())
+        } else {
+            (Stdio::inherit(), Stdio::inherit())
+        };
+
+        let mut child = Command::new("mpv")
+            .args(mpv_args)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()?;
+
+        let stream = match Mpv::connect(&mut child, &socket) {
+            Ok(s) => s,
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
+            }
+        };
+
+        Ok(Mpv {
+            child,
+            _socket_dir: socket_dir,
+            ipc: BufReader::new(stream),
+            request_id: 0,
+            _playlist: playlist,
+        })
+    }
+
+    /// Writes the music of `dir` to a temporary playlist, removed when it's dropped
+    fn write_playlist(dir: &Path) -> Result<NamedTempFile> {
+        // Absolute, or mpv would look for them next to the playlist
+        let files = local::audio_files(&dir.canonicalize()?)?;
+        if files.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("There is no music in {}", dir.display()),
+            ));
+        }
+
+        let mut playlist = tempfile::Builder::new()
+            .prefix("radio-cli-")
+            .suffix(".m3u")
+            .tempfile()?;
+        for file in &files {
+            writeln!(playlist, "{}", file.display())?;
+        }
+        playlist.flush()?;
+
+        Ok(playlist)
+    }
+
+    fn connect(child: &mut Child, socket: &PathBuf) -> Result<UnixStream> {
+        let start = Instant::now();
+
+        loop {
+            match UnixStream::connect(socket) {
+                Ok(s) => {
+                    s.set_read_timeout(Some(IPC_TIMEOUT))?;
+                    return Ok(s);
+                }
+                Err(e) => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(Error::other(format!("mpv exited early ({})", status)));
+                    }
+
+                    if start.elapsed() > CONNECT_TIMEOUT {
+                        return Err(e);
+                    }
+
+                    thread::sleep(Duration::from_millis(50));
+                }
+            }
+        }
+    }
+
+    /// Sends a raw IPC command and returns its `data` field.
+    pub fn command(&mut self, command: Value) -> Result<Value> {
+        self.request_id += 1;
+        let request = json!({ "command": command, "request_id": self.request_id });
+        trace!("mpv <- {}", request);
+
+        let stream = self.ipc.get_mut();
+        stream.write_all(format!("{}\n", request).as_bytes())?;
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.ipc.read_line(&mut line)? == 0 {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "mpv closed the socket",
+                ));
+            }
+            trace!("mpv -> {}", line.trim_end());
+
+            let response: Value = match serde_json::from_str(&line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            // Events are sent to every client, skip them
+            if response.get("request_id").and_then(Value::as_u64) != Some(self.request_id) {
+                continue;
+            }
+
+            return match response.get("error").and_then(Value::as_str) {
+                Some("success") => Ok(response.get("data").cloned().unwrap_or(Value::Null)),
+                Some(e) => Err(Error::other(format!("mpv: {}", e))),
+                None => Err(Error::other("mpv: malformed response")),
+            };
+        }
+    }
+
+    pub fn get_property(&mut self, name: &str) -> Result<Value> {
+        self.command(json!(["get_property", name]))
+    }
+
+    pub fn set_property(&mut self, name: &str, value: Value) -> Result<()> {
+        self.command(json!(["set_property", name, value]))
+            .map(|_| ())
+    }
+
+    pub fn show_text(&mut self, text: &str) -> Result<()> {
+        self.command(json!(["show-text", text])).map(|_| ())
+    }
+
+    fn bool_property(&mut self, name: &str) -> Result<bool> {
+        self.get_property(name)?
+            .as_bool()
+            .ok_or_else(|| Error::other(format!("mpv: {} is not a boolean", name)))
+    }
+}
+
+impl Player for Mpv {
+    fn volume(&mut self) -> Result<f64> {
+        self.get_property("volume")?
+            .as_f64()
+            .ok_or_else(|| Error::other("mpv: volume is not a number"))
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<()> {
+        self.set_property("volume", volume.into())
+    }
+
+    fn paused(&mut self) -> Result<bool> {
+        self.bool_property("pause")
+    }
+
+    fn set_paused(&mut self, paused: bool) -> Result<()> {
+        self.set_property("pause", paused.into())
+    }
+
+    fn muted(&mut self) -> Result<bool> {
+        self.bool_property("mute")
+    }
+
+    fn set_muted(&mut self, muted: bool) -> Result<()> {
+        self.set_property("mute", muted.into())
+    }
+
+    fn title(&mut self) -> Option<String> {
+        self.get_property("metadata/by-key/icy-title")
+            .ok()?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    fn try_wait(&mut self) -> Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Asks mpv to quit and waits for it, killing it if it does not listen.
+    fn stop(&mut self) -> Result<()> {
+        let _ = self.command(json!(["quit"]));
+
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if self.child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        self.child.kill()?;
+        self.child.wait().map(|_| ())
+    }
+}
+
+impl Drop for Mpv {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+        // Then the playlist and the socket are removed with their fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn station(preset: Option<&str>) -> Station {
+        Station {
+            station: String::from("Fixture FM"),
+            url: String::from("http://fixture.example/live"),
+            preset: preset.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn puts_the_filters_before_the_preset() {
+        let mut config = testing::config(&[]);
+        config.presets = HashMap::from([(String::from("night"), String::from("volume=0.5"))]);
+        let options = PlayerOptions {
+            audio_filters: vec![String::from("loudnorm")],
+            presets: config.presets(),
+            ..Default::default()
+        };
+
+        // Those of the config replace the built-in ones
+        assert_eq!(
+            options.filters_for(&station(Some("night"))).unwrap(),
+            ["loudnorm", "volume=0.5"]
+        );
+        assert_eq!(
+            options.filters_for(&station(Some("speech"))).unwrap(),
+            ["loudnorm", "highpass=f=120,equalizer=f=3000:t=q:w=1:g=5"]
+        );
+        assert_eq!(options.filters_for(&station(None)).unwrap(), ["loudnorm"]);
+
+        let unknown = options.filters_for(&station(Some("loud"))).unwrap_err();
+        assert_eq!(unknown.kind(), ErrorKind::InvalidInput);
+        assert_eq!(unknown.to_string(), "Unknown preset \"loud\"");
+    }
+
+    #[test]
+    fn the_preset_given_wins_over_the_stations() {
+        let options = PlayerOptions {
+            presets: testing::config(&[]).presets(),
+            preset: Some(String::from("bass-boost")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.preset_for(&station(Some("night"))),
+            Some("bass-boost")
+        );
+        assert_eq!(
+            options.filters_for(&station(Some("night"))).unwrap(),
+            ["bass=g=8"]
+        );
+        assert_eq!(
+            PlayerOptions::default().preset_for(&station(Some("night"))),
+            Some("night")
+        );
+    }
+
+    #[test]
+    fn lists_the_music_of_a_folder_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.mp3", "a.flac", "cover.jpg"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let playlist = Mpv::write_playlist(dir.path()).unwrap();
+        let music = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(playlist.path()).unwrap(),
+            format!("{0}/a.flac\n{0}/b.mp3\n", music.display())
+        );
+
+        let path = playlist.path().to_path_buf();
+        drop(playlist);
+        assert!(!path.exists());
+
+        std::fs::remove_file(dir.path().join("a.flac")).unwrap();
+        std::fs::remove_file(dir.path().join("b.mp3")).unwrap();
+        assert!(Mpv::write_playlist(dir.path()).is_err());
+    }
+
+    #[test]
+    fn reads_the_devices_mpv_lists() {
+        let output = std::fs::read_to_string(testing::fixture("mpv-audio-devices.txt")).unwrap();
+        let devices = parse_audio_devices(&output);
+
+        // Not the header
+        assert_eq!(devices.len(), 7);
+        assert_eq!(devices[0].name, "auto");
+        assert_eq!(devices[0].description, "Autoselect device");
+        assert_eq!(devices[1].description, "Default (pipewire)");
+        assert_eq!(
+            devices[3].description,
+            "Scarlett Solo (3rd Gen.) Analog Stereo"
+        );
+        assert_eq!(devices[4].name, "alsa/hdmi:CARD=PCH,DEV=0");
+        assert_eq!(
+            devices[4].to_string(),
+            "HDA Intel PCH, HDMI 0 (HDMI Audio Output) (alsa/hdmi:CARD=PCH,DEV=0)"
+        );
+
+        assert!(parse_audio_devices("").is_empty());
+    }
+
+    #[test]
+    fn picks_the_device_given_then_the_stations_then_the_default() {
+        let mut usb = station(None);
+        usb.audio_device = Some(String::from("pulse/usb"));
+        let options = |given: Option<&str>, default: Option<&str>| PlayerOptions {
+            audio_device: given.map(str::to_string),
+            default_audio_device: default.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options(Some("alsa/hdmi"), Some("auto")).audio_device_for(&usb),
+            Some("alsa/hdmi")
+        );
+        assert_eq!(
+            options(None, Some("auto")).audio_device_for(&usb),
+            Some("pulse/usb")
+        );
+        assert_eq!(
+            options(None, Some("auto")).audio_device_for(&station(None)),
+            Some("auto")
+        );
+        assert_eq!(options(None, None).audio_device_for(&station(None)), None);
+    }
+
+    #[test]
+    fn parses_volumes_from_0_to_100() {
+        assert_eq!(parse_volume("0"), Ok(0.0));
+        assert_eq!(parse_volume(" 55.5 "), Ok(55.5));
+        assert_eq!(parse_volume("100"), Ok(100.0));
+        assert!(parse_volume("101").is_err());
+        assert!(parse_volume("-1").is_err());
+        assert!(parse_volume("NaN").is_err());
+        assert_eq!(
+            parse_volume("loud"),
+            Err(String::from("\"loud\" is not a volume from 0 to 100"))
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Podcasts, from their RSS or Atom feeds.
+//!
+//! Only the episodes with audio (an RSS `<enclosure>` or an Atom `<link rel="enclosure">`)
+//! are kept, newest first.
+
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::radio_browser::http_client;
+use crate::station::Station;
+use crate::timer::format_duration;
+use crate::xml::{self, Element};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Podcast {
+    pub name: String,
+
+    /// Address of the RSS or Atom feed
+    pub url: String,
+}
+
+impl std::fmt::Display for Podcast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub title: String,
+
+    /// Address of the audio
+    pub url: String,
+
+    pub published: Option<DateTime<FixedOffset>>,
+
+    /// Length, when the feed tells it
+    pub duration: Option<Duration>,
+}
+
+impl Episode {
+    /// The episode as something the player can play
+    pub fn station(&self, podcast: &Podcast) -> Station {
+        Station {
+            station: format!("{}: {}", podcast.name, self.title),
+            url: self.url.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl std::fmt::Display for Episode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(date) = self.published {
+            write!(f, "{}  ", date.format("%Y-%m-%d"))?;
+        }
+        write!(f, "{}", self.title)?;
+        if let Some(duration) = self.duration {
+            write!(f, " ({})", format_duration(duration))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Downloads the feed of `podcast` and reads its episodes
+pub fn fetch(config: &Config, podcast: &Podcast) -> Result<Vec<Episode>, Box<dyn Error>> {
+    let body = http_client(config)?
+        .get(&podcast.url)
+        .send()?
+        .error_for_status()?
+        .text()?;
+
+    parse_feed(&body)
+}
+
+/// The episodes of an RSS or Atom feed, newest first
+pub fn parse_feed(body: &str) -> Result<Vec<Episode>, Box<dyn Error>> {
+    let root = xml::parse(body)?;
+
+    let mut episodes: Vec<Episode> = match root.name.as_str() {
+        "rss" => root
+            .child("channel")
+            .ok_or("The feed has no <channel>")?
+            .children
+            .iter()
+            .filter(|e| e.name == "item")
+            .filter_map(rss_episode)
+            .collect(),
+        "feed" => root
+            .children
+            .iter()
+            .filter(|e| e.name == "entry")
+            .filter_map(atom_episode)
+            .collect(),
+        other => return Err(format!("<{}> is not an RSS or Atom feed", other).into()),
+    };
+
+    // Episodes without a date go last
+    episodes.sort_by_key(|e| Reverse(e.published));
+
+    Ok(episodes)
+}
+
+fn rss_episode(item: &Element) -> Option<Episode> {
+    let url = item.child("enclosure")?.attr("url")?;
+
+    Some(Episode {
+        title: item.child_text("title").unwrap_or(url).to_string(),
+        url: url.to_string(),
+        published: item
+            .child_text("pubDate")
+            .and_then(|d| DateTime::parse_from_rfc2822(d).ok()),
+        duration: item.child_text("itunes:duration").and_then(parse_duration),
+    })
+}
+
+fn atom_episode(entry: &Element) -> Option<Episode> {
+    let url = entry
+        .children
+        .iter()
+        .find(|e| e.name == "link" && e.attr("rel") == Some("enclosure"))?
+        .attr("href")?;
+
+    Some(Episode {
+        title: entry.child_text("title").unwrap_or(url).to_string(),
+        url: url.to_string(),
+        published: entry
+            .child_text("published")
+            .or(entry.child_text("updated"))
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok()),
+        duration: None,
+    })
+}
+
+/// Reads an `<itunes:duration>`: seconds, `MM:SS` or `HH:MM:SS`
+fn parse_duration(s: &str) -> Option<Duration> {
+    let mut secs: u64 = 0;
+    for part in s.split(':') {
+        secs = secs
+            .checked_mul(60)?
+            .checked_add(part.trim().parse().ok()?)?;
+    }
+
+    Some(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+    use std::fs;
+
+    fn feed(name: &str) -> Vec<Episode> {
+        parse_feed(&fs::read_to_string(fixture(name)).unwrap()).unwrap()
+    }
+
+    fn titles(episodes: &[Episode]) -> Vec<&str> {
+        episodes.iter().map(|e| e.title.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_the_episodes_of_an_rss_feed_newest_first() {
+        let episodes = feed("show.rss");
+
+        // Not the one without audio, and the one without a date last
+        assert_eq!(
+            titles(&episodes),
+            ["Episode 3", "Episode 2", "Episode 1", "Bonus"]
+        );
+
+        let latest = &episodes[0];
+        assert_eq!(latest.url, "http://podcast.example/3.mp3?source=rss&id=3");
+        assert_eq!(
+            latest.published.unwrap().to_rfc3339(),
+            "2024-03-01T10:00:00+01:00"
+        );
+        assert_eq!(latest.duration, Some(Duration::from_secs(3723)));
+        assert_eq!(episodes[1].duration, Some(Duration::from_secs(90)));
+        assert_eq!(episodes[2].duration, Some(Duration::from_secs(2700)));
+        assert_eq!(episodes[3].duration, None);
+        assert!(episodes[3].published.is_none());
+    }
+
+    #[test]
+    fn reads_the_entries_of_an_atom_feed_newest_first() {
+        let episodes = feed("news.atom");
+
+        // The dates are those of publication, or of the last update
+        assert_eq!(titles(&episodes), ["Tuesday", "Monday"]);
+        assert_eq!(episodes[0].url, "http://news.example/tuesday.ogg");
+        assert_eq!(
+            episodes[1].published.unwrap().to_rfc3339(),
+            "2024-05-06T07:00:00+00:00"
+        );
+        assert_eq!(episodes[1].duration, None);
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_feed() {
+        assert!(parse_feed("<html><body/></html>").is_err());
+        assert!(parse_feed("<rss version=\"2.0\"></rss>").is_err());
+        assert!(parse_feed("<rss><channel>").is_err());
+        assert!(
+            parse_feed("<rss><channel><title>Empty</title></channel></rss>")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reads_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("01:30"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_duration(" 5 : 07 "), Some(Duration::from_secs(307)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1:30.5"), None);
+        assert_eq!(parse_duration("an hour"), None);
+        assert_eq!(parse_duration("-1"), None);
+
+        // Too long to be true, rather than overflowing
+        assert_eq!(
+            parse_duration(&u64::MAX.to_string()),
+            Some(Duration::from_secs(u64::MAX))
+        );
+        assert_eq!(parse_duration(&format!("{}:00", u64::MAX)), None);
+        assert_eq!(parse_duration(&format!("{}:59", u64::MAX / 60)), None);
+    }
+}
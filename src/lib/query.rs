@@ -0,0 +1,341 @@
+//! Station searches with filters, e.g. `jazz codec:aac bitrate>=128 lang:english tag:smooth`.
+//!
+//! The plain words must all be in the name of the station, and the filters are:
+//!
+//! - `codec:<codec>`
+//! - `bitrate>=<kbps>`, and also `>`, `<=`, `<` and `=` (or `:`)
+//! - `lang:<language>` (or `language:`), by name or code
+//! - `tag:<tag>`, as many times as needed
+//! - `country:<code>`
+//!
+//! Values with spaces go in quotes: `lang:"brazilian portuguese"`. Unknown filters are
+//! taken as words, so names with colons can still be searched.
+
+use crate::radio_browser::StationSearch;
+use radiobrowser::ApiStation;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    /// Lowercase words of the name
+    pub words: Vec<String>,
+    pub codec: Option<String>,
+    /// In kbps
+    pub bitrate_min: Option<u32>,
+    pub bitrate_max: Option<u32>,
+    pub language: Option<String>,
+    pub tags: Vec<String>,
+    /// ISO 3166-1 code
+    pub country: Option<String>,
+}
+
+/// A filter that can't be understood, e.g. `bitrate>=fast`
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryError(pub String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let mut query = Query::default();
+
+        for token in tokens(input) {
+            if let Some(condition) = token.to_lowercase().strip_prefix("bitrate")
+                && condition.starts_with(['<', '>', '=', ':'])
+            {
+                query.add_bitrate(condition)?;
+                continue;
+            }
+
+            let Some((key, value)) = token.split_once(':') else {
+                query.words.push(token.to_lowercase());
+                continue;
+            };
+
+            // Still being typed
+            if value.is_empty() {
+                continue;
+            }
+
+            match key.to_lowercase().as_str() {
+                "codec" => query.codec = Some(value.to_string()),
+                "lang" | "language" => query.language = Some(value.to_string()),
+                "tag" => query.tags.push(value.to_string()),
+                "country" => query.country = Some(value.to_string()),
+                _ => query.words.push(token.to_lowercase()),
+            }
+        }
+
+        Ok(query)
+    }
+
+    fn add_bitrate(&mut self, condition: &str) -> Result<(), QueryError> {
+        let (operator, number) = match condition.get(..2) {
+            Some(op @ (">=" | "<=")) => (op, &condition[2..]),
+            _ => condition.split_at(1),
+        };
+
+        // Still being typed
+        if number.is_empty() {
+            return Ok(());
+        }
+
+        let Ok(kbps) = number.parse::<u32>() else {
+            return Err(QueryError(format!(
+                "\"{}\" is not a bitrate, try bitrate>=128",
+                number
+            )));
+        };
+
+        match operator {
+            ">=" => self.bitrate_min = Some(kbps),
+            ">" => self.bitrate_min = Some(kbps.saturating_add(1)),
+            "<=" => self.bitrate_max = Some(kbps),
+            "<" => self.bitrate_max = Some(kbps.saturating_sub(1)),
+            _ => {
+                self.bitrate_min = Some(kbps);
+                self.bitrate_max = Some(kbps);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether there is anything besides the words of the name
+    pub fn has_filters(&self) -> bool {
+        self.codec.is_some()
+            || self.bitrate_min.is_some()
+            || self.bitrate_max.is_some()
+            || self.language.is_some()
+            || !self.tags.is_empty()
+            || self.country.is_some()
+    }
+
+    pub fn matches(&self, station: &ApiStation) -> bool {
+        let name = station.name.to_lowercase();
+
+        self.words.iter().all(|w| name.contains(w.as_str()))
+            && self
+                .codec
+                .as_ref()
+                .is_none_or(|c| station.codec.eq_ignore_ascii_case(c))
+            && self.bitrate_min.is_none_or(|b| station.bitrate >= b)
+            && self.bitrate_max.is_none_or(|b| station.bitrate <= b)
+            && self.language.as_ref().is_none_or(|l| {
+                list_contains(&station.language, l)
+                    || list_contains(station.languagecodes.as_deref().unwrap_or(""), l)
+            })
+            && self.tags.iter().all(|t| list_contains(&station.tags, t))
+            && self
+                .country
+                .as_ref()
+                .is_none_or(|c| station.countrycode.eq_ignore_ascii_case(c))
+    }
+
+    /// Adds the query to a search of Radio Browser.
+    /// Its results still need `matches`, as the API finds the words together.
+    pub fn apply(&self, mut search: StationSearch) -> StationSearch {
+        if !self.words.is_empty() {
+            search = search.name(&self.words.join(" "));
+        }
+        if let Some(codec) = &self.codec {
+            search = search.codec(codec);
+        }
+        if let Some(b) = self.bitrate_min {
+            search = search.bitrate_min(b);
+        }
+        if let Some(b) = self.bitrate_max {
+            search = search.bitrate_max(b);
+        }
+        if let Some(language) = &self.language {
+            search = search.language(language);
+        }
+        if !self.tags.is_empty() {
+            search = search.tag_list(&self.tags);
+        }
+        if let Some(country) = &self.country {
+            search = search.countrycode(country);
+        }
+
+        search
+    }
+}
+
+/// Whether a comma-separated list of Radio Browser (tags, languages) has `value`
+fn list_contains(list: &str, value: &str) -> bool {
+    list.split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(value.trim()))
+}
+
+/// Splits by spaces, except inside quotes. An unclosed quote runs to the end.
+fn tokens(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+/// Codec and bitrate of a station, e.g. `[AAC 128k]`, or nothing if unknown
+pub fn badge(station: &ApiStation) -> String {
+    match (station.codec.trim(), station.bitrate) {
+        ("", 0) => String::new(),
+        ("", b) => format!("[{}k]", b),
+        (c, 0) => format!("[{}]", c.to_uppercase()),
+        (c, b) => format!("[{} {}k]", c.to_uppercase(), b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directory::new_station;
+
+    fn station(name: &str, codec: &str, bitrate: u32) -> ApiStation {
+        ApiStation {
+            codec: codec.to_string(),
+            bitrate,
+            tags: String::from("jazz, Smooth Jazz,lounge"),
+            language: String::from("english,brazilian portuguese"),
+            languagecodes: Some(String::from("en,pt")),
+            countrycode: String::from("BR"),
+            ..new_station("id", name, "http://example.com/stream")
+        }
+    }
+
+    fn bitrates(input: &str) -> (Option<u32>, Option<u32>) {
+        let query = Query::parse(input).unwrap();
+        (query.bitrate_min, query.bitrate_max)
+    }
+
+    #[test]
+    fn splits_by_spaces_except_in_quotes() {
+        assert_eq!(tokens("  smooth   jazz "), ["smooth", "jazz"]);
+        assert_eq!(
+            tokens(r#"lang:"brazilian portuguese" fm"#),
+            ["lang:brazilian portuguese", "fm"]
+        );
+        assert_eq!(tokens(r#"tag:"still typing"#), ["tag:still typing"]);
+        assert!(tokens("   ").is_empty());
+    }
+
+    #[test]
+    fn parses_words_and_filters() {
+        let query = Query::parse(
+            r#"Smooth JAZZ codec:aac lang:"brazilian portuguese" tag:jazz tag:lounge country:BR"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query,
+            Query {
+                words: vec![String::from("smooth"), String::from("jazz")],
+                codec: Some(String::from("aac")),
+                language: Some(String::from("brazilian portuguese")),
+                tags: vec![String::from("jazz"), String::from("lounge")],
+                country: Some(String::from("BR")),
+                ..Default::default()
+            }
+        );
+        assert!(query.has_filters());
+        assert!(!Query::parse("just words").unwrap().has_filters());
+    }
+
+    #[test]
+    fn parses_bitrates() {
+        assert_eq!(bitrates("bitrate>=128"), (Some(128), None));
+        assert_eq!(bitrates("bitrate>128"), (Some(129), None));
+        assert_eq!(bitrates("bitrate<=128"), (None, Some(128)));
+        assert_eq!(bitrates("bitrate<128"), (None, Some(127)));
+        assert_eq!(bitrates("bitrate=128"), (Some(128), Some(128)));
+        assert_eq!(bitrates("BITRATE:128"), (Some(128), Some(128)));
+        assert_eq!(bitrates("bitrate>=64 bitrate<=192"), (Some(64), Some(192)));
+
+        // At the limits
+        assert_eq!(bitrates("bitrate>4294967295"), (Some(u32::MAX), None));
+        assert_eq!(bitrates("bitrate<0"), (None, Some(0)));
+    }
+
+    #[test]
+    fn half_typed_filters_are_ignored() {
+        assert_eq!(
+            Query::parse("jazz codec:").unwrap(),
+            Query {
+                words: vec![String::from("jazz")],
+                ..Default::default()
+            }
+        );
+        assert_eq!(bitrates("bitrate>="), (None, None));
+        assert_eq!(bitrates("bitrate<"), (None, None));
+    }
+
+    #[test]
+    fn unknown_keys_are_words() {
+        let query = Query::parse("Radio:Rock bitrates bitrate").unwrap();
+
+        assert_eq!(query.words, ["radio:rock", "bitrates", "bitrate"]);
+        assert!(!query.has_filters());
+    }
+
+    #[test]
+    fn bitrates_must_be_numbers() {
+        let e = Query::parse("jazz bitrate>=fast").unwrap_err();
+        assert_eq!(e.0, "\"fast\" is not a bitrate, try bitrate>=128");
+
+        assert!(Query::parse("bitrate>-5").is_err());
+        assert!(Query::parse("bitrate<=99999999999").is_err());
+    }
+
+    #[test]
+    fn matches_stations() {
+        let jazz = station("Smooth Jazz FM", "AAC", 128);
+        let matches = |input: &str| Query::parse(input).unwrap().matches(&jazz);
+
+        assert!(matches(""));
+        assert!(matches("jazz smooth"));
+        assert!(!matches("jazz rock"));
+        assert!(matches("codec:aac bitrate>=128 bitrate<=128"));
+        assert!(!matches("codec:mp3"));
+        assert!(!matches("bitrate>128"));
+        assert!(!matches("bitrate<128"));
+        assert!(matches("bitrate=128"));
+        assert!(matches("lang:english"));
+        assert!(matches(r#"lang:"Brazilian Portuguese""#));
+        assert!(matches("language:pt"));
+        assert!(!matches("lang:port"));
+        assert!(matches(r#"tag:"smooth jazz" tag:LOUNGE"#));
+        assert!(!matches("tag:smooth"));
+        assert!(matches("country:br"));
+        assert!(!matches("country:us"));
+    }
+
+    #[test]
+    fn badges() {
+        assert_eq!(badge(&station("A", "aac", 128)), "[AAC 128k]");
+        assert_eq!(badge(&station("A", "mp3", 0)), "[MP3]");
+        assert_eq!(badge(&station("A", " ", 64)), "[64k]");
+        assert_eq!(badge(&station("A", "", 0)), "");
+    }
+}
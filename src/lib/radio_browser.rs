@@ -0,0 +1,336 @@
+//! A small client of the Radio Browser API (<https://api.radio-browser.info>).
+//!
+//! The servers are the `api_servers` of the config, or else the ones announced in the DNS
+//! of radio-browser.info. The first one that answers is used, and when it stops
+//! answering the next ones are tried in order.
+
+use log::{debug, warn};
+use radiobrowser::blocking::RadioBrowserAPI;
+use radiobrowser::{ApiCountry, ApiStation, ApiStationVoteResult};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::Config;
+use crate::browser::{Category, Group, Order};
+use crate::directory::StationDirectory;
+
+/// Most tags or languages listed when browsing, the ones with more stations first
+const GROUP_LIMIT: usize = 500;
+
+/// How long a server has to answer
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a server has to answer the health check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Used when the servers can't be found through the DNS
+const FALLBACK_SERVERS: [&str; 3] = [
+    "de1.api.radio-browser.info",
+    "nl1.api.radio-browser.info",
+    "at1.api.radio-browser.info",
+];
+
+const USER_AGENT: &str = concat!("radio-cli/", env!("CARGO_PKG_VERSION"));
+
+pub struct RadioBrowser {
+    client: Client,
+    /// Base URLs, the one in use first
+    servers: Vec<String>,
+    /// Server the requests go to first
+    current: Cell<usize>,
+}
+
+impl RadioBrowser {
+    /// Connects to the first server of the config that answers
+    pub fn new(config: &Config) -> Result<RadioBrowser, Box<dyn Error>> {
+        let servers = match config.api_servers.is_empty() {
+            true => discover_servers(),
+            false => config.api_servers.clone(),
+        };
+
+        let mut api = RadioBrowser {
+            client: http_client(config)?,
+            servers: servers.iter().map(|s| base_url(s)).collect(),
+            current: Cell::new(0),
+        };
+        api.check()?;
+
+        Ok(api)
+    }
+
+    /// Puts the first server that answers first, or fails if none does
+    fn check(&mut self) -> Result<(), Box<dyn Error>> {
+        let healthy = self.servers.iter().position(|server| {
+            let answer = self
+                .client
+                .get(format!("{}/json/stats", server))
+                .timeout(CHECK_TIMEOUT)
+                .send()
+                .and_then(|r| r.error_for_status());
+
+            match answer {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("The Radio Browser server {} does not answer: {}", server, e);
+                    false
+                }
+            }
+        });
+
+        match healthy {
+            Some(i) => {
+                debug!("Using the Radio Browser server {}", self.servers[i]);
+                self.servers[..=i].rotate_right(1);
+                Ok(())
+            }
+            None if self.servers.is_empty() => Err("There are no Radio Browser servers".into()),
+            None => Err(format!(
+                "None of the Radio Browser servers answer ({})",
+                self.servers.join(", ")
+            )
+            .into()),
+        }
+    }
+
+    /// Sends a request to the current server, or to the next ones if it fails
+    pub fn send<Q: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<Q, Box<dyn Error>> {
+        let mut last_error: Box<dyn Error> = "There are no Radio Browser servers".into();
+
+        for n in 0..self.servers.len() {
+            let i = (self.current.get() + n) % self.servers.len();
+            let server = &self.servers[i];
+
+            let answer = self
+                .client
+                .post(format!("{}{}", server, endpoint))
+                .json(params)
+                .send()
+                .and_then(|r| r.error_for_status())
+                .and_then(|r| r.json::<Q>());
+
+            match answer {
+                Ok(answer) => {
+                    self.current.set(i);
+                    return Ok(answer);
+                }
+                Err(e) => {
+                    warn!("{} failed on {}: {}", endpoint, server, e);
+                    last_error = e.into();
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+impl StationDirectory for RadioBrowser {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        self.send("/json/stations/search", &search.params)
+    }
+
+    fn station(&self, uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        let params = HashMap::from([(String::from("uuids"), uuid.to_string())]);
+        let stations: Vec<ApiStation> = self.send("/json/stations/byuuid", &params)?;
+
+        Ok(stations.into_iter().next())
+    }
+
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        self.send("/json/countries", &HashMap::new())
+    }
+
+    fn groups(
+        &self,
+        category: Category,
+        country: Option<&str>,
+        hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        let endpoint = match (category, country) {
+            (Category::Tags, _) => String::from("/json/tags"),
+            (Category::Languages, _) => String::from("/json/languages"),
+            (Category::Codecs, _) => String::from("/json/codecs"),
+            (Category::States, Some(country)) => format!("/json/states/{}/", country),
+            (Category::States, None) => String::from("/json/states"),
+        };
+
+        let params = HashMap::from([
+            (String::from("order"), String::from("stationcount")),
+            (String::from("reverse"), String::from("true")),
+            (String::from("hidebroken"), hidebroken.to_string()),
+            (String::from("limit"), GROUP_LIMIT.to_string()),
+        ]);
+        self.send(&endpoint, &params)
+    }
+
+    fn click(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        let click: Click = self.send(&format!("/json/url/{}", uuid), &HashMap::new())?;
+
+        match click.ok && !click.url.is_empty() {
+            true => Ok(click.url),
+            false => Err(click.message.into()),
+        }
+    }
+
+    fn vote(&self, uuid: &str) -> Result<String, Box<dyn Error>> {
+        let vote: ApiStationVoteResult =
+            self.send(&format!("/json/vote/{}", uuid), &HashMap::new())?;
+
+        match vote.ok {
+            true => Ok(vote.message),
+            false => Err(vote.message.into()),
+        }
+    }
+}
+
+/// Answer of `/json/url/{uuid}`
+#[derive(Deserialize)]
+struct Click {
+    ok: bool,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    url: String,
+}
+
+/// A client for station directories, with the user agent and timeout of radio-cli
+pub(crate) fn http_client(config: &Config) -> reqwest::Result<Client> {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(config.api_timeout.unwrap_or(DEFAULT_TIMEOUT))
+        .build()
+}
+
+/// Servers announced in the DNS of radio-browser.info, or some well-known ones
+fn discover_servers() -> Vec<String> {
+    match RadioBrowserAPI::get_default_servers() {
+        Ok(servers) if !servers.is_empty() => servers,
+        Ok(_) => FALLBACK_SERVERS.iter().map(|s| s.to_string()).collect(),
+        Err(e) => {
+            debug!("Could not find the Radio Browser servers: {}", e);
+            FALLBACK_SERVERS.iter().map(|s| s.to_string()).collect()
+        }
+    }
+}
+
+/// `host` or `http(s)://host[:port][/path]`, without the trailing slash
+fn base_url(server: &str) -> String {
+    let server = server.trim().trim_end_matches('/');
+
+    match server.starts_with("http://") || server.starts_with("https://") {
+        true => server.to_string(),
+        false => format!("https://{}", server),
+    }
+}
+
+/// Parameters of `/json/stations/search`
+#[derive(Debug, Clone, Default)]
+pub struct StationSearch {
+    params: HashMap<String, String>,
+}
+
+impl StationSearch {
+    fn with(mut self, key: &str, value: impl ToString) -> StationSearch {
+        self.params.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// The search without a parameter
+    pub fn without(mut self, key: &str) -> StationSearch {
+        self.params.remove(key);
+        self
+    }
+
+    /// A parameter, by the name Radio Browser gives it (e.g. `bitrateMin`)
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.get(key).map(String::as_str)
+    }
+
+    pub fn name(self, name: &str) -> StationSearch {
+        self.with("name", name)
+    }
+
+    pub fn codec(self, codec: &str) -> StationSearch {
+        self.with("codec", codec)
+    }
+
+    /// In kbps
+    pub fn bitrate_min(self, bitrate: u32) -> StationSearch {
+        self.with("bitrateMin", bitrate)
+    }
+
+    /// In kbps
+    pub fn bitrate_max(self, bitrate: u32) -> StationSearch {
+        self.with("bitrateMax", bitrate)
+    }
+
+    pub fn language(self, language: &str) -> StationSearch {
+        self.with("language", language)
+    }
+
+    pub fn language_exact(self, exact: bool) -> StationSearch {
+        self.with("languageExact", exact)
+    }
+
+    pub fn tag(self, tag: &str) -> StationSearch {
+        self.with("tag", tag)
+    }
+
+    pub fn tag_exact(self, exact: bool) -> StationSearch {
+        self.with("tagExact", exact)
+    }
+
+    /// Stations with all of these tags
+    pub fn tag_list(self, tags: &[String]) -> StationSearch {
+        self.with("tagList", tags.join(","))
+    }
+
+    pub fn countrycode(self, code: &str) -> StationSearch {
+        self.with("countrycode", code)
+    }
+
+    pub fn country(self, country: &str) -> StationSearch {
+        self.with("country", country)
+    }
+
+    pub fn country_exact(self, exact: bool) -> StationSearch {
+        self.with("countryExact", exact)
+    }
+
+    pub fn state(self, state: &str) -> StationSearch {
+        self.with("state", state)
+    }
+
+    pub fn state_exact(self, exact: bool) -> StationSearch {
+        self.with("stateExact", exact)
+    }
+
+    pub fn order(self, order: Order) -> StationSearch {
+        self.with("order", order.name())
+    }
+
+    pub fn reverse(self, reverse: bool) -> StationSearch {
+        self.with("reverse", reverse)
+    }
+
+    pub fn hidebroken(self, hidebroken: bool) -> StationSearch {
+        self.with("hidebroken", hidebroken)
+    }
+
+    pub fn offset(self, offset: usize) -> StationSearch {
+        self.with("offset", offset)
+    }
+
+    pub fn limit(self, limit: usize) -> StationSearch {
+        self.with("limit", limit)
+    }
+}
@@ -0,0 +1,369 @@
+use crate::icy::ReconnectingStream;
+use crate::station::Station;
+
+use chrono::Local;
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordOptions {
+    /// Stop after this long. If `None`, record until the program is stopped.
+    pub duration: Option<Duration>,
+
+    /// Where to save the recording. The extension is added from the stream type when missing.
+    pub output: Option<PathBuf>,
+
+    /// Add the extension of the stream even if `output` seems to have one already,
+    /// for names made up of others that may have dots ("Kiss 92.5 2024-01-01")
+    pub add_extension: bool,
+
+    /// Start a new file every time the stream announces a new title
+    pub split: bool,
+}
+
+/// What was recorded
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+    pub reconnects: u32,
+    pub elapsed: Duration,
+}
+
+/// The file currently being written
+struct Output {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    title: Option<String>,
+}
+
+/// Saves the raw stream of a station to disk.
+///
+/// If the connection drops, it reconnects and keeps appending to the same file until
+/// `options.duration` is over.
+pub fn record(station: &Station, options: &RecordOptions) -> Result<Recording> {
+    if station.is_local() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is on the disk, not an internet stream", station.station),
+        ));
+    }
+
+    let started = Instant::now();
+    let deadline = options.duration.and_then(|d| started.checked_add(d));
+
+    // Nothing is recorded if the station can't be reached at first
+    let mut stream = ReconnectingStream::connect(station, deadline)?;
+
+    let base = output_path(station, options, extension_for(&stream.content_type));
+    let first = if options.split {
+        part_path(&base, 1, None)
+    } else {
+        base.clone()
+    };
+    let mut output = Output::create(first)?;
+
+    let mut recording = Recording {
+        files: vec![output.path.clone()],
+        ..Default::default()
+    };
+    let mut buf = [0u8; 16 * 1024];
+
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        if let Some(title) = stream.take_title_change() {
+            info!("Now playing: {}", title);
+
+            if options.split {
+                if output.title.is_none() {
+                    // The first part didn't have a title yet, name it now
+                    output.rename(part_path(&base, recording.files.len(), Some(&title)))?;
+                    *recording.files.last_mut().unwrap() = output.path.clone();
+                } else {
+                    output.writer.flush()?;
+                    let path = part_path(&base, recording.files.len() + 1, Some(&title));
+                    output = Output::create(path)?;
+                    recording.files.push(output.path.clone());
+                }
+
+                output.title = Some(title);
+            }
+        }
+
+        output.writer.write_all(&buf[..n])?;
+        recording.bytes += n as u64;
+    }
+
+    output.writer.flush()?;
+
+    recording.reconnects = stream.reconnects;
+    recording.elapsed = started.elapsed();
+    Ok(recording)
+}
+
+/// File extension for the `Content-Type` of a stream
+pub fn extension_for(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+
+    match mime {
+        "audio/mpeg" | "audio/mp3" | "audio/mpeg3" => Some("mp3"),
+        "audio/aac" | "audio/aacp" | "audio/x-aac" => Some("aac"),
+        "audio/mp4" | "audio/x-m4a" => Some("m4a"),
+        "audio/ogg" | "application/ogg" | "audio/vorbis" => Some("ogg"),
+        "audio/opus" => Some("opus"),
+        "audio/flac" | "audio/x-flac" => Some("flac"),
+        "audio/wav" | "audio/x-wav" => Some("wav"),
+        "video/mp2t" => Some("ts"),
+        _ => None,
+    }
+}
+
+/// Makes a string safe to be used as a file name
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(100)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Path given by the user, or `<station> <date>` in the current directory,
+/// with the extension of the stream added if there isn't one.
+fn output_path(station: &Station, options: &RecordOptions, extension: Option<&str>) -> PathBuf {
+    let path = match &options.output {
+        Some(p) if p.extension().is_some() && !options.add_extension => return p.clone(),
+        Some(p) => p.clone(),
+        None => PathBuf::from(format!(
+            "{} {}",
+            sanitize_file_name(&station.station),
+            Local::now().format("%Y-%m-%d %Hh%M")
+        )),
+    };
+
+    match extension {
+        // Not `set_extension`, the name of the station could have dots
+        Some(ext) => PathBuf::from(format!("{}.{}", path.to_string_lossy(), ext)),
+        None => {
+            warn!("Unknown stream type, the recording won't have an extension");
+            path
+        }
+    }
+}
+
+/// `<base> - 003 - <title>.<ext>`, used when splitting by title
+fn part_path(base: &Path, number: usize, title: Option<&str>) -> PathBuf {
+    let stem = base
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut name = format!("{} - {:03}", stem, number);
+    if let Some(t) = title {
+        name = format!("{} - {}", name, sanitize_file_name(t));
+    }
+
+    if let Some(ext) = base.extension() {
+        name = format!("{}.{}", name, ext.to_string_lossy());
+    }
+
+    base.with_file_name(name)
+}
+
+impl Output {
+    fn create(path: PathBuf) -> Result<Output> {
+        info!("Recording to {}", path.display());
+
+        Ok(Output {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            title: None,
+        })
+    }
+
+    fn rename(&mut self, path: PathBuf) -> Result<()> {
+        self.writer.flush()?;
+        std::fs::rename(&self.path, &path)?;
+        self.path = path;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestServer, icy_stream, response};
+
+    fn station(server: &TestServer) -> Station {
+        Station {
+            station: String::from("Fixture FM"),
+            url: format!("{}/stream", server.url),
+            ..Default::default()
+        }
+    }
+
+    fn options(output: PathBuf, seconds: f64, split: bool) -> RecordOptions {
+        RecordOptions {
+            duration: Some(Duration::from_secs_f64(seconds)),
+            output: Some(output),
+            split,
+            add_extension: false,
+        }
+    }
+
+    #[test]
+    fn extensions_come_from_the_content_type() {
+        assert_eq!(extension_for("audio/mpeg"), Some("mp3"));
+        assert_eq!(extension_for("audio/aacp"), Some("aac"));
+        assert_eq!(extension_for("application/ogg; codecs=vorbis"), Some("ogg"));
+        assert_eq!(extension_for("text/html"), None);
+    }
+
+    #[test]
+    fn names_parts_by_number_and_title() {
+        let base = Path::new("/tmp/Radio 3 2024.mp3");
+
+        assert_eq!(
+            part_path(base, 1, None),
+            PathBuf::from("/tmp/Radio 3 2024 - 001.mp3")
+        );
+        assert_eq!(
+            part_path(base, 12, Some("AC/DC - T.N.T.")),
+            PathBuf::from("/tmp/Radio 3 2024 - 012 - AC_DC - T.N.T..mp3")
+        );
+    }
+
+    #[test]
+    fn adds_the_extension_of_the_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(vec![response(
+            "200 OK",
+            &[("Content-Type", "audio/ogg; codecs=opus")],
+            b"OggS audio",
+        )]);
+
+        let recording = record(
+            &station(&server),
+            &options(dir.path().join("show"), 0.5, false),
+        )
+        .unwrap();
+
+        let path = dir.path().join("show.ogg");
+        assert_eq!(recording.files, vec![path.clone()]);
+        assert_eq!(std::fs::read(path).unwrap(), b"OggS audio");
+    }
+
+    #[test]
+    fn keeps_the_extension_given() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(vec![icy_stream(16, &[(None, b"audio")])]);
+
+        let path = dir.path().join("show.raw");
+        let recording = record(&station(&server), &options(path.clone(), 0.5, false)).unwrap();
+
+        assert_eq!(recording.files, vec![path]);
+    }
+
+    #[test]
+    fn adds_the_extension_to_names_with_dots_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(vec![icy_stream(16, &[(None, b"audio")])]);
+
+        let options = RecordOptions {
+            add_extension: true,
+            ..options(dir.path().join("Kiss 92.5 2024-01-01"), 0.5, false)
+        };
+        let recording = record(&station(&server), &options).unwrap();
+
+        assert_eq!(
+            recording.files,
+            vec![dir.path().join("Kiss 92.5 2024-01-01.mp3")]
+        );
+    }
+
+    #[test]
+    fn unreachable_stations_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(Vec::new());
+
+        let result = record(
+            &station(&server),
+            &options(dir.path().join("show"), 5.0, false),
+        );
+
+        assert!(result.is_err());
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn reconnects_appending_to_the_same_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(vec![
+            icy_stream(16, &[(Some("Song"), &[b'a'; 40])]),
+            icy_stream(16, &[(Some("Song"), &[b'b'; 40])]),
+        ]);
+
+        let recording = record(
+            &station(&server),
+            &options(dir.path().join("show"), 1.5, false),
+        )
+        .unwrap();
+
+        let mut expected = vec![b'a'; 40];
+        expected.extend_from_slice(&[b'b'; 40]);
+        assert_eq!(
+            std::fs::read(dir.path().join("show.mp3")).unwrap(),
+            expected
+        );
+        assert_eq!(recording.bytes, 80);
+        assert!(recording.reconnects >= 1);
+        assert!(server.requests().len() >= 2);
+    }
+
+    #[test]
+    fn splits_by_title() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = TestServer::scripted(vec![icy_stream(
+            16,
+            &[(Some("First"), &[b'a'; 32]), (Some("Second"), &[b'b'; 32])],
+        )]);
+
+        let recording = record(
+            &station(&server),
+            &options(dir.path().join("show"), 0.5, true),
+        )
+        .unwrap();
+
+        // The first part is renamed when its title arrives
+        let first = dir.path().join("show - 001 - First.mp3");
+        let second = dir.path().join("show - 002 - Second.mp3");
+        assert_eq!(recording.files, vec![first.clone(), second.clone()]);
+        assert_eq!(std::fs::read(first).unwrap(), vec![b'a'; 32]);
+        assert_eq!(std::fs::read(second).unwrap(), vec![b'b'; 32]);
+        assert!(!dir.path().join("show - 001.mp3").exists());
+    }
+
+    #[test]
+    fn local_stations_are_not_recorded() {
+        let station = Station {
+            station: String::from("Music"),
+            url: String::from("/tmp"),
+            kind: crate::StationKind::Directory,
+            ..Default::default()
+        };
+
+        assert!(record(&station, &RecordOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,351 @@
+//! Serves a station to several clients of the local network, pulling it only once.
+//!
+//! Clients that ask for `Icy-MetaData: 1` get the titles of the station too,
+//! re-interleaved every `METAINT` bytes.
+
+use crate::icy::{IcyWriter, ReconnectingStream};
+use crate::station::Station;
+
+use log::{debug, info, warn};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Audio bytes between two metadata blocks sent to the clients
+pub const METAINT: usize = 16000;
+
+/// Chunks a client can fall behind before it is dropped
+const CLIENT_QUEUE: usize = 64;
+
+/// A client that doesn't send its request in this time is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A piece of the upstream audio, with the title playing at the time
+struct Chunk {
+    audio: Vec<u8>,
+    title: Option<String>,
+}
+
+/// What the clients are told about the stream
+struct StreamInfo {
+    content_type: String,
+    name: Option<String>,
+}
+
+/// Hands every chunk of audio to all the clients
+#[derive(Default)]
+struct Hub {
+    clients: Mutex<Vec<SyncSender<Arc<Chunk>>>>,
+}
+
+impl Hub {
+    fn subscribe(&self) -> Receiver<Arc<Chunk>> {
+        let (tx, rx) = sync_channel(CLIENT_QUEUE);
+        self.clients.lock().unwrap().push(tx);
+
+        rx
+    }
+
+    fn broadcast(&self, chunk: Chunk) {
+        let chunk = Arc::new(chunk);
+
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| match client.try_send(chunk.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!("A client can't keep up with the stream, dropping it");
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+/// Pulls the station and serves it to whoever connects to `listener`, until it fails.
+pub fn relay(station: &Station, listener: TcpListener) -> Result<()> {
+    if station.is_local() {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is on the disk, not an internet stream", station.station),
+        ));
+    }
+
+    // Connect before accepting anyone, so an unreachable station is noticed right away
+    let stream = ReconnectingStream::connect(station, None)?;
+
+    let info = Arc::new(StreamInfo {
+        content_type: match header_value(&stream.content_type) {
+            Some("") | None => String::from("audio/mpeg"),
+            Some(c) => c.to_string(),
+        },
+        name: header_value(stream.name.as_deref().unwrap_or(&station.station)).map(str::to_string),
+    });
+    let hub = Arc::new(Hub::default());
+
+    {
+        let hub = hub.clone();
+        thread::spawn(move || pull(stream, &hub));
+    }
+
+    for connection in listener.incoming() {
+        match connection {
+            Ok(socket) => {
+                let chunks = hub.subscribe();
+                let info = info.clone();
+
+                thread::spawn(move || {
+                    if let Err(e) = serve_client(socket, chunks, &info) {
+                        debug!("Client error: {}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Could not accept a client: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the station forever, reconnecting when the connection drops
+fn pull(mut stream: ReconnectingStream, hub: &Hub) {
+    let mut buf = [0u8; 8 * 1024];
+
+    loop {
+        let n = match stream.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Could not read the station: {}", e);
+                return;
+            }
+        };
+
+        if let Some(title) = stream.take_title_change() {
+            info!("Now playing: {}", title);
+        }
+
+        hub.broadcast(Chunk {
+            audio: buf[..n].to_vec(),
+            title: stream.title().map(str::to_string),
+        });
+    }
+}
+
+/// `value` if it can be echoed in a header, without a line break (or anything like it)
+/// that would let it add headers of its own
+fn header_value(value: &str) -> Option<&str> {
+    match value.chars().any(char::is_control) {
+        true => None,
+        false => Some(value),
+    }
+}
+
+fn serve_client(socket: TcpStream, chunks: Receiver<Arc<Chunk>>, info: &StreamInfo) -> Result<()> {
+    let peer = socket.peer_addr()?;
+    socket.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    // Only the headers matter, whatever was asked for gets the stream
+    let mut metadata = false;
+    let mut reader = BufReader::new(socket.try_clone()?);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("icy-metadata")
+        {
+            metadata = value.trim() == "1";
+        }
+    }
+
+    let mut head = format!(
+        "HTTP/1.0 200 OK\r\nContent-Type: {}\r\nCache-Control: no-cache\r\n",
+        info.content_type
+    );
+    if let Some(name) = &info.name {
+        head.push_str(&format!("icy-name: {}\r\n", name));
+    }
+    if metadata {
+        head.push_str(&format!("icy-metaint: {}\r\n", METAINT));
+    }
+    head.push_str("\r\n");
+
+    let mut socket = socket;
+    socket.write_all(head.as_bytes())?;
+
+    info!("{} connected", peer);
+
+    let mut writer = IcyWriter::new(socket, metadata.then_some(METAINT));
+    for chunk in chunks {
+        writer.set_title(chunk.title.as_deref());
+
+        if let Err(e) = writer.write_all(&chunk.audio) {
+            debug!("{}: {}", peer, e);
+            break;
+        }
+    }
+
+    info!("{} disconnected", peer);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icy::IcyReader;
+    use crate::testing::{TestServer, icy_stream, response};
+
+    /// Audio of the upstream fixture, longer than `METAINT` so that clients get a title.
+    /// Scrambled, so that any 64 bytes of it tell where they come from.
+    fn audio() -> Vec<u8> {
+        (0..METAINT as u32 + 4000)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect()
+    }
+
+    /// Relays a fixture that plays `audio()` over and over, as a station reconnected to
+    fn start() -> (TestServer, String) {
+        let upstream =
+            TestServer::start(|_, _| icy_stream(1000, &[(Some("Fixture Song"), &audio())]));
+        let station = Station {
+            station: String::from("Fixture"),
+            url: upstream.url.clone(),
+            ..Default::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || relay(&station, listener));
+
+        (upstream, address)
+    }
+
+    /// Connects to the relay, returning the headers and the rest of the response
+    fn listen(address: &str, metadata: bool) -> (Vec<String>, BufReader<TcpStream>) {
+        let mut socket = TcpStream::connect(address).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        write!(socket, "GET / HTTP/1.0\r\n").unwrap();
+        if metadata {
+            write!(socket, "Icy-MetaData: 1\r\n").unwrap();
+        }
+        write!(socket, "\r\n").unwrap();
+
+        let mut reader = BufReader::new(socket);
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            headers.push(line.trim().to_string());
+        }
+
+        (headers, reader)
+    }
+
+    #[test]
+    fn serves_the_station_with_its_titles() {
+        let (_upstream, address) = start();
+        let (headers, body) = listen(&address, true);
+
+        assert_eq!(headers[0], "HTTP/1.0 200 OK");
+        assert!(headers.contains(&String::from("Content-Type: audio/mpeg")));
+        assert!(headers.contains(&String::from("icy-name: Fixture FM")));
+        assert!(headers.contains(&format!("icy-metaint: {}", METAINT)));
+
+        let mut reader = IcyReader::new(body, Some(METAINT));
+        let mut received = vec![0u8; METAINT + 10];
+        reader.read_exact(&mut received).unwrap();
+
+        // Whole, from wherever the upstream was when the client connected
+        let audio = audio();
+        let start = audio
+            .windows(64)
+            .position(|w| w == &received[..64])
+            .expect("audio of the upstream");
+        for (i, byte) in received.iter().enumerate() {
+            assert_eq!(*byte, audio[(start + i) % audio.len()], "byte {}", i);
+        }
+        assert_eq!(reader.title(), Some("Fixture Song"));
+    }
+
+    #[test]
+    fn serves_plain_audio_when_titles_are_not_asked_for() {
+        let (_upstream, address) = start();
+        let (headers, mut body) = listen(&address, false);
+
+        assert!(!headers.iter().any(|h| h.starts_with("icy-metaint")));
+
+        let mut received = vec![0u8; METAINT + 10];
+        body.read_exact(&mut received).unwrap();
+        assert!(!received.windows(12).any(|w| w == b"StreamTitle="));
+    }
+
+    #[test]
+    fn several_clients_share_one_connection() {
+        let (upstream, address) = start();
+        let (_, mut first) = listen(&address, false);
+        let (_, mut second) = listen(&address, true);
+
+        let mut buf = [0u8; 1000];
+        first.read_exact(&mut buf).unwrap();
+        second.read_exact(&mut buf).unwrap();
+
+        // The upstream is pulled again as each of its streams ends, but not once per client
+        let connections = upstream.requests().len();
+        assert!(
+            (1..=2).contains(&connections),
+            "{} connections",
+            connections
+        );
+    }
+
+    #[test]
+    fn does_not_echo_line_breaks_in_the_headers() {
+        assert_eq!(header_value("Fixture FM"), Some("Fixture FM"));
+        assert_eq!(header_value("Evil\r\nSet-Cookie: a=b"), None);
+        assert_eq!(header_value("Tab\tbed"), None);
+
+        // Without an icy-name, the name of the station would be used
+        let upstream = TestServer::start(|_, _| {
+            response("200 OK", &[("Content-Type", "audio/mpeg")], &audio())
+        });
+        let station = Station {
+            station: String::from("Evil\r\nSet-Cookie: a=b"),
+            url: upstream.url.clone(),
+            ..Default::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || relay(&station, listener));
+
+        let (headers, _) = listen(&address, false);
+        assert!(headers.contains(&String::from("Content-Type: audio/mpeg")));
+        assert!(!headers.iter().any(|h| h.starts_with("icy-name")));
+        assert!(!headers.iter().any(|h| h.starts_with("Set-Cookie")));
+    }
+
+    #[test]
+    fn unreachable_stations_fail_before_listening() {
+        let upstream = TestServer::scripted(vec![]);
+        let station = Station {
+            station: String::from("Nowhere"),
+            url: upstream.url.clone(),
+            ..Default::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(relay(&station, listener).is_err());
+    }
+}
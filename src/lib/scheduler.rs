@@ -0,0 +1,428 @@
+use crate::Config;
+use crate::alarm::{AlarmTime, Clock};
+use crate::browser::Directory;
+use crate::record::{RecordOptions, Recording, record, sanitize_file_name};
+use crate::station::Station;
+use crate::timer::format_duration;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use log::{error, warn};
+use serde::Deserialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::Duration;
+
+/// Longest nap between two checks of the schedule.
+const TICK: Duration = Duration::from_secs(5);
+
+const DEFAULT_TEMPLATE: &str = "{name} {date}";
+
+/// A recording that repeats every week, from the `schedule` section of the config
+#[derive(Deserialize, Debug, Clone)]
+pub struct ScheduledRecording {
+    /// Name of the programme, used in the file names and the logs
+    pub name: Option<String>,
+
+    /// Station to record, by name
+    pub station: String,
+
+    #[serde(flatten)]
+    pub start: AlarmTime,
+
+    #[serde(deserialize_with = "crate::timer::deserialize_required_duration")]
+    pub duration: Duration,
+
+    /// Where to save it. `{name}`, `{station}`, `{date}`, `{time}` and `{weekday}` are replaced,
+    /// and the extension is added from the stream type.
+    pub output: Option<String>,
+
+    /// Start a new file every time the stream announces a new title
+    #[serde(default)]
+    pub split: bool,
+}
+
+impl ScheduledRecording {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.station)
+    }
+
+    /// Path of the recording starting at `start`
+    pub fn output_path(&self, start: NaiveDateTime) -> PathBuf {
+        let template = self.output.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+
+        let path = template
+            .replace("{name}", &sanitize_file_name(self.name()))
+            .replace("{station}", &sanitize_file_name(&self.station))
+            .replace("{date}", &start.format("%Y-%m-%d").to_string())
+            .replace("{time}", &start.format("%Hh%M").to_string())
+            .replace("{weekday}", &start.format("%A").to_string());
+
+        match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    /// How to record the programme starting at `start`
+    pub fn record_options(&self, start: NaiveDateTime) -> RecordOptions {
+        RecordOptions {
+            duration: Some(self.duration),
+            output: Some(self.output_path(start)),
+            split: self.split,
+            // The names in the template may have dots, that are not the extension
+            add_extension: true,
+        }
+    }
+}
+
+/// Outcome of one scheduled recording
+pub struct JobResult {
+    pub name: String,
+    pub station: String,
+    pub start: NaiveDateTime,
+    pub result: std::io::Result<Recording>,
+}
+
+impl JobResult {
+    /// One line of the summary file
+    pub fn summary_line(&self) -> String {
+        let outcome = match &self.result {
+            Ok(r) => format!(
+                "OK\t{}\t{} bytes\t{} reconnects\t{}",
+                format_duration(r.elapsed),
+                r.bytes,
+                r.reconnects,
+                r.files
+                    .iter()
+                    .map(|f| f.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Err(e) => format!("FAILED\t{}", e),
+        };
+
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.start.format("%Y-%m-%d %H:%M"),
+            self.name,
+            self.station,
+            outcome
+        )
+    }
+}
+
+/// Runs the recordings of the schedule as their time comes.
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    recordings: Vec<ScheduledRecording>,
+    stations: Vec<Station>,
+    summary: Option<PathBuf>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    /// Resolves the stations of the schedule up front, so a typo is noticed right away
+    /// instead of when the recording should start. Unknown stations are skipped.
+    pub fn new(clock: C, config: Rc<Config>, summary: Option<PathBuf>) -> Scheduler<C> {
+        let mut recordings = Vec::new();
+        let mut stations = Vec::new();
+        let directory = Directory::new(config.clone());
+
+        for r in config.schedule.iter() {
+            match directory.find_station(&r.station) {
+                Some(s) => {
+                    recordings.push(r.clone());
+                    stations.push(s);
+                }
+                None => error!(
+                    "Station \"{}\" of \"{}\" was not found, it won't be recorded",
+                    r.station,
+                    r.name()
+                ),
+            }
+        }
+
+        Scheduler {
+            clock,
+            recordings,
+            stations,
+            summary,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recordings.is_empty()
+    }
+
+    /// Next recording to start strictly after `now`, with its start time
+    pub fn next_after(&self, now: NaiveDateTime) -> Option<(NaiveDateTime, usize)> {
+        self.recordings
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.start.next_after(now).map(|t| (t, i)))
+            .min()
+    }
+
+    /// Upcoming recordings within the next week
+    pub fn upcoming(&self) -> Vec<(NaiveDateTime, &ScheduledRecording)> {
+        let now = self.clock.now();
+        let mut list: Vec<(NaiveDateTime, &ScheduledRecording)> = self
+            .recordings
+            .iter()
+            .filter_map(|r| r.start.next_after(now).map(|t| (t, r)))
+            .collect();
+
+        list.sort_by_key(|(t, _)| *t);
+        list
+    }
+
+    /// Runs forever, recording each programme in its own thread so they can overlap.
+    pub fn run(&self) -> ! {
+        let (tx, rx) = channel::<JobResult>();
+        let mut last_check = self.clock.now() - TimeDelta::seconds(1);
+
+        loop {
+            let now = self.clock.now();
+
+            // Start everything that became due since the last check
+            for (i, r) in self.recordings.iter().enumerate() {
+                if let Some(start) = r.start.next_after(last_check)
+                    && start <= now
+                {
+                    self.start_job(start, i, tx.clone());
+                }
+            }
+            last_check = now;
+
+            while let Ok(result) = rx.try_recv() {
+                self.report(result);
+            }
+
+            let nap = match self.next_after(now) {
+                Some((next, _)) => (next - now).to_std().unwrap_or_default().min(TICK),
+                None => TICK,
+            };
+            self.clock.sleep(nap);
+        }
+    }
+
+    fn start_job(&self, start: NaiveDateTime, i: usize, tx: Sender<JobResult>) {
+        let recording = &self.recordings[i];
+        let station = self.stations[i].clone();
+
+        let options = recording.record_options(start);
+
+        if let Some(dir) = options.output.as_ref().and_then(|p| p.parent())
+            && !dir.as_os_str().is_empty()
+            && let Err(e) = std::fs::create_dir_all(dir)
+        {
+            warn!("Could not create {}: {}", dir.display(), e);
+        }
+
+        println!(
+            "[{}] Recording \"{}\" from {} for {}",
+            self.clock.now().format("%Y-%m-%d %H:%M:%S"),
+            recording.name(),
+            station.station,
+            format_duration(recording.duration)
+        );
+
+        let name = recording.name().to_string();
+        thread::spawn(move || {
+            let result = record(&station, &options);
+
+            let _ = tx.send(JobResult {
+                name,
+                station: station.station,
+                start,
+                result,
+            });
+        });
+    }
+
+    fn report(&self, result: JobResult) {
+        match &result.result {
+            Ok(r) => println!(
+                "[{}] Finished \"{}\": {} file(s), {} bytes",
+                self.clock.now().format("%Y-%m-%d %H:%M:%S"),
+                result.name,
+                r.files.len(),
+                r.bytes
+            ),
+            Err(e) => error!("Recording \"{}\" failed: {}", result.name, e),
+        }
+
+        let Some(path) = &self.summary else { return };
+
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{}", result.summary_line()));
+
+        if let Err(e) = written {
+            warn!("Could not write the summary to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Default location of the summary: `$XDG_DATA_HOME/radio-cli/scheduler.log`
+pub fn default_summary_path() -> Option<PathBuf> {
+    xdg::BaseDirectories::with_prefix("radio-cli")
+        .ok()?
+        .place_data_file("scheduler.log")
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::NaiveDate;
+
+    /// A clock stopped at a given time
+    struct Stopped(NaiveDateTime);
+
+    impl Clock for Stopped {
+        fn now(&self) -> NaiveDateTime {
+            self.0
+        }
+
+        fn sleep(&self, _duration: Duration) {}
+    }
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        // January 2024 starts on a Monday
+        NaiveDate::from_ymd_opt(2024, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    fn recording(json: serde_json::Value) -> ScheduledRecording {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn scheduler(now: NaiveDateTime) -> Scheduler<Stopped> {
+        let mut config = testing::config(&[("Jazz FM", "http://jazz.example/live")]);
+        // Unknown stations aren't looked for on the internet
+        config.directories.radio_browser = false;
+        config.schedule = vec![
+            recording(serde_json::json!({
+                "name": "Friday jazz", "station": "Jazz FM",
+                "days": ["fri"], "time": "22:00", "duration": "2h",
+            })),
+            recording(serde_json::json!({
+                "name": "Mornings", "station": "Jazz FM",
+                "days": ["mon", "wed"], "time": "07:30", "duration": "1h",
+            })),
+            recording(serde_json::json!({
+                "name": "Missing", "station": "Nowhere FM",
+                "time": "12:00", "duration": "1h",
+            })),
+        ];
+
+        Scheduler::new(Stopped(now), Rc::new(config), None)
+    }
+
+    #[test]
+    fn fills_in_the_output_template() {
+        let show = recording(serde_json::json!({
+            "name": "Late: Night", "station": "Jazz/FM",
+            "time": "22:00", "duration": "2h",
+            "output": "shows/{name}/{station} {weekday} {date} {time}",
+        }));
+        assert_eq!(
+            show.output_path(at(5, 22, 0)),
+            PathBuf::from("shows/Late_ Night/Jazz_FM Friday 2024-01-05 22h00")
+        );
+
+        // The name is the station's without one, and the template has a default
+        let show = recording(serde_json::json!({
+            "station": "Kiss 92.5", "time": "22:00", "duration": "2h",
+        }));
+        assert_eq!(
+            show.output_path(at(1, 22, 0)),
+            PathBuf::from("Kiss 92.5 2024-01-01")
+        );
+
+        let show = recording(serde_json::json!({
+            "station": "Jazz FM", "time": "22:00", "duration": "2h",
+            "output": "~/Recordings/{date}",
+        }));
+        let expected = match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join("Recordings/2024-01-01"),
+            None => PathBuf::from("~/Recordings/2024-01-01"),
+        };
+        assert_eq!(show.output_path(at(1, 22, 0)), expected);
+    }
+
+    #[test]
+    fn always_adds_the_extension_of_the_stream() {
+        let show = recording(serde_json::json!({
+            "station": "Kiss 92.5", "time": "22:00", "duration": "2h", "split": true,
+        }));
+        let options = show.record_options(at(1, 22, 0));
+
+        assert!(options.add_extension);
+        assert!(options.split);
+        assert_eq!(options.duration, Some(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn finds_the_next_recordings_in_order() {
+        // Wednesday morning
+        let scheduler = scheduler(at(3, 8, 0));
+
+        // The one with an unknown station is left out
+        assert!(!scheduler.is_empty());
+        let upcoming: Vec<(NaiveDateTime, &str)> = scheduler
+            .upcoming()
+            .into_iter()
+            .map(|(t, r)| (t, r.name()))
+            .collect();
+        assert_eq!(
+            upcoming,
+            [(at(5, 22, 0), "Friday jazz"), (at(8, 7, 30), "Mornings")]
+        );
+
+        assert_eq!(scheduler.next_after(at(3, 8, 0)), Some((at(5, 22, 0), 0)));
+        assert_eq!(scheduler.next_after(at(2, 23, 0)), Some((at(3, 7, 30), 1)));
+        // Strictly after
+        assert_eq!(scheduler.next_after(at(3, 7, 30)), Some((at(5, 22, 0), 0)));
+    }
+
+    #[test]
+    fn summarizes_each_recording_in_a_line() {
+        let done = JobResult {
+            name: String::from("Friday jazz"),
+            station: String::from("Jazz FM"),
+            start: at(5, 22, 0),
+            result: Ok(Recording {
+                files: vec![PathBuf::from("a.mp3"), PathBuf::from("b.mp3")],
+                bytes: 1024,
+                reconnects: 2,
+                elapsed: Duration::from_secs(7200),
+            }),
+        };
+        assert_eq!(
+            done.summary_line(),
+            format!(
+                "2024-01-05 22:00\tFriday jazz\tJazz FM\tOK\t{}\t1024 bytes\t2 reconnects\ta.mp3, b.mp3",
+                format_duration(Duration::from_secs(7200))
+            )
+        );
+
+        let failed = JobResult {
+            result: Err(std::io::Error::other("Connection refused")),
+            ..done
+        };
+        assert_eq!(
+            failed.summary_line(),
+            "2024-01-05 22:00\tFriday jazz\tJazz FM\tFAILED\tConnection refused"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! The Shoutcast directory (<https://directory.shoutcast.com>), through its legacy API,
+//! which needs a developer key.
+//!
+//! The API searches by name or by genre, so the rest of the filters are applied locally,
+//! and it only gives one page of results.
+
+use radiobrowser::{ApiCountry, ApiStation};
+use reqwest::Url;
+use reqwest::blocking::Client;
+use std::error::Error;
+
+use crate::browser::{Category, Group};
+use crate::directory::{MemoryDirectory, StationDirectory, new_station};
+use crate::radio_browser::StationSearch;
+use crate::xml;
+
+const API_URL: &str = "http://api.shoutcast.com/legacy";
+
+/// Where the playlists of the stations are
+const TUNEIN_URL: &str = "http://yp.shoutcast.com";
+
+/// Most stations the API is asked for
+const LIMIT: usize = 500;
+
+pub struct ShoutcastDirectory {
+    client: Client,
+    key: String,
+}
+
+impl ShoutcastDirectory {
+    pub fn new(client: Client, key: &str) -> ShoutcastDirectory {
+        ShoutcastDirectory {
+            client,
+            key: key.to_string(),
+        }
+    }
+
+    fn get(&self, endpoint: &str, params: &[(&str, &str)]) -> Result<String, Box<dyn Error>> {
+        let limit = LIMIT.to_string();
+        let mut query = vec![("k", self.key.as_str()), ("limit", limit.as_str())];
+        query.extend_from_slice(params);
+
+        let url = Url::parse_with_params(&format!("{}/{}", API_URL, endpoint), &query)?;
+        Ok(self.client.get(url).send()?.error_for_status()?.text()?)
+    }
+}
+
+impl StationDirectory for ShoutcastDirectory {
+    fn search(&self, search: &StationSearch) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+        if search.get("offset").is_some_and(|o| o != "0") {
+            return Ok(Vec::new());
+        }
+
+        let genre = search.get("tag").or(search.get("tagList"));
+        let body = match (search.get("name"), genre) {
+            (Some(name), _) => self.get("stationsearch", &[("search", name)])?,
+            (None, Some(genre)) => self.get("genresearch", &[("genre", genre)])?,
+            (None, None) => self.get("Top500", &[])?,
+        };
+
+        // Without what the API did already
+        let search = search.clone().without("name").offset(0).limit(0);
+        MemoryDirectory::new(parse_stations(&body)?).search(&search)
+    }
+
+    /// The API can't find stations by id
+    fn station(&self, _uuid: &str) -> Result<Option<ApiStation>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    fn countries(&self) -> Result<Vec<ApiCountry>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// The API doesn't tell how many stations each genre has
+    fn groups(
+        &self,
+        _category: Category,
+        _country: Option<&str>,
+        _hidebroken: bool,
+    ) -> Result<Vec<Group>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+}
+
+/// The stations of a `<stationlist>`
+pub fn parse_stations(body: &str) -> Result<Vec<ApiStation>, Box<dyn Error>> {
+    let root = xml::parse(body)?;
+    let base = root
+        .child("tunein")
+        .and_then(|t| t.attr("base"))
+        .unwrap_or("/sbin/tunein-station.pls");
+
+    Ok(root
+        .children
+        .iter()
+        .filter(|e| e.name == "station")
+        .filter_map(|s| {
+            let name = s.attr("name")?;
+            let id = s.attr("id")?;
+            let url = format!("{}{}?id={}", TUNEIN_URL, base, id);
+
+            let mut station = new_station(&format!("shoutcast:{}", id), name, &url);
+            station.codec = match s.attr("mt") {
+                Some("audio/mpeg") => String::from("MP3"),
+                Some("audio/aacp") => String::from("AAC+"),
+                _ => String::new(),
+            };
+            station.bitrate = s.attr("br").and_then(|b| b.parse().ok()).unwrap_or(0);
+            station.tags = ["genre", "genre2", "genre3"]
+                .iter()
+                .filter_map(|g| s.attr(g))
+                .collect::<Vec<&str>>()
+                .join(",");
+            station.favicon = s.attr("logo").unwrap_or("").to_string();
+            station.clickcount = s.attr("lc").and_then(|l| l.parse().ok()).unwrap_or(0);
+
+            Some(station)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+    use std::fs;
+
+    #[test]
+    fn reads_a_stationlist() {
+        let body = fs::read_to_string(fixture("shoutcast.xml")).unwrap();
+        let stations = parse_stations(&body).unwrap();
+
+        let names: Vec<&str> = stations.iter().map(|s| s.name.as_str()).collect();
+        // The one without a name is left out
+        assert_eq!(names, ["Lounge & Chill", "Hits FM", "Odd One"]);
+
+        let lounge = &stations[0];
+        assert_eq!(lounge.stationuuid, "shoutcast:1234");
+        assert_eq!(
+            lounge.url,
+            "http://yp.shoutcast.com/sbin/tunein-station.pls?id=1234"
+        );
+        assert_eq!(lounge.codec, "MP3");
+        assert_eq!(lounge.bitrate, 128);
+        assert_eq!(lounge.tags, "Lounge,Chillout");
+        assert_eq!(lounge.favicon, "http://logo.example/1234.png");
+        assert_eq!(lounge.clickcount, 321);
+
+        assert_eq!(stations[1].codec, "AAC+");
+        assert_eq!(stations[1].tags, "Top 40");
+        assert_eq!(stations[2].codec, "");
+        assert_eq!(stations[2].bitrate, 0);
+        assert_eq!(stations[2].tags, "");
+    }
+
+    #[test]
+    fn tunes_in_through_the_base_given() {
+        let stations = parse_stations(
+            r#"<stationlist><tunein base="/sbin/tunein-station.m3u"/><station name="A" id="7"/></stationlist>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            stations[0].url,
+            "http://yp.shoutcast.com/sbin/tunein-station.m3u?id=7"
+        );
+
+        let stations =
+            parse_stations(r#"<stationlist><station name="A" id="7"/></stationlist>"#).unwrap();
+        assert_eq!(
+            stations[0].url,
+            "http://yp.shoutcast.com/sbin/tunein-station.pls?id=7"
+        );
+    }
+}
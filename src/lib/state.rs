@@ -0,0 +1,161 @@
+//! What radio-cli remembers from one run to the next, kept in
+//! `$XDG_STATE_HOME/radio-cli/state.json` (it's not configuration, so not in the config).
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Error, Result};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct State {
+    /// Volume the user chose last time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+
+    /// Where the podcast episodes were stopped, in seconds, by the URL of their audio
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub positions: HashMap<String, f64>,
+
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl State {
+    /// Reads the state of the last run. Anything wrong with it just starts afresh.
+    pub fn load() -> State {
+        let path = xdg::BaseDirectories::with_prefix("radio-cli")
+            .ok()
+            .and_then(|dirs| dirs.place_state_file("state.json").ok());
+
+        match path {
+            Some(p) => State::load_from(p),
+            None => {
+                warn!("Could not find where to keep the state, nothing will be remembered");
+                State::default()
+            }
+        }
+    }
+
+    pub fn load_from(path: PathBuf) -> State {
+        let mut state = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str::<State>(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring the state in {}: {}", path.display(), e);
+                State::default()
+            }),
+            Err(e) => {
+                debug!("No state in {}: {}", path.display(), e);
+                State::default()
+            }
+        };
+
+        state.path = Some(path);
+        state
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let json = serde_json::to_string_pretty(self).map_err(Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    /// Remembers the volume, complaining only in the log if it can't
+    pub fn set_volume(&mut self, volume: f64) {
+        if self.volume == Some(volume) {
+            return;
+        }
+
+        self.volume = Some(volume);
+        if let Err(e) = self.save() {
+            warn!("Could not save the volume: {}", e);
+        }
+    }
+
+    /// Where to resume the episode at `url`, if it was stopped before its end
+    pub fn position(&self, url: &str) -> Option<f64> {
+        self.positions.get(url).copied().filter(|&p| is_position(p))
+    }
+
+    /// Remembers where the episode at `url` was stopped, or forgets it once it's over
+    pub fn set_position(&mut self, url: &str, position: Option<f64>) {
+        match position {
+            Some(p) if is_position(p) => self.positions.insert(url.to_string(), p),
+            _ => self.positions.remove(url),
+        };
+
+        if let Err(e) = self.save() {
+            warn!("Could not save where the episode was stopped: {}", e);
+        }
+    }
+}
+
+/// Whether `seconds` can be where an episode was stopped, unlike a negative or NaN one
+fn is_position(seconds: f64) -> bool {
+    seconds.is_finite() && seconds >= 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_from_one_run_to_the_next() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut state = State::load_from(path.clone());
+        assert_eq!(state.volume, None);
+        state.set_volume(40.0);
+        state.set_position("http://podcast.example/1.mp3", Some(90.5));
+        state.set_position("http://podcast.example/2.mp3", Some(10.0));
+        state.set_position("http://podcast.example/2.mp3", None);
+
+        let state = State::load_from(path);
+        assert_eq!(state.volume, Some(40.0));
+        assert_eq!(state.position("http://podcast.example/1.mp3"), Some(90.5));
+        assert_eq!(state.position("http://podcast.example/2.mp3"), None);
+    }
+
+    #[test]
+    fn forgets_the_positions_that_are_not_in_the_episode() {
+        let url = "http://podcast.example/1.mp3";
+        let mut state = State::default();
+
+        state.set_position(url, Some(30.0));
+        state.set_position(url, Some(f64::NAN));
+        assert_eq!(state.position(url), None);
+        state.set_position(url, Some(-1.0));
+        assert_eq!(state.position(url), None);
+
+        // Even if the file has them
+        state.positions.insert(url.to_string(), f64::INFINITY);
+        assert_eq!(state.position(url), None);
+        state.positions.insert(url.to_string(), 0.0);
+        assert_eq!(state.position(url), Some(0.0));
+    }
+
+    #[test]
+    fn starts_afresh_when_the_file_is_wrong() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let mut state = State::load_from(path.clone());
+        assert_eq!(state.volume, None);
+
+        // And fixes it
+        state.set_volume(60.0);
+        assert_eq!(State::load_from(path).volume, Some(60.0));
+    }
+
+    #[test]
+    fn saves_nothing_without_a_file() {
+        let mut state = State::default();
+        state.set_volume(60.0);
+        assert!(state.save().is_ok());
+        assert_eq!(state.volume, Some(60.0));
+    }
+}
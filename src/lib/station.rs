@@ -1,12 +1,166 @@
-use serde::Deserialize;
-#[derive(Deserialize, Debug, Clone)]
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::local;
+
+/// What a station plays
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StationKind {
+    /// An internet stream, or anything else mpv opens by itself
+    #[default]
+    Stream,
+    /// A file on disk
+    File,
+    /// A folder of music, played shuffled
+    Directory,
+}
+
+impl StationKind {
+    pub fn is_stream(&self) -> bool {
+        *self == StationKind::Stream
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Station {
     pub station: String,
     pub url: String,
+
+    /// `file` and `directory` stations play from the disk, `url` being their path
+    #[serde(
+        default,
+        rename = "type",
+        skip_serializing_if = "StationKind::is_stream"
+    )]
+    pub kind: StationKind,
+
+    /// Id of the station in Radio Browser, if it came from there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+
+    /// Added to the volume when playing this station, to even out loud and quiet ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f64>,
+
+    /// Audio filter preset to play this station with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+
+    /// Audio output to play this station on, as listed by `radio-cli devices`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_device: Option<String>,
+}
+
+impl Station {
+    /// Volume to play this station at, given the volume chosen by the user
+    pub fn adjusted_volume(&self, volume: f64) -> f64 {
+        (volume + self.volume.unwrap_or(0.0)).clamp(0.0, 100.0)
+    }
+
+    pub fn is_local(&self) -> bool {
+        !self.kind.is_stream()
+    }
+
+    /// Where a `file` or `directory` station is on the disk
+    pub fn path(&self) -> Option<PathBuf> {
+        self.is_local().then(|| local::expand_home(&self.url))
+    }
+
+    /// Checks that a `file` or `directory` station is there to be played
+    pub fn check(&self) -> Result<(), String> {
+        let Some(path) = self.path() else {
+            return Ok(());
+        };
+
+        match self.kind {
+            _ if !path.exists() => Err(format!("{} does not exist", path.display())),
+            StationKind::File if !path.is_file() => {
+                Err(format!("{} is not a file", path.display()))
+            }
+            StationKind::Directory if !path.is_dir() => {
+                Err(format!("{} is not a folder", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Volume chosen by the user, given the volume this station is playing at and the one
+    /// chosen before. The volumes clamped to 0 or 100 could come from several of the user's,
+    /// so the one before is kept if it plays at `volume`, rather than drifting.
+    pub fn base_volume(&self, volume: f64, previous: f64) -> f64 {
+        if self.adjusted_volume(previous) == volume {
+            return previous;
+        }
+
+        (volume - self.volume.unwrap_or(0.0)).clamp(0.0, 100.0)
+    }
+}
+
+/// Station of `stations` `step` places away from the one with this URL, wrapping around.
+/// URLs that are not in the list start from its ends.
+pub fn station_near(stations: &[&Station], url: &str, step: isize) -> Option<Station> {
+    if stations.is_empty() {
+        return None;
+    }
+
+    let len = stations.len() as isize;
+    let i = match stations.iter().position(|s| s.url == url) {
+        Some(i) => (i as isize + step).rem_euclid(len),
+        None if step > 0 => 0,
+        None => len - 1,
+    };
+
+    Some(stations[i as usize].clone())
 }
 
 impl std::fmt::Display for Station {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.station)
+        match self.kind {
+            StationKind::Stream => write!(f, "{}", self.station),
+            StationKind::File => write!(f, "{} [file]", self.station),
+            StationKind::Directory => write!(f, "{} [folder]", self.station),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(volume: Option<f64>) -> Station {
+        Station {
+            station: String::from("Fixture FM"),
+            url: String::from("http://fixture.example/live"),
+            volume,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn adjusts_the_volume_by_the_station_offset() {
+        assert_eq!(station(None).adjusted_volume(70.0), 70.0);
+        assert_eq!(station(Some(10.0)).adjusted_volume(70.0), 80.0);
+        assert_eq!(station(Some(-10.0)).adjusted_volume(70.0), 60.0);
+        assert_eq!(station(Some(20.0)).adjusted_volume(90.0), 100.0);
+        assert_eq!(station(Some(-20.0)).adjusted_volume(10.0), 0.0);
+    }
+
+    #[test]
+    fn finds_the_volume_of_the_user_back() {
+        for offset in [None, Some(20.0), Some(-20.0)] {
+            let station = station(offset);
+            for v in [0.0, 10.0, 50.0, 90.0, 100.0] {
+                assert_eq!(station.base_volume(station.adjusted_volume(v), v), v);
+            }
+        }
+
+        // Changed by the user, the offset is taken away
+        let louder = station(Some(20.0));
+        assert_eq!(louder.base_volume(75.0, 50.0), 55.0);
+        // Lowering it from the top starts from what is heard
+        assert_eq!(louder.base_volume(95.0, 90.0), 75.0);
+        let quieter = station(Some(-20.0));
+        assert_eq!(quieter.base_volume(5.0, 10.0), 25.0);
     }
 }
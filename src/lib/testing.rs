@@ -0,0 +1,308 @@
+//! Stand-ins for the servers radio-cli talks to, for the tests.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::icy::IcyWriter;
+use crate::player::{Player, PlayerOptions};
+use crate::station::Station;
+
+/// A request received by a `TestServer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// With the query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// An HTTP server on a free local port, answering each connection with whatever its
+/// handler returns (the raw response) and closing it.
+pub struct TestServer {
+    /// `http://127.0.0.1:<port>`
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    /// `handler` gets how many requests came before, and the request
+    pub fn start<F>(handler: F) -> TestServer
+    where
+        F: Fn(usize, &Request) -> Vec<u8> + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = Arc::new(handler);
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let handler = handler.clone();
+                let received = received.clone();
+
+                thread::spawn(move || {
+                    let Some(request) = read_request(&stream) else {
+                        return;
+                    };
+                    let count = {
+                        let mut received = received.lock().unwrap();
+                        received.push(request.clone());
+                        received.len() - 1
+                    };
+
+                    let mut stream = stream;
+                    let _ = stream.write_all(&handler(count, &request));
+                });
+            }
+        });
+
+        TestServer { url, requests }
+    }
+
+    /// Serves `responses` in order, one per connection, and `503` once they're over
+    pub fn scripted(responses: Vec<Vec<u8>>) -> TestServer {
+        TestServer::start(move |i, _| match responses.get(i) {
+            Some(r) => r.clone(),
+            None => response("503 Service Unavailable", &[], b""),
+        })
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+/// A whole response. Without a `Content-Length` header, the body ends when the connection does.
+pub fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status).into_bytes();
+    for (name, value) in headers {
+        out.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    out.extend_from_slice(b"\r\n");
+    out.extend_from_slice(body);
+
+    out
+}
+
+/// A file of `tests/fixtures`
+pub fn fixture(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// A JSON answer
+pub fn json(body: &serde_json::Value) -> Vec<u8> {
+    let body = body.to_string();
+    response(
+        "200 OK",
+        &[
+            ("Content-Type", "application/json"),
+            ("Content-Length", &body.len().to_string()),
+        ],
+        body.as_bytes(),
+    )
+}
+
+/// A station as Radio Browser describes it
+pub fn api_station(uuid: &str, name: &str, url: &str) -> serde_json::Value {
+    serde_json::json!({
+        "changeuuid": "",
+        "stationuuid": uuid,
+        "name": name,
+        "url": url,
+        "url_resolved": url,
+        "homepage": "",
+        "favicon": "",
+        "tags": "",
+        "country": "",
+        "countrycode": "ES",
+        "state": "",
+        "language": "",
+        "votes": 0,
+        "codec": "MP3",
+        "bitrate": 128,
+        "hls": 0,
+        "lastcheckok": 1,
+        "clickcount": 0,
+        "clicktrend": 0,
+    })
+}
+
+/// The body of an ICY stream, each part of the audio announced with its title
+pub fn icy_body(metaint: usize, parts: &[(Option<&str>, &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    let mut writer = IcyWriter::new(&mut body, Some(metaint));
+    for (title, audio) in parts {
+        writer.set_title(*title);
+        writer.write_all(audio).unwrap();
+    }
+
+    body
+}
+
+/// A stream of `audio/mpeg` with ICY metadata every `metaint` bytes
+pub fn icy_stream(metaint: usize, parts: &[(Option<&str>, &[u8])]) -> Vec<u8> {
+    response(
+        "200 OK",
+        &[
+            ("Content-Type", "audio/mpeg"),
+            ("icy-name", "Fixture FM"),
+            ("icy-metaint", &metaint.to_string()),
+        ],
+        &icy_body(metaint, parts),
+    )
+}
+
+/// A player that only takes note of what it's asked, in a log shared with the test
+pub struct FakePlayer {
+    log: Arc<Mutex<Vec<String>>>,
+    volume: f64,
+    paused: bool,
+    muted: bool,
+    pub title: Option<String>,
+}
+
+impl FakePlayer {
+    /// A spawner of fake players, and the log of all of them
+    #[allow(clippy::type_complexity)]
+    pub fn spawner() -> (
+        impl FnMut(&Station, &PlayerOptions) -> std::io::Result<FakePlayer>,
+        Arc<Mutex<Vec<String>>>,
+    ) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let shared = log.clone();
+
+        let spawn = move |station: &Station, options: &PlayerOptions| {
+            let volume = options.volume.unwrap_or(100.0);
+            shared
+                .lock()
+                .unwrap()
+                .push(format!("play {} at {}", station.url, volume));
+
+            Ok(FakePlayer {
+                log: shared.clone(),
+                volume,
+                paused: false,
+                muted: false,
+                title: Some(format!("Live on {}", station.station)),
+            })
+        };
+
+        (spawn, log)
+    }
+
+    fn note(&self, what: String) {
+        self.log.lock().unwrap().push(what);
+    }
+}
+
+impl Player for FakePlayer {
+    fn volume(&mut self) -> std::io::Result<f64> {
+        Ok(self.volume)
+    }
+
+    fn set_volume(&mut self, volume: f64) -> std::io::Result<()> {
+        self.note(format!("volume {}", volume));
+        self.volume = volume;
+        Ok(())
+    }
+
+    fn paused(&mut self) -> std::io::Result<bool> {
+        Ok(self.paused)
+    }
+
+    fn set_paused(&mut self, paused: bool) -> std::io::Result<()> {
+        self.note(format!("paused {}", paused));
+        self.paused = paused;
+        Ok(())
+    }
+
+    fn muted(&mut self) -> std::io::Result<bool> {
+        Ok(self.muted)
+    }
+
+    fn set_muted(&mut self, muted: bool) -> std::io::Result<()> {
+        self.note(format!("muted {}", muted));
+        self.muted = muted;
+        Ok(())
+    }
+
+    fn title(&mut self) -> Option<String> {
+        self.title.clone()
+    }
+
+    fn try_wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        Ok(None)
+    }
+
+    fn stop(&mut self) -> std::io::Result<()> {
+        self.note(String::from("stop"));
+        Ok(())
+    }
+}
+
+/// A config with these stations (name and URL), and nothing to look for on the internet
+pub fn config(stations: &[(&str, &str)]) -> crate::Config {
+    let data: Vec<Station> = stations
+        .iter()
+        .map(|(name, url)| Station {
+            station: name.to_string(),
+            url: url.to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    serde_json::from_value(serde_json::json!({
+        "config_version": "2.3.0",
+        "country": "ES",
+        "data": data,
+    }))
+    .unwrap()
+}
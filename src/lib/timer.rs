@@ -0,0 +1,239 @@
+use chrono::{Local, NaiveTime, TimeDelta};
+use serde::Deserialize;
+use serde::de::{Deserializer, Error as SeError};
+use std::time::{Duration, Instant};
+
+/// Durations the in-session sleep key cycles through, in minutes.
+const SLEEP_STEPS: [u64; 5] = [15, 30, 45, 60, 90];
+
+/// Parses a human duration such as `30m`, `1h30m`, `90s` or `45` (minutes).
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+
+    if s.is_empty() {
+        return Err(String::from("empty duration"));
+    }
+
+    let too_long = || format!("\"{}\" is too long", s);
+
+    // A bare number is taken as minutes
+    if let Ok(minutes) = s.parse::<u64>() {
+        return minutes
+            .checked_mul(60)
+            .map(Duration::from_secs)
+            .ok_or_else(too_long);
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let n = match number.parse::<u64>() {
+            Ok(n) => n,
+            Err(_) => return Err(format!("invalid duration \"{}\"", s)),
+        };
+        number.clear();
+
+        let secs = match c {
+            'h' => n.checked_mul(3600),
+            'm' => n.checked_mul(60),
+            's' => Some(n),
+            _ => return Err(format!("invalid unit '{}' in \"{}\"", c, s)),
+        };
+        total = secs
+            .and_then(|secs| total.checked_add(secs))
+            .ok_or_else(too_long)?;
+    }
+
+    if !number.is_empty() {
+        return Err(format!("missing unit at the end of \"{}\"", s));
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Parses a wall-clock time such as `23:30` or `07:00:15`.
+pub fn parse_time_of_day(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s.trim(), "%H:%M:%S"))
+        .map_err(|_| format!("invalid time \"{}\", expected HH:MM", s))
+}
+
+/// Formats a duration the same way `parse_duration` reads it.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+
+    match (h, m, s) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, 0) => format!("{}m", m),
+        (0, m, s) => format!("{}m{}s", m, s),
+        (h, m, _) => format!("{}h{}m", h, m),
+    }
+}
+
+/// Returns how long it is until the next time the local clock shows `time`.
+pub fn until_time_of_day(time: NaiveTime) -> Duration {
+    let now = Local::now().naive_local();
+    let mut target = now.date().and_time(time);
+
+    if target <= now {
+        target += TimeDelta::days(1);
+    }
+
+    (target - now).to_std().unwrap_or_default()
+}
+
+/// What the player should do at a given point of a sleep timer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SleepState {
+    Running,
+    /// Inside the fade-out window, the volume should be scaled by the given factor (1.0 -> 0.0)
+    Fading(f64),
+    Expired,
+}
+
+/// Stops playback at a given instant, optionally fading the volume out before.
+#[derive(Debug, Clone, Copy)]
+pub struct SleepTimer {
+    deadline: Instant,
+    fade: Option<Duration>,
+}
+
+impl SleepTimer {
+    /// Fails when the timer would end further away than the clock can count
+    pub fn new(duration: Duration, fade: Option<Duration>) -> Result<SleepTimer, String> {
+        let deadline = Instant::now()
+            .checked_add(duration)
+            .ok_or_else(|| format!("{} is too long for a timer", format_duration(duration)))?;
+
+        Ok(SleepTimer { deadline, fade })
+    }
+
+    pub fn until(time: NaiveTime, fade: Option<Duration>) -> Result<SleepTimer, String> {
+        SleepTimer::new(until_time_of_day(time), fade)
+    }
+
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+
+    pub fn state(&self, now: Instant) -> SleepState {
+        let remaining = self.remaining(now);
+
+        if remaining.is_zero() {
+            return SleepState::Expired;
+        }
+
+        match self.fade {
+            Some(fade) if !fade.is_zero() && remaining < fade => {
+                SleepState::Fading(remaining.as_secs_f64() / fade.as_secs_f64())
+            }
+            _ => SleepState::Running,
+        }
+    }
+
+    /// Advances a (possibly unset) timer to the next step, as done by the in-session key.
+    /// After the longest step the timer is turned off.
+    pub fn cycle(current: Option<SleepTimer>, fade: Option<Duration>) -> Option<SleepTimer> {
+        let now = Instant::now();
+        let remaining = current.map(|t| t.remaining(now)).unwrap_or_default();
+
+        SLEEP_STEPS
+            .iter()
+            .map(|m| Duration::from_secs(m * 60))
+            .find(|step| *step > remaining + Duration::from_secs(60))
+            .and_then(|step| SleepTimer::new(step, fade).ok())
+    }
+}
+
+pub(crate) fn deserialize_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_time_of_day(&String::deserialize(deserializer)?).map_err(SeError::custom)
+}
+
+pub(crate) fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => parse_duration(&s).map(Some).map_err(SeError::custom),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn deserialize_required_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    parse_duration(&String::deserialize(deserializer)?).map_err(SeError::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 90s "), Ok(Duration::from_secs(90)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn huge_durations_are_errors() {
+        assert!(parse_duration("18446744073709551615").is_err());
+        assert!(parse_duration("9999999999999999999h").is_err());
+        assert!(parse_duration("18446744073709551615m").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        for s in ["45s", "30m", "1m30s", "2h15m"] {
+            assert_eq!(format_duration(parse_duration(s).unwrap()), s);
+        }
+    }
+
+    #[test]
+    fn timers_too_long_are_errors() {
+        assert!(SleepTimer::new(Duration::MAX, None).is_err());
+        assert!(SleepTimer::new(Duration::from_secs(60), None).is_ok());
+    }
+
+    #[test]
+    fn fades_out_before_expiring() {
+        let timer =
+            SleepTimer::new(Duration::from_secs(60), Some(Duration::from_secs(20))).unwrap();
+        let start = timer.deadline - Duration::from_secs(60);
+
+        assert_eq!(timer.state(start), SleepState::Running);
+        assert_eq!(
+            timer.state(start + Duration::from_secs(50)),
+            SleepState::Fading(0.5)
+        );
+        assert_eq!(timer.state(timer.deadline), SleepState::Expired);
+    }
+
+    #[test]
+    fn the_sleep_key_cycles_and_turns_off() {
+        let first = SleepTimer::cycle(None, None).unwrap();
+        assert!(first.remaining(Instant::now()) <= Duration::from_secs(15 * 60));
+
+        let second = SleepTimer::cycle(Some(first), None).unwrap();
+        assert!(second.remaining(Instant::now()) > Duration::from_secs(29 * 60));
+
+        let longest = SleepTimer::new(Duration::from_secs(90 * 60), None).unwrap();
+        assert!(SleepTimer::cycle(Some(longest), None).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+//! Full-screen interface: the state machine lives in `state`, the drawing in `render`,
+//! and this module glues them to the terminal, the player and Radio Browser.
+
+pub mod render;
+pub mod state;
+
+use crate::Config;
+use crate::browser::{Browser, Directory};
+use crate::player::{Mpv, Player, PlayerOptions};
+use crate::state::State;
+use state::{App, Effect, Event, Key};
+
+use ratatui::DefaultTerminal;
+use ratatui::crossterm::event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use std::io::Result;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How often the player is checked when no key is pressed
+const TICK: Duration = Duration::from_millis(250);
+
+/// Everything the effects of the UI act upon
+struct Context {
+    /// Connected on the first search or station of Radio Browser, and kept
+    directory: Directory,
+    options: PlayerOptions,
+    player: Option<Mpv>,
+    browser: Option<Browser>,
+    /// Where the volume is remembered, if it is
+    state: Option<State>,
+}
+
+pub fn run(config: Rc<Config>, options: &PlayerOptions, state: Option<State>) -> Result<()> {
+    let stations = config.stations().cloned().collect();
+
+    let mut app = App::new(stations);
+    app.volume = options.volume.unwrap_or(100.0);
+
+    let mut ctx = Context {
+        directory: Directory::new(config),
+        options: PlayerOptions {
+            no_terminal: true,
+            ..options.clone()
+        },
+        player: None,
+        browser: None,
+        state,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app, &mut ctx);
+    ratatui::restore();
+
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App, ctx: &mut Context) -> Result<()> {
+    loop {
+        terminal.draw(|f| render::draw(f, app))?;
+
+        if event::poll(TICK)?
+            && let event::Event::Key(k) = event::read()?
+            && let Some(key) = map_key(k)
+        {
+            let effects = match key {
+                KeyInput::Interrupt => vec![Effect::Quit],
+                KeyInput::Key(key) => app.handle_key(key),
+            };
+
+            for effect in effects {
+                if effect == Effect::Quit {
+                    return Ok(());
+                }
+
+                // Searching blocks, so show that it started
+                if let Effect::Search(_) = effect {
+                    terminal.draw(|f| render::draw(f, app))?;
+                }
+
+                apply(effect, app, ctx);
+            }
+        }
+
+        poll_player(app, ctx);
+    }
+}
+
+enum KeyInput {
+    Key(Key),
+    Interrupt,
+}
+
+fn map_key(k: KeyEvent) -> Option<KeyInput> {
+    if k.kind != KeyEventKind::Press {
+        return None;
+    }
+
+    if k.modifiers.contains(KeyModifiers::CONTROL) && k.code == KeyCode::Char('c') {
+        return Some(KeyInput::Interrupt);
+    }
+
+    let key = match k.code {
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Tab | KeyCode::BackTab => Key::Tab,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    };
+
+    Some(KeyInput::Key(key))
+}
+
+fn apply(effect: Effect, app: &mut App, ctx: &mut Context) {
+    match effect {
+        Effect::Play(station) => {
+            // Stops the previous one
+            ctx.player = None;
+
+            let options = PlayerOptions {
+                volume: Some(station.adjusted_volume(app.volume)),
+                ..ctx.options.clone()
+            };
+
+            let stream = ctx.directory.report_click(station.clone());
+            match Mpv::spawn(&stream, &options) {
+                Ok(mut mpv) => {
+                    if app.muted {
+                        let _ = mpv.set_muted(true);
+                    }
+                    ctx.player = Some(mpv);
+                    app.handle_event(Event::Started);
+                }
+                Err(e) => app.handle_event(Event::Stopped(Some(format!(
+                    "Could not play {}: {}",
+                    station.station, e
+                )))),
+            }
+        }
+        Effect::Stop => ctx.player = None,
+        Effect::Search(query) => {
+            if ctx.browser.is_none() {
+                match ctx.directory.browser(None) {
+                    Ok((b, _)) => ctx.browser = Some(b),
+                    Err(e) => {
+                        app.handle_event(Event::Error(format!(
+                            "Could not connect with Radio Browser: {}",
+                            e
+                        )));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(browser) = &ctx.browser {
+                app.handle_event(Event::SearchResults(browser.search(&query)));
+            }
+        }
+        Effect::SetVolume(v) => {
+            if let (Some(mpv), Some(station)) = (&mut ctx.player, &app.now_playing) {
+                let _ = mpv.set_volume(station.adjusted_volume(v));
+            }
+
+            if let Some(state) = &mut ctx.state {
+                state.set_volume(v);
+            }
+        }
+        Effect::SetPause(p) => {
+            if let Some(mpv) = &mut ctx.player {
+                let _ = mpv.set_paused(p);
+            }
+        }
+        Effect::SetMute(m) => {
+            if let Some(mpv) = &mut ctx.player {
+                let _ = mpv.set_muted(m);
+            }
+        }
+        Effect::Quit => {}
+    }
+}
+
+fn poll_player(app: &mut App, ctx: &mut Context) {
+    let Some(mpv) = &mut ctx.player else { return };
+
+    match mpv.try_wait() {
+        Ok(None) => {
+            let title = mpv.title();
+            if title != app.title {
+                app.handle_event(Event::Title(title));
+            }
+        }
+        Ok(Some(status)) => {
+            ctx.player = None;
+            app.handle_event(Event::Stopped(if status.success() {
+                None
+            } else {
+                Some(format!("mpv {}", status))
+            }));
+        }
+        Err(e) => {
+            ctx.player = None;
+            app.handle_event(Event::Stopped(Some(e.to_string())));
+        }
+    }
+}
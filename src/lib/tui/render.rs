@@ -0,0 +1,155 @@
+use super::state::{App, Focus, PlayerState};
+use crate::station::Station;
+
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph};
+
+const HELP: &str = "↑↓ select · Enter play · / search · Tab switch list · s stop · space pause · -/+ volume · m mute · q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [lists, now_playing, status] = Layout::vertical([
+        Constraint::Min(5),
+        Constraint::Length(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let [stations, search] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(lists);
+
+    draw_list(
+        frame,
+        stations,
+        " Stations ",
+        &app.stations.items,
+        app.stations.selected,
+        app.focus == Focus::Stations,
+        app,
+    );
+    draw_search(frame, search, app);
+    draw_now_playing(frame, now_playing, app);
+
+    let status_line = match &app.status {
+        Some(s) => Line::from(s.as_str()).yellow(),
+        None => Line::from(HELP).dark_gray(),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+}
+
+fn block(title: &str, focused: bool) -> Block<'_> {
+    let style = if focused {
+        Style::default().fg(Color::Green)
+    } else {
+        Style::default()
+    };
+
+    Block::bordered().title(title).border_style(style)
+}
+
+fn draw_list(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    items: &[Station],
+    selected: usize,
+    focused: bool,
+    app: &App,
+) {
+    let playing = app.now_playing.as_ref().map(|s| s.url.as_str());
+
+    let items: Vec<ListItem> = items
+        .iter()
+        .map(|s| {
+            let marker = if Some(s.url.as_str()) == playing {
+                "♪ "
+            } else {
+                "  "
+            };
+            ListItem::new(format!("{}{}", marker, s))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block(title, focused))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default().with_selected(Some(selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_search(frame: &mut Frame, area: Rect, app: &App) {
+    let [input, results] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(2)]).areas(area);
+
+    let cursor = if app.focus == Focus::Search {
+        "█"
+    } else {
+        ""
+    };
+    let query = Paragraph::new(format!("{}{}", app.query, cursor)).block(block(
+        " Search Radio Browser (/) ",
+        app.focus == Focus::Search,
+    ));
+    frame.render_widget(query, input);
+
+    draw_list(
+        frame,
+        results,
+        " Results ",
+        &app.results.items,
+        app.results.selected,
+        app.focus == Focus::Results,
+        app,
+    );
+}
+
+fn draw_now_playing(frame: &mut Frame, area: Rect, app: &App) {
+    let outer = block(" Now playing ", false);
+    let inner = outer.inner(area);
+    frame.render_widget(outer, area);
+
+    let [station, title, volume] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(inner);
+
+    let state = match (&app.player, app.paused) {
+        (PlayerState::Stopped, _) => "stopped",
+        (PlayerState::Loading, _) => "loading...",
+        (PlayerState::Playing, true) => "paused",
+        (PlayerState::Playing, false) => "playing",
+    };
+
+    let station_line = match &app.now_playing {
+        Some(s) => Line::from(vec![
+            Span::from(s.station.as_str()).bold().green(),
+            Span::from(format!("  ({})", state)).dark_gray(),
+        ]),
+        None => Line::from("Nothing is playing").dark_gray(),
+    };
+    frame.render_widget(Paragraph::new(station_line), station);
+
+    if let Some(t) = &app.title {
+        frame.render_widget(Paragraph::new(Line::from(t.as_str()).italic()), title);
+    }
+
+    let label = if app.muted {
+        String::from("muted")
+    } else {
+        format!("volume {:.0}%", app.volume)
+    };
+    let gauge = Gauge::default()
+        .ratio((app.volume / 100.0).clamp(0.0, 1.0))
+        .label(label)
+        .gauge_style(Style::default().fg(if app.muted {
+            Color::DarkGray
+        } else {
+            Color::Cyan
+        }));
+    frame.render_widget(gauge, volume);
+}
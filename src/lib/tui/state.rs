@@ -0,0 +1,422 @@
+use crate::controls::VOLUME_STEP;
+use crate::station::Station;
+
+/// Keys the TUI understands, independent of the terminal library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Enter,
+    Tab,
+    Esc,
+    Backspace,
+    Char(char),
+}
+
+/// Which part of the screen receives the keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Stations,
+    Search,
+    Results,
+}
+
+/// Things that happen outside of the UI
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The player is playing the station that was asked for
+    Started,
+    /// The player stopped by itself, with an error message if it failed
+    Stopped(Option<String>),
+    Title(Option<String>),
+    SearchResults(Vec<Station>),
+    Error(String),
+}
+
+/// What the UI asks the outside world to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+    Play(Station),
+    Stop,
+    Search(String),
+    SetVolume(f64),
+    SetPause(bool),
+    SetMute(bool),
+    Quit,
+}
+
+/// A list with a selected row
+#[derive(Debug, Clone)]
+pub struct Selectable<T> {
+    pub items: Vec<T>,
+    pub selected: usize,
+}
+
+impl<T> Selectable<T> {
+    pub fn new(items: Vec<T>) -> Selectable<T> {
+        Selectable { items, selected: 0 }
+    }
+
+    pub fn current(&self) -> Option<&T> {
+        self.items.get(self.selected)
+    }
+
+    fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    fn down(&mut self) {
+        if self.selected + 1 < self.items.len() {
+            self.selected += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerState {
+    Stopped,
+    Loading,
+    Playing,
+}
+
+/// State of the whole TUI. Keys and events go in, effects come out, and rendering only reads it.
+#[derive(Debug, Clone)]
+pub struct App {
+    pub stations: Selectable<Station>,
+    pub results: Selectable<Station>,
+    pub query: String,
+    pub focus: Focus,
+
+    pub now_playing: Option<Station>,
+    pub title: Option<String>,
+    pub player: PlayerState,
+    pub volume: f64,
+    pub paused: bool,
+    pub muted: bool,
+
+    /// Last message for the status bar
+    pub status: Option<String>,
+}
+
+impl App {
+    pub fn new(stations: Vec<Station>) -> App {
+        App {
+            stations: Selectable::new(stations),
+            results: Selectable::new(Vec::new()),
+            query: String::new(),
+            focus: Focus::Stations,
+            now_playing: None,
+            title: None,
+            player: PlayerState::Stopped,
+            volume: 100.0,
+            paused: false,
+            muted: false,
+            status: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> Vec<Effect> {
+        if self.focus == Focus::Search {
+            return self.handle_search_key(key);
+        }
+
+        match key {
+            Key::Char('q') => vec![Effect::Quit],
+            Key::Up | Key::Char('k') => {
+                self.list_mut().up();
+                vec![]
+            }
+            Key::Down | Key::Char('j') => {
+                self.list_mut().down();
+                vec![]
+            }
+            Key::Tab => {
+                self.focus = match self.focus {
+                    Focus::Stations if !self.results.items.is_empty() => Focus::Results,
+                    _ => Focus::Stations,
+                };
+                vec![]
+            }
+            Key::Char('/') => {
+                self.focus = Focus::Search;
+                vec![]
+            }
+            Key::Enter => match self.list_mut().current().cloned() {
+                Some(station) => self.play(station),
+                None => vec![],
+            },
+            Key::Char('s') | Key::Esc if self.now_playing.is_some() => {
+                self.now_playing = None;
+                self.title = None;
+                self.player = PlayerState::Stopped;
+                vec![Effect::Stop]
+            }
+            Key::Char(' ') if self.now_playing.is_some() => {
+                self.paused = !self.paused;
+                vec![Effect::SetPause(self.paused)]
+            }
+            Key::Char('m') => {
+                self.muted = !self.muted;
+                vec![Effect::SetMute(self.muted)]
+            }
+            Key::Char('+') | Key::Char('=') | Key::Char('0') => self.change_volume(VOLUME_STEP),
+            Key::Char('-') | Key::Char('9') => self.change_volume(-VOLUME_STEP),
+            _ => vec![],
+        }
+    }
+
+    fn handle_search_key(&mut self, key: Key) -> Vec<Effect> {
+        match key {
+            Key::Esc => {
+                self.focus = Focus::Stations;
+                vec![]
+            }
+            Key::Enter if !self.query.trim().is_empty() => {
+                self.status = Some(format!("Searching \"{}\"...", self.query.trim()));
+                vec![Effect::Search(self.query.trim().to_string())]
+            }
+            Key::Backspace => {
+                self.query.pop();
+                vec![]
+            }
+            Key::Char(c) => {
+                self.query.push(c);
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::Started => {
+                self.player = PlayerState::Playing;
+                self.status = None;
+            }
+            Event::Stopped(error) => {
+                self.player = PlayerState::Stopped;
+                self.now_playing = None;
+                self.title = None;
+                self.status = error;
+            }
+            Event::Title(title) => self.title = title,
+            Event::SearchResults(results) => {
+                self.status = Some(format!("{} stations found", results.len()));
+                self.results = Selectable::new(results);
+                self.focus = if self.results.items.is_empty() {
+                    Focus::Search
+                } else {
+                    Focus::Results
+                };
+            }
+            Event::Error(e) => self.status = Some(e),
+        }
+    }
+
+    fn play(&mut self, station: Station) -> Vec<Effect> {
+        self.now_playing = Some(station.clone());
+        self.title = None;
+        self.paused = false;
+        self.player = PlayerState::Loading;
+        self.status = None;
+
+        vec![Effect::Play(station)]
+    }
+
+    fn change_volume(&mut self, delta: f64) -> Vec<Effect> {
+        self.volume = (self.volume + delta).clamp(0.0, 100.0);
+        vec![Effect::SetVolume(self.volume)]
+    }
+
+    fn list_mut(&mut self) -> &mut Selectable<Station> {
+        match self.focus {
+            Focus::Results => &mut self.results,
+            _ => &mut self.stations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(name: &str) -> Station {
+        Station {
+            station: name.to_string(),
+            url: format!("http://{}", name),
+            ..Default::default()
+        }
+    }
+
+    fn app() -> App {
+        App::new(vec![station("one"), station("two")])
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            assert!(app.handle_key(Key::Char(c)).is_empty());
+        }
+    }
+
+    #[test]
+    fn tab_only_moves_to_the_results_when_there_are_some() {
+        let mut app = app();
+
+        app.handle_key(Key::Tab);
+        assert_eq!(app.focus, Focus::Stations);
+
+        app.handle_event(Event::SearchResults(vec![station("three")]));
+        app.focus = Focus::Stations;
+        app.handle_key(Key::Tab);
+        assert_eq!(app.focus, Focus::Results);
+        app.handle_key(Key::Tab);
+        assert_eq!(app.focus, Focus::Stations);
+    }
+
+    #[test]
+    fn slash_searches_and_esc_goes_back() {
+        let mut app = app();
+
+        app.handle_key(Key::Char('/'));
+        assert_eq!(app.focus, Focus::Search);
+
+        // Keys are text while searching, even the ones that are commands
+        type_text(&mut app, "jazz q");
+        app.handle_key(Key::Backspace);
+        app.handle_key(Key::Backspace);
+        assert_eq!(app.query, "jazz");
+
+        app.handle_key(Key::Esc);
+        assert_eq!(app.focus, Focus::Stations);
+        assert_eq!(app.query, "jazz");
+    }
+
+    #[test]
+    fn enter_searches_the_query() {
+        let mut app = app();
+        app.handle_key(Key::Char('/'));
+
+        assert!(app.handle_key(Key::Enter).is_empty());
+
+        type_text(&mut app, " rock ");
+        assert_eq!(
+            app.handle_key(Key::Enter),
+            vec![Effect::Search(String::from("rock"))]
+        );
+    }
+
+    #[test]
+    fn enter_plays_the_selected_station() {
+        let mut app = app();
+        app.handle_key(Key::Down);
+        app.handle_key(Key::Down);
+
+        assert_eq!(
+            app.handle_key(Key::Enter),
+            vec![Effect::Play(station("two"))]
+        );
+        assert_eq!(app.now_playing, Some(station("two")));
+        assert_eq!(app.player, PlayerState::Loading);
+
+        app.handle_event(Event::Started);
+        assert_eq!(app.player, PlayerState::Playing);
+    }
+
+    #[test]
+    fn enter_plays_from_the_results_when_they_have_the_focus() {
+        let mut app = app();
+        app.handle_event(Event::SearchResults(vec![
+            station("three"),
+            station("four"),
+        ]));
+        app.handle_key(Key::Char('j'));
+
+        assert_eq!(
+            app.handle_key(Key::Enter),
+            vec![Effect::Play(station("four"))]
+        );
+        assert_eq!(app.stations.selected, 0);
+    }
+
+    #[test]
+    fn stops_only_what_plays() {
+        let mut app = app();
+        assert!(app.handle_key(Key::Char('s')).is_empty());
+        assert!(app.handle_key(Key::Char(' ')).is_empty());
+
+        app.handle_key(Key::Enter);
+        assert_eq!(app.handle_key(Key::Char(' ')), vec![Effect::SetPause(true)]);
+        assert_eq!(app.handle_key(Key::Esc), vec![Effect::Stop]);
+        assert_eq!(app.now_playing, None);
+        assert_eq!(app.player, PlayerState::Stopped);
+    }
+
+    #[test]
+    fn the_volume_stays_between_0_and_100() {
+        let mut app = app();
+
+        assert_eq!(
+            app.handle_key(Key::Char('+')),
+            vec![Effect::SetVolume(100.0)]
+        );
+        assert_eq!(
+            app.handle_key(Key::Char('-')),
+            vec![Effect::SetVolume(95.0)]
+        );
+
+        app.volume = 3.0;
+        assert_eq!(app.handle_key(Key::Char('9')), vec![Effect::SetVolume(0.0)]);
+        assert_eq!(app.handle_key(Key::Char('0')), vec![Effect::SetVolume(5.0)]);
+    }
+
+    #[test]
+    fn no_results_go_back_to_the_search() {
+        let mut app = app();
+        app.handle_key(Key::Char('/'));
+        type_text(&mut app, "nothing");
+        app.handle_key(Key::Enter);
+
+        app.handle_event(Event::SearchResults(Vec::new()));
+
+        assert_eq!(app.focus, Focus::Search);
+        assert_eq!(app.status.as_deref(), Some("0 stations found"));
+        assert!(app.results.current().is_none());
+    }
+
+    #[test]
+    fn results_take_the_focus() {
+        let mut app = app();
+        app.handle_key(Key::Char('/'));
+
+        app.handle_event(Event::SearchResults(vec![station("three")]));
+
+        assert_eq!(app.focus, Focus::Results);
+        assert_eq!(app.status.as_deref(), Some("1 stations found"));
+        assert_eq!(app.results.current(), Some(&station("three")));
+    }
+
+    #[test]
+    fn the_selection_stays_in_the_list() {
+        let mut app = app();
+
+        app.handle_key(Key::Up);
+        assert_eq!(app.stations.selected, 0);
+        for _ in 0..5 {
+            app.handle_key(Key::Down);
+        }
+        assert_eq!(app.stations.selected, 1);
+    }
+
+    #[test]
+    fn a_player_that_fails_says_why() {
+        let mut app = app();
+        app.handle_key(Key::Enter);
+        app.handle_event(Event::Title(Some(String::from("Song"))));
+
+        app.handle_event(Event::Stopped(Some(String::from("mpv exited"))));
+
+        assert_eq!(app.player, PlayerState::Stopped);
+        assert_eq!(app.title, None);
+        assert_eq!(app.status.as_deref(), Some("mpv exited"));
+    }
+}
@@ -26,7 +26,14 @@ impl Version {
     pub fn from(v: String) -> Option<Version> {
         let nums: Vec<&str> = v.split('.').collect();
 
-        if nums.len() < 3 {}
+        if nums.len() < 3 {
+            println!(
+                "{}: expected \"major.minor.patch\", got \"{}\"",
+                "Version error".bright_red(),
+                v
+            );
+            return None;
+        }
 
         let major = match nums[0].parse::<u32>() {
             Ok(n) => n,
@@ -0,0 +1,332 @@
+//! Just enough XML for the listings of station directories: elements, attributes, text,
+//! comments, CDATA and the usual entities. No namespaces, no DTDs.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// All the text directly inside the element
+    pub text: String,
+}
+
+/// XML that can't be read, e.g. a tag that is never closed
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlError(pub String);
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+impl Element {
+    /// The value of an attribute, whatever its case (OPML has both `url` and `URL`)
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// The trimmed text of a child, if it has some
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name)
+            .map(|c| c.text.trim())
+            .filter(|t| !t.is_empty())
+    }
+
+    /// This element and all the ones inside it, in document order
+    pub fn descendants(&self) -> Vec<&Element> {
+        let mut all = vec![self];
+        for child in &self.children {
+            all.extend(child.descendants());
+        }
+        all
+    }
+}
+
+/// The root element of a document
+pub fn parse(input: &str) -> Result<Element, XmlError> {
+    // The root goes into a dummy element, so there's always a parent
+    let mut stack: Vec<Element> = vec![Element::default()];
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut stack, rest);
+            break;
+        };
+        push_text(&mut stack, &rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = skip_past(after, "-->")?;
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after
+                .find("]]>")
+                .ok_or_else(|| XmlError(String::from("Unclosed CDATA")))?;
+            if let Some(current) = stack.last_mut() {
+                current.text.push_str(&after[..end]);
+            }
+            rest = &after[end + 3..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = skip_past(rest, ">")?;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after
+                .find('>')
+                .ok_or_else(|| XmlError(String::from("Unclosed end tag")))?;
+            close(&mut stack, after[..end].trim())?;
+            rest = &after[end + 1..];
+        } else {
+            let end = tag_end(rest).ok_or_else(|| XmlError(String::from("Unclosed tag")))?;
+            let tag = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(t) => (t, true),
+                None => (tag, false),
+            };
+            let element = read_tag(tag)?;
+
+            match empty {
+                true => stack.last_mut().unwrap().children.push(element),
+                false => stack.push(element),
+            }
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err(XmlError(format!(
+            "<{}> is never closed",
+            stack.last().unwrap().name
+        )));
+    }
+
+    stack
+        .pop()
+        .and_then(|root| root.children.into_iter().next())
+        .ok_or_else(|| XmlError(String::from("There is no root element")))
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if let Some(current) = stack.last_mut() {
+        current.text.push_str(&unescape(text));
+    }
+}
+
+/// What follows the first `end` in `s`
+fn skip_past<'a>(s: &'a str, end: &str) -> Result<&'a str, XmlError> {
+    match s.find(end) {
+        Some(i) => Ok(&s[i + end.len()..]),
+        None => Err(XmlError(format!("Missing {}", end))),
+    }
+}
+
+/// Position of the `>` closing the tag at the start of `s`, skipping the ones in quotes
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+
+    for (i, c) in s.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('>', None) => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Ends the element called `name`, and any left open inside it
+fn close(stack: &mut Vec<Element>, name: &str) -> Result<(), XmlError> {
+    if !stack[1..].iter().any(|e| e.name == name) {
+        return Err(XmlError(format!("</{}> closes nothing", name)));
+    }
+
+    while let Some(element) = stack.pop() {
+        let done = element.name == name;
+        stack.last_mut().unwrap().children.push(element);
+        if done {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// The name and attributes of a start tag, without the `<>`
+fn read_tag(tag: &str) -> Result<Element, XmlError> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut element = Element {
+        name: tag[..name_end].to_string(),
+        ..Default::default()
+    };
+    if element.name.is_empty() {
+        return Err(XmlError(String::from("A tag has no name")));
+    }
+
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let Some(eq) = rest.find('=') else {
+            // An attribute without a value, as HTML allows
+            break;
+        };
+        let name = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim_start();
+
+        let (value, after) = match value.chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let end = value[1..]
+                    .find(q)
+                    .ok_or_else(|| XmlError(format!("Unclosed value of {}", name)))?;
+                (&value[1..end + 1], &value[end + 2..])
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+
+        element.attributes.push((name, unescape(value)));
+        rest = after.trim_start();
+    }
+
+    Ok(element)
+}
+
+/// Replaces the entities (`&amp;`, `&#233;`...)
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(e) if e.starts_with("#x") || e.starts_with("#X") => {
+                u32::from_str_radix(&e[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            Some(e) if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+
+        match (c, entity) {
+            (Some(c), Some(e)) => {
+                out.push(c);
+                rest = &rest[e.len() + 2..];
+            }
+            // A lone `&`, kept as it is
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(input: &str) -> String {
+        parse(input).unwrap_err().0
+    }
+
+    #[test]
+    fn reads_elements_attributes_and_text() {
+        let root = parse(
+            r#"<?xml version="1.0"?>
+            <!DOCTYPE opml>
+            <list kind="radio">
+              <!-- <station name="commented out"/> -->
+              <station name="One" url='http://one.example/?a=1&amp;b=2' br=128/>
+              <station name="Two &gt; One"><title> Second </title><empty/></station>
+            </list>"#,
+        )
+        .unwrap();
+
+        assert_eq!(root.name, "list");
+        assert_eq!(root.attr("KIND"), Some("radio"));
+        assert_eq!(root.children.len(), 2);
+
+        let one = &root.children[0];
+        assert_eq!(one.attr("url"), Some("http://one.example/?a=1&b=2"));
+        assert_eq!(one.attr("br"), Some("128"));
+        assert_eq!(one.attr("missing"), None);
+
+        let two = &root.children[1];
+        assert_eq!(two.attr("name"), Some("Two > One"));
+        assert_eq!(two.child_text("title"), Some("Second"));
+        assert_eq!(two.child_text("empty"), None);
+        assert!(two.child("missing").is_none());
+
+        let names: Vec<&str> = root.descendants().iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["list", "station", "station", "title", "empty"]);
+    }
+
+    #[test]
+    fn replaces_entities_and_keeps_cdata() {
+        let root = parse(
+            "<t>&lt;b&gt; &quot;x&apos; &#233;&#xE9; AT&T &bogus; <![CDATA[a &amp; <b>]]></t>",
+        )
+        .unwrap();
+        assert_eq!(root.text, "<b> \"x' éé AT&T &bogus; a &amp; <b>");
+    }
+
+    #[test]
+    fn skips_quoted_brackets_in_tags() {
+        let root = parse(r#"<a title="1 > 0" other='<b>'>text</a>"#).unwrap();
+        assert_eq!(root.attr("title"), Some("1 > 0"));
+        assert_eq!(root.attr("other"), Some("<b>"));
+        assert_eq!(root.text, "text");
+    }
+
+    #[test]
+    fn closes_what_was_left_open_inside() {
+        // As HTML does with <br>
+        let root = parse("<a><b><br></b><c/></a>").unwrap();
+        assert_eq!(root.children[0].name, "b");
+        assert_eq!(root.children[0].children[0].name, "br");
+        assert_eq!(root.children[1].name, "c");
+    }
+
+    #[test]
+    fn explains_what_is_wrong() {
+        assert_eq!(error("<a><b></b>"), "<a> is never closed");
+        assert_eq!(error("<a></b></a>"), "</b> closes nothing");
+        assert_eq!(error("just text"), "There is no root element");
+        assert_eq!(error(""), "There is no root element");
+        assert_eq!(error("<a"), "Unclosed tag");
+        assert_eq!(error("<a></a"), "Unclosed end tag");
+        assert_eq!(error("< >"), "A tag has no name");
+        assert_eq!(error("<a b='1>"), "Unclosed tag");
+        assert_eq!(error("<a><!-- never ends</a>"), "Missing -->");
+        assert_eq!(error("<a><![CDATA[</a>"), "Unclosed CDATA");
+    }
+}
@@ -3,13 +3,36 @@ use colored::*;
 use inquire::{InquireError, Select};
 use log::{debug, error, info, log_enabled, warn};
 use radio_libs::{
-    Cli, Config, ConfigError, Station, Version,
-    browser::{Browser, StationCache},
+    AlarmArgs, BrowseArgs, Cli, Commands, Config, ConfigError, CtlArgs, CtlCommand, DaemonArgs,
+    DevicesArgs, PodcastArgs, RecordArgs, RelayArgs, SchedulerArgs, ServeArgs, Station, Version,
+    VoteArgs,
+    alarm::{AlarmTime, Clock, SystemClock, VolumeRamp, wait_until},
+    api::Api,
+    browser::{Category, Directory, StationCache},
+    controls::{self, Control, Keyboard, VOLUME_STEP},
+    country::{CountryChoice, find_countries},
+    daemon::{self, Daemon, Request, default_socket_path},
+    directory,
+    icy::{TitleLog, TitleWatcher},
     perror,
+    player::{Mpv, Player, PlayerOptions},
+    podcast::{self, Episode, Podcast},
+    record::RecordOptions,
+    scheduler::{Scheduler, default_summary_path},
+    state::State,
+    station_near,
+    timer::{SleepState, SleepTimer, format_duration},
+    tui,
 };
-use std::io::Write;
-use std::process::{Command, Stdio};
+use serde_json::Value;
+use std::io::{ErrorKind, IsTerminal};
+use std::net::TcpListener;
+use std::process::ExitStatus;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How often the player is checked while playing
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
 
 fn main() {
     let version = match Version::from(String::from(env!("CARGO_PKG_VERSION"))) {
@@ -26,16 +49,9 @@ fn main() {
         .filter_level(args.verbose.log_level_filter())
         .init();
 
-    if args.list_countries {
-        if let Ok(countries) = Browser::get_countries() {
-            for country in countries {
-                println!("{}: \"{}\"", country.name, country.iso_3166_1.bold());
-            }
-        } else {
-            error!("Could not connect to the server, please check your connection.");
-        }
-
-        std::process::exit(0);
+    // The client doesn't need the config
+    if let Some(Commands::Ctl(ctl)) = &args.command {
+        run_ctl(ctl.clone());
     }
 
     // Parse the config file
@@ -44,12 +60,31 @@ fn main() {
         Some(x) => Config::load_from_file(x),
     };
 
-    let config = match config_result {
+    let mut config = match config_result {
         Ok(mut x) => {
             if let Some(cc) = args.country_code {
                 x.country_code = Some(cc);
             }
 
+            if let Some(order) = args.order {
+                x.search.order = order;
+            }
+            if args.reverse {
+                x.search.reverse = true;
+            }
+            if let Some(limit) = args.limit {
+                x.search.limit = limit;
+            }
+            if let Some(offset) = args.offset {
+                x.search.offset = offset;
+            }
+            if let Some(hidebroken) = args.hidebroken {
+                x.search.hidebroken = hidebroken;
+            }
+            if !args.api_server.is_empty() {
+                x.api_servers = args.api_server.clone();
+            }
+
             x
         }
         Err(error) => {
@@ -67,6 +102,37 @@ fn main() {
             std::process::exit(1);
         }
     };
+
+    if args.list_countries {
+        if let Ok(countries) = directory::connect(&config).and_then(|d| d.countries()) {
+            for country in countries {
+                println!(
+                    "{}: \"{}\" {}",
+                    country.name,
+                    country.iso_3166_1.bold(),
+                    format!("({} stations)", country.stationcount).bright_black()
+                );
+            }
+        } else {
+            error!("Could not connect to the server, please check your connection.");
+        }
+
+        std::process::exit(0);
+    }
+
+    if let Some(name) = &args.country {
+        config.country_code = Some(find_country_code(&config, name));
+    } else if config.country_code.is_none()
+        // Only when the stations of Radio Browser are about to be listed
+        && args.command.is_none()
+        && args.url.is_none()
+        && args.station.is_none()
+        && !args.tui
+        && std::io::stdin().is_terminal()
+    {
+        pick_country(&mut config);
+    }
+
     let config = Rc::new(config);
 
     debug!(
@@ -87,6 +153,7 @@ fn main() {
     }
 
     if config.country_code.is_none() {
+        // Only when the country could not be asked for
         warn!("\n{} {}", "Warning!".yellow().bold(), 
 		"The config does not contain a valid country (for example, \"ES\" for Spain or \"US\" for the US).".italic());
         info!(
@@ -102,14 +169,134 @@ fn main() {
         );
     }
 
+    let state = State::load();
+    let volume = args
+        .volume
+        .or(state.volume.filter(|_| config.remember_volume))
+        .or(config.volume)
+        .unwrap_or(100.0);
+
+    let mut audio_filters = Vec::new();
+    if args.normalize || config.normalize {
+        audio_filters.push(String::from("loudnorm"));
+    }
+
+    if let Some(preset) = &args.preset
+        && !config.has_preset(preset)
+    {
+        error!(
+            "Unknown preset \"{}\", the presets are: {}",
+            preset,
+            config.preset_names().join(", ")
+        );
+        std::process::exit(1);
+    }
+
+    let options = PlayerOptions {
+        show_video: args.show_video,
+        volume: Some(volume),
+        audio_filters,
+        presets: config.presets(),
+        preset: args.preset,
+        audio_device: args.audio_device,
+        default_audio_device: config.audio_device.clone(),
+        ..Default::default()
+    };
+
+    let mut browse_args: Option<BrowseArgs> = None;
+    match args.command {
+        Some(Commands::Alarm(alarm)) => run_alarm(alarm, config, &options),
+        Some(Commands::Record(record)) => run_record(record, config, &options),
+        Some(Commands::Scheduler(scheduler)) => run_scheduler(scheduler, config),
+        Some(Commands::Daemon(daemon)) => run_daemon(daemon, config, &options),
+        Some(Commands::Serve(serve)) => run_serve(serve, config, &options),
+        Some(Commands::Relay(relay)) => run_relay(relay, config, &options),
+        Some(Commands::Devices(devices)) => run_devices(devices, config),
+        Some(Commands::Browse(browse)) if browse.list => list_groups(browse.category, config),
+        Some(Commands::Browse(browse)) => browse_args = Some(browse),
+        Some(Commands::Vote(vote)) => run_vote(vote, config),
+        Some(Commands::Podcast(podcast)) => run_podcast(podcast, config, &options, state),
+        Some(Commands::Ctl(_)) | None => {}
+    }
+
+    if args.tui {
+        // Only kept up to date when it's going to be used
+        let state = config.remember_volume.then_some(state);
+        if let Err(e) = tui::run(config, &options, state) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+
+        std::process::exit(0);
+    }
+
+    let sleep_timer = match (args.sleep, args.until) {
+        (Some(d), _) => Some(SleepTimer::new(d, args.fade)),
+        (None, Some(t)) => Some(SleepTimer::until(t, args.fade)),
+        (None, None) => None,
+    };
+    let sleep_timer = match sleep_timer.transpose() {
+        Ok(t) => t,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut session = Session {
+        directory: Directory::new(config.clone()),
+        config,
+        volume,
+        state,
+        sleep_timer,
+        fade: args.fade,
+        title_log: args.log_titles.map(TitleLog::new),
+        resume: false,
+        playlist: None,
+    };
+
+    if let Some(timer) = &session.sleep_timer {
+        info!(
+            "Sleep timer set, playback will stop in {}",
+            format_duration(timer.remaining(Instant::now()))
+        );
+    }
+
     let mut url = args.url;
     let mut station_arg = args.station;
     let mut cached_stations = None;
+    // Station chosen with the next/previous keys, or by browsing
+    let mut next: Option<Station> = browse_args.map(|b| {
+        match browse(
+            session.config.clone(),
+            &session.directory,
+            Some(b.category),
+            b.name,
+        ) {
+            Ok(s) => s,
+            Err(error) => {
+                println!("\n\t{}", "Bye!".bold().green());
+                info!("({:?})", error);
+                std::process::exit(0);
+            }
+        }
+    });
     loop {
-        let station = match url {
-            None => {
-                let (station, internet, updated_cached_stations) =
-                    get_station(station_arg, config.clone(), cached_stations.clone());
+        let station = match (next.take(), url) {
+            (Some(s), _) => {
+                println!("Playing {}", s.station.green());
+                print!("\x1B]0;Now playing: {}\x07", s.station);
+
+                s
+            }
+
+            (None, None) => {
+                let (station, internet, updated_cached_stations) = get_station(
+                    station_arg,
+                    session.config.clone(),
+                    &session.directory,
+                    cached_stations.clone(),
+                );
                 if !args.no_station_cache {
                     cached_stations = updated_cached_stations;
                 }
@@ -126,12 +313,13 @@ fn main() {
                 station
             }
 
-            Some(x) => {
+            (None, Some(x)) => {
                 println!("Playing url '{}'", x.blue());
 
                 Station {
                     station: String::from("URL"),
                     url: x,
+                    ..Default::default()
                 }
             }
         };
@@ -140,62 +328,1128 @@ fn main() {
         url = None;
         station_arg = None;
 
+        println!("{}", controls::HELP.italic().bright_black());
+
+        match play(station, &options, &mut session, None) {
+            Playback::Finished(status) => {
+                if !status.success() {
+                    mpv_failed(format!("mpv {}", status).as_str());
+                }
+            }
+            Playback::Menu => {}
+            Playback::Switch(s) => next = Some(s),
+            Playback::Slept => {
+                println!("\n\t{}", "Good night!".bold().green());
+                std::process::exit(0);
+            }
+            Playback::Quit => {
+                println!("\n\t{}", "Bye!".bold().green());
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+/// Code of the country whose name looks like `name`, asking which one if several do
+fn find_country_code(config: &Config, name: &str) -> String {
+    let max_lines = config.max_lines;
+    let countries = match directory::connect(config).and_then(|d| d.countries()) {
+        Ok(c) => c,
+        Err(e) => {
+            debug!("{}", e);
+            error!("Could not connect to the server, please check your connection.");
+            std::process::exit(1);
+        }
+    };
+
+    let found = find_countries(&countries, name);
+    let choices: Vec<CountryChoice> = match found.as_slice() {
+        [] => {
+            error!(
+                "There is no country like \"{}\", try --list-countries",
+                name
+            );
+            std::process::exit(1);
+        }
+        [country] => {
+            info!("Searching the stations of {}", country.name);
+            return country.iso_3166_1.clone();
+        }
+        _ => found
+            .into_iter()
+            .map(|c| CountryChoice(Some(c.clone())))
+            .collect(),
+    };
+
+    let best = choices[0].clone();
+    let prompt = format!("Which country do you mean by \"{}\"?", name);
+    let choice = Select::new(&prompt.bold(), choices)
+        .with_page_size(max_lines.unwrap_or(Select::<CountryChoice>::DEFAULT_PAGE_SIZE))
+        .prompt();
+
+    match choice {
+        Ok(CountryChoice(Some(c))) => c.iso_3166_1,
+        // Nobody to ask
+        Err(InquireError::NotTTY) => match best.0 {
+            Some(c) => {
+                info!("Searching the stations of {}", c.name);
+                c.iso_3166_1
+            }
+            None => std::process::exit(1),
+        },
+        _ => std::process::exit(0),
+    }
+}
+
+/// Asks for the country to search stations in and saves it in the config
+fn pick_country(config: &mut Config) {
+    let mut countries = match directory::connect(config).and_then(|d| d.countries()) {
+        Ok(c) => c,
+        Err(e) => {
+            debug!("Could not get the countries: {}", e);
+            return;
+        }
+    };
+    countries.sort_by_key(|c| std::cmp::Reverse(c.stationcount));
+
+    let mut choices = vec![CountryChoice(None)];
+    choices.extend(countries.into_iter().map(|c| CountryChoice(Some(c))));
+
+    let choice = Select::new(
+        &"Choose your country, to search its stations (type to filter):".bold(),
+        choices,
+    )
+    .with_page_size(
+        config
+            .max_lines
+            .unwrap_or(Select::<CountryChoice>::DEFAULT_PAGE_SIZE),
+    )
+    .prompt();
+
+    let code = match choice {
+        Ok(CountryChoice(c)) => c.map(|c| c.iso_3166_1).unwrap_or_default(),
+        Err(_) => return,
+    };
+
+    match config.set_country(&code) {
+        Ok(()) => println!("{}", "Saved in the config, edit it to change it.".italic()),
+        Err(e) => {
+            debug!("{:?}", e);
+            warn!("{}", e);
+        }
+    }
+    config.country_code = Some(code);
+}
+
+fn mpv_failed(msg: &str) -> ! {
+    perror(msg);
+
+    if !log_enabled!(log::Level::Info) {
         println!(
-            "{}",
-            "Info: press 'q' to stop playing this station"
-                .italic()
-                .bright_black()
+            "{}: {}",
+            "Hint".italic().bold(),
+            "Try running radio-cli with the verbose flag (-vv or -vvv)".italic()
         );
+    }
 
-        let output_status = run_mpv(station, args.show_video);
-        if !output_status.success() {
-            perror(format!("mpv {}", output_status).as_str());
+    std::process::exit(2);
+}
 
-            if !log_enabled!(log::Level::Info) {
-                println!(
-                    "{}: {}",
-                    "Hint".italic().bold(),
-                    "Try running radio-cli with the verbose flag (-vv or -vvv)".italic()
+/// Votes for a station of the config (or asked for) in Radio Browser
+fn run_vote(vote: VoteArgs, config: Rc<Config>) -> ! {
+    let directory = Directory::new(config.clone());
+    let (mut station, _, _) = get_station(vote.station, config, &directory, None);
+
+    if station.is_local() {
+        error!("{} is on the disk, not in Radio Browser", station.station);
+        std::process::exit(1);
+    }
+
+    // Stations of the config saved before their id was kept, the user picks which one it is
+    if station.uuid.is_none() {
+        let found = directory
+            .browser(Some(StationCache::default()))
+            .map_err(|e| e.to_string())
+            .and_then(|(b, _)| {
+                b.choose_station(station.station.clone())
+                    .map_err(|e| e.to_string())
+            });
+
+        match found {
+            Ok(s) => station.uuid = s.uuid,
+            Err(e) => {
+                debug!("{}", e);
+                error!("Could not find {} in Radio Browser", station.station);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match directory.vote(&station) {
+        Ok(message) => {
+            println!(
+                "{} {} ({})",
+                "Voted for".green(),
+                station.station.bold(),
+                message
+            );
+            std::process::exit(0);
+        }
+        Err(e) => {
+            error!("Could not vote for {}: {}", station.station, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Lists the episodes of a podcast, or plays one from where it was stopped last time
+fn run_podcast(args: PodcastArgs, config: Rc<Config>, options: &PlayerOptions, state: State) -> ! {
+    let max_lines = config
+        .max_lines
+        .unwrap_or(Select::<Podcast>::DEFAULT_PAGE_SIZE);
+
+    let podcast = match (&args.name, config.podcasts.as_slice()) {
+        (_, []) => {
+            error!("There are no podcasts in your config");
+            std::process::exit(1);
+        }
+        (Some(name), podcasts) => match config.get_podcast(name) {
+            Some(p) => p.clone(),
+            None => {
+                let names: Vec<&str> = podcasts.iter().map(|p| p.name.as_str()).collect();
+                error!(
+                    "There is no podcast called \"{}\" in your config, the podcasts are: {}",
+                    name,
+                    names.join(", ")
                 );
+                std::process::exit(1);
             }
+        },
+        (None, [podcast]) => podcast.clone(),
+        (None, podcasts) => match Select::new(&"Choose a podcast:".bold(), podcasts.to_vec())
+            .with_page_size(max_lines)
+            .prompt()
+        {
+            Ok(p) => p,
+            Err(_) => {
+                println!("\n\t{}", "Bye!".bold().green());
+                std::process::exit(0);
+            }
+        },
+    };
 
-            std::process::exit(2);
+    let episodes = match podcast::fetch(&config, &podcast) {
+        Ok(e) if e.is_empty() => {
+            error!("{} has no episodes to play", podcast.name);
+            std::process::exit(1);
+        }
+        Ok(e) => e,
+        Err(e) => {
+            error!("Could not read the feed of {}: {}", podcast.name, e);
+            std::process::exit(1);
+        }
+    };
+
+    // The episodes with where they were stopped, if they were
+    let label = |episode: &Episode| match state
+        .position(&episode.url)
+        .and_then(|p| Duration::try_from_secs_f64(p).ok())
+    {
+        Some(p) => format!(
+            "{} {}",
+            episode,
+            format!("[stopped at {}]", format_duration(p)).bright_black()
+        ),
+        None => episode.to_string(),
+    };
+
+    if args.list {
+        for (i, episode) in episodes.iter().enumerate() {
+            println!("{:>3}. {}", i + 1, label(episode));
+        }
+        std::process::exit(0);
+    }
+
+    let mut chosen = match (args.latest, args.episode) {
+        (true, _) => Some(0),
+        (false, Some(n)) if (1..=episodes.len()).contains(&n) => Some(n - 1),
+        (false, Some(n)) => {
+            error!(
+                "There is no episode {}, {} has {}",
+                n,
+                podcast.name,
+                episodes.len()
+            );
+            std::process::exit(1);
+        }
+        (false, None) => None,
+    };
+    let labels: Vec<String> = episodes.iter().map(label).collect();
+
+    let directory = Directory::new(config.clone());
+    let mut session = Session::new(config.clone(), options.volume.unwrap_or(100.0), directory);
+    session.state = state;
+
+    // The next/previous keys go through the episodes, each resumed where it was stopped
+    session.playlist = Some(episodes.iter().map(|e| e.station(&podcast)).collect());
+    session.resume = true;
+
+    // Episode chosen with the next/previous keys
+    let mut next: Option<usize> = None;
+    loop {
+        let given = chosen.is_some();
+        let i = match next.take().or(chosen.take()) {
+            Some(i) => i,
+            None => match Select::new(&"Choose an episode:".bold(), labels.clone())
+                .with_page_size(max_lines)
+                .raw_prompt()
+            {
+                Ok(choice) => choice.index,
+                Err(_) => {
+                    println!("\n\t{}", "Bye!".bold().green());
+                    std::process::exit(0);
+                }
+            },
+        };
+
+        let episode = &episodes[i];
+        let station = episode.station(&podcast);
+        let start = session
+            .state
+            .position(&episode.url)
+            .and_then(|p| Duration::try_from_secs_f64(p).ok());
+
+        println!("Playing {}", station.station.green());
+        print!("\x1B]0;Now playing: {}\x07", station.station);
+        if let Some(p) = start {
+            println!(
+                "{}",
+                format!("Resuming at {}", format_duration(p))
+                    .italic()
+                    .bright_black()
+            );
+        }
+        println!("{}", controls::HELP.italic().bright_black());
+
+        let options = PlayerOptions {
+            start: start.map(|p| p.as_secs_f64()),
+            ..options.clone()
+        };
+        match play(station, &options, &mut session, None) {
+            Playback::Finished(status) => {
+                if !status.success() {
+                    mpv_failed(format!("mpv {}", status).as_str());
+                }
+
+                // Only the episode that was asked for
+                if given {
+                    std::process::exit(0);
+                }
+            }
+            Playback::Menu => {}
+            Playback::Switch(s) => next = episodes.iter().position(|e| e.url == s.url),
+            Playback::Slept => {
+                println!("\n\t{}", "Good night!".bold().green());
+                std::process::exit(0);
+            }
+            Playback::Quit => {
+                println!("\n\t{}", "Bye!".bold().green());
+                std::process::exit(0);
+            }
         }
     }
 }
 
-fn run_mpv(station: Station, show_video: bool) -> std::process::ExitStatus {
-    let mut mpv = Command::new("mpv");
-    let mut mpv_args: Vec<String> = [station.url].to_vec();
+/// Lists the audio outputs, or lets the user pick one and saves it in the config
+fn run_devices(devices: DevicesArgs, config: Rc<Config>) -> ! {
+    let station = devices
+        .station
+        .as_deref()
+        .map(|name| match config.get_station(name) {
+            Some(s) => s,
+            None => {
+                error!("There is no station \"{}\" in the config", name);
+                std::process::exit(1);
+            }
+        });
+
+    let list = match Mpv::audio_devices() {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Could not list the audio outputs: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if !show_video {
-        mpv_args.push(String::from("--no-video"));
+    let current = station
+        .and_then(|s| s.audio_device)
+        .or(config.audio_device.clone());
+
+    if !devices.pick {
+        for device in list {
+            let marker = if current.as_ref() == Some(&device.name) {
+                "*".green().bold()
+            } else {
+                " ".normal()
+            };
+
+            println!(
+                "{} {}: {}",
+                marker,
+                device.name.bold(),
+                device.description.italic()
+            );
+        }
+
+        std::process::exit(0);
     }
 
-    if !log_enabled!(log::Level::Info) {
-        mpv_args.push(String::from("--really-quiet"));
+    let prompt = match &devices.station {
+        Some(s) => format!("Select the audio output of {}:", s),
+        None => String::from("Select the audio output:"),
+    };
+
+    let device = match Select::new(&prompt.bold(), list).prompt() {
+        Ok(d) => d,
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => {
+            std::process::exit(0)
+        }
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // mpv's own default doesn't need to be written down
+    let name = Some(device.name.as_str()).filter(|n| *n != "auto");
+
+    match config.set_audio_device(devices.station.as_deref(), name) {
+        Ok(()) => {
+            println!("Playing on {} from now on", device.description.green());
+            std::process::exit(0);
+        }
+        Err(e) => {
+            debug!("{:?}", e);
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Presets are mpv filters, so they can't change what doesn't go through mpv
+fn unsupported_preset(options: &PlayerOptions, station: &Station, what: &str) {
+    if let Some(preset) = options.preset_for(station) {
+        println!(
+            "{} {}",
+            "Warning!".yellow().bold(),
+            format!(
+                "{} can't use audio filters, the preset \"{}\" is ignored.",
+                what, preset
+            )
+            .italic()
+        );
+    }
+}
+
+/// Waits for the alarm (or each of the scheduled alarms) and plays the station when it rings.
+fn run_alarm(alarm: AlarmArgs, config: Rc<Config>, options: &PlayerOptions) -> ! {
+    let clock = SystemClock;
+    let next_alarm = |now| match alarm.time {
+        Some(t) => AlarmTime::new(t).next_after(now),
+        None => config.alarm.next_after(now),
+    };
+
+    if next_alarm(clock.now()).is_none() {
+        error!("No alarm time was given and there is no alarm schedule in the config");
+        std::process::exit(1);
     }
 
-    let output = mpv
-        .args(mpv_args)
-        .stdin(Stdio::inherit())
-        .stdout(Stdio::inherit())
-        .output()
-        .expect("Failed to execute mpv. Is it installed?");
+    // Choose the station now, so nobody has to be awake to pick it
+    let station_arg = alarm.station.clone().or(config.alarm.station.clone());
+    let directory = Directory::new(config.clone());
+    let (station, _, _) = get_station(station_arg, config.clone(), &directory, None);
+
+    let fallback = alarm.fallback.clone().or(config.alarm.fallback.clone());
+    let volume = options.volume.unwrap_or(100.0);
+    let ramp = alarm
+        .ramp
+        .or(config.alarm.ramp)
+        .map(|d| VolumeRamp::new(d, station.adjusted_volume(volume)));
+
+    while let Some(next) = next_alarm(clock.now()) {
+        println!(
+            "Alarm set for {} with {}",
+            next.format("%A %H:%M").to_string().bold(),
+            station.station.green()
+        );
+        wait_until(&clock, next);
+
+        println!("{}", "Good morning!".bold().green());
+
+        let mut session = Session::new(config.clone(), volume, directory.clone());
+        let mut playing = station.clone();
+        let status = loop {
+            match play(playing, options, &mut session, ramp) {
+                Playback::Finished(status) => break status,
+                Playback::Switch(s) => playing = s,
+                Playback::Slept | Playback::Menu => break ExitStatus::default(),
+                Playback::Quit => std::process::exit(0),
+            }
+        };
+
+        if !status.success() {
+            match &fallback {
+                Some(file) => {
+                    warn!(
+                        "Could not play {}, playing the fallback file",
+                        station.station
+                    );
+
+                    let fallback_station = Station {
+                        station: String::from("Alarm fallback"),
+                        url: file.display().to_string(),
+                        ..Default::default()
+                    };
+                    let fallback_options = PlayerOptions {
+                        loop_file: true,
+                        ..options.clone()
+                    };
+
+                    if let Playback::Quit =
+                        play(fallback_station, &fallback_options, &mut session, ramp)
+                    {
+                        std::process::exit(0);
+                    }
+                }
+                None => perror(format!("mpv {}", status).as_str()),
+            }
+        }
+
+        // A one-off alarm only rings once
+        if alarm.time.is_some() {
+            break;
+        }
+    }
+
+    std::process::exit(0);
+}
+
+/// Records a station to disk, optionally playing it at the same time.
+fn run_record(record: RecordArgs, config: Rc<Config>, options: &PlayerOptions) -> ! {
+    let station = match record.url {
+        Some(url) => Station {
+            station: String::from("URL"),
+            url,
+            ..Default::default()
+        },
+        None => {
+            get_station(
+                record.station,
+                config.clone(),
+                &Directory::new(config),
+                None,
+            )
+            .0
+        }
+    };
+
+    let record_options = RecordOptions {
+        duration: record.duration,
+        output: record.output,
+        split: record.split,
+        add_extension: false,
+    };
+
+    let options = PlayerOptions {
+        volume: options.volume.map(|v| station.adjusted_volume(v)),
+        ..options.clone()
+    };
+
+    if !record.play {
+        unsupported_preset(&options, &station, "Recordings");
+    }
 
-    if !output.status.success() {
-        eprintln!("mpv error: {:?}", output.status);
-        std::io::stderr().write_all(&output.stderr).unwrap();
+    // Dropping it at the end of the recording stops it
+    let _player = if record.play {
+        match Mpv::spawn(&station, &options) {
+            Ok(m) => Some(m),
+            Err(e) => {
+                warn!("Could not play the station while recording: {}", e);
+                None
+            }
+        }
     } else {
-        std::io::stdout().write_all(&output.stdout).unwrap();
+        None
+    };
+
+    match record.duration {
+        Some(d) => println!(
+            "Recording {} for {}",
+            station.station.green(),
+            format_duration(d)
+        ),
+        None => println!(
+            "Recording {} {}",
+            station.station.green(),
+            "(press Ctrl+C to stop)".italic().bright_black()
+        ),
+    }
+
+    match radio_libs::record::record(&station, &record_options) {
+        Ok(recording) => {
+            for file in recording.files {
+                println!("Saved {}", file.display().to_string().bold());
+            }
+
+            if recording.reconnects > 0 {
+                info!("Reconnected {} times", recording.reconnects);
+            }
+
+            std::process::exit(0);
+        }
+        Err(e) => {
+            error!("Could not record {}: {}", station.station, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Serves a station to the players of the network.
+fn run_relay(relay: RelayArgs, config: Rc<Config>, options: &PlayerOptions) -> ! {
+    let station = match relay.url {
+        Some(url) => Station {
+            station: String::from("URL"),
+            url,
+            ..Default::default()
+        },
+        None => get_station(relay.station, config.clone(), &Directory::new(config), None).0,
+    };
+
+    unsupported_preset(options, &station, "The relay");
+
+    let listener = match TcpListener::bind(relay.bind) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Could not listen on {}: {}", relay.bind, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Relaying {} on {} {}",
+        station.station.green(),
+        format!("http://{}", relay.bind).bold(),
+        "(press Ctrl+C to stop)".italic().bright_black()
+    );
+
+    if let Err(e) = radio_libs::relay::relay(&station, listener) {
+        error!("Could not relay {}: {}", station.station, e);
+        std::process::exit(1);
+    }
+
+    std::process::exit(0);
+}
+
+/// Performs the scheduled recordings of the config until stopped.
+fn run_scheduler(args: SchedulerArgs, config: Rc<Config>) -> ! {
+    if config.schedule.is_empty() {
+        error!("There are no recordings in the schedule of the config");
+        std::process::exit(1);
+    }
+
+    let summary = args.summary.or_else(default_summary_path);
+    let scheduler = Scheduler::new(SystemClock, config, summary.clone());
+
+    if scheduler.is_empty() {
+        error!("None of the scheduled recordings can be performed");
+        std::process::exit(1);
+    }
+
+    for (start, recording) in scheduler.upcoming() {
+        println!(
+            "{} {} ({}, {})",
+            start.format("%a %H:%M").to_string().bold(),
+            recording.name().green(),
+            recording.station,
+            format_duration(recording.duration)
+        );
+    }
+
+    if args.list {
+        std::process::exit(0);
+    }
+
+    if let Some(path) = summary {
+        info!("Writing the summary to {}", path.display());
+    }
+
+    println!(
+        "{}",
+        "Waiting for the scheduled recordings (press Ctrl+C to stop)"
+            .italic()
+            .bright_black()
+    );
+
+    scheduler.run()
+}
+
+/// Plays stations in the background as the clients of the socket say.
+fn run_daemon(args: DaemonArgs, config: Rc<Config>, options: &PlayerOptions) -> ! {
+    let socket = args.socket.unwrap_or_else(default_socket_path);
+    let listener = match daemon::bind(&socket) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Could not listen on {}: {}", socket.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Listening on {} {}",
+        socket.display().to_string().bold(),
+        "(press Ctrl+C to stop)".italic().bright_black()
+    );
+
+    let options = PlayerOptions {
+        no_terminal: true,
+        ..options.clone()
+    };
+    let mut daemon = Daemon::new(config, options, Box::new(Mpv::spawn));
+
+    #[cfg(feature = "mpris")]
+    match radio_libs::mpris::Mpris::new() {
+        Ok(mpris) => daemon.add_remote(Box::new(mpris)),
+        Err(e) => warn!("Could not register the MPRIS interface: {}", e),
+    }
+
+    if let Err(e) = daemon.serve(&listener) {
+        error!("The daemon stopped: {}", e);
+        std::process::exit(1);
+    }
+
+    std::process::exit(0);
+}
+
+/// Plays stations as the clients of the HTTP API say.
+fn run_serve(args: ServeArgs, config: Rc<Config>, options: &PlayerOptions) -> ! {
+    let server = match tiny_http::Server::http(args.bind) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("Could not listen on {}: {}", args.bind, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Listening on {} {}",
+        format!("http://{}", args.bind).bold(),
+        "(press Ctrl+C to stop)".italic().bright_black()
+    );
+
+    let options = PlayerOptions {
+        no_terminal: true,
+        ..options.clone()
+    };
+    let daemon = Daemon::new(config.clone(), options, Box::new(Mpv::spawn));
+
+    if let Err(e) = Api::new(config, daemon).serve(&server) {
+        error!("The server stopped: {}", e);
+        std::process::exit(1);
+    }
+
+    std::process::exit(0);
+}
+
+/// Sends a command to the daemon and shows its answer.
+fn run_ctl(ctl: CtlArgs) -> ! {
+    let request = match ctl.command {
+        CtlCommand::Play { station } => Request::Play {
+            station: station.join(" "),
+            url: None,
+        },
+        CtlCommand::Stop => Request::Stop,
+        CtlCommand::Next => Request::Next,
+        CtlCommand::Previous => Request::Previous,
+        CtlCommand::Toggle => Request::Toggle,
+        CtlCommand::Status => Request::Status,
+        CtlCommand::Volume { value } => match Request::volume(value.as_deref()) {
+            Some(r) => r,
+            None => {
+                error!("The volume must be a number, like 50, +5 or -5");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let socket = ctl.socket.unwrap_or_else(default_socket_path);
+    match daemon::send(&socket, &request) {
+        Ok(response) if response.ok => {
+            if let Some(status) = response.status {
+                println!("{}", status);
+            }
+
+            std::process::exit(0);
+        }
+        Ok(response) => {
+            perror(response.error.unwrap_or_default().as_str());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            error!("Could not reach the daemon at {}: {}", socket.display(), e);
+            info!("{}", "Start it with `radio-cli daemon`".yellow());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// How a station stopped playing
+enum Playback {
+    /// mpv exited by itself (e.g. the stream ended)
+    Finished(ExitStatus),
+    /// The sleep timer ran out
+    Slept,
+    /// The user asked to go back to the station selector
+    Menu,
+    /// The user asked for the next or previous station
+    Switch(Station),
+    /// The user asked to exit radio-cli
+    Quit,
+}
+
+/// What carries over from one station to the next while playing
+struct Session {
+    /// Updated when a station is added to the favorites
+    config: Rc<Config>,
+    /// Connected once, for the clicks and votes of all the stations played
+    directory: Directory,
+    /// Volume chosen by the user, before the adjustment of each station
+    volume: f64,
+    /// Where the volume and the podcast positions are remembered
+    state: State,
+    sleep_timer: Option<SleepTimer>,
+    /// Fade-out used by the sleep timers set while playing
+    fade: Option<Duration>,
+    title_log: Option<TitleLog>,
+    /// Remember where the station is stopped, to resume it (podcast episodes)
+    resume: bool,
+    /// What the next/previous keys go through instead of the stations of the config
+    /// (the episodes of a podcast)
+    playlist: Option<Vec<Station>>,
+}
+
+impl Session {
+    fn new(config: Rc<Config>, volume: f64, directory: Directory) -> Session {
+        Session {
+            config,
+            directory,
+            volume,
+            // Remembers nothing
+            state: State::default(),
+            sleep_timer: None,
+            fade: None,
+            title_log: None,
+            resume: false,
+            playlist: None,
+        }
+    }
+
+    /// Takes note of the volume a station is playing at, after the user changed it
+    fn set_volume(&mut self, station: &Station, volume: f64) {
+        self.volume = station.base_volume(volume, self.volume);
+
+        if self.config.remember_volume {
+            self.state.set_volume(self.volume);
+        }
+    }
+
+    /// Takes note of where an episode is being stopped, to resume it next time
+    fn remember_position(&mut self, station: &Station, mpv: &mut Mpv) {
+        if !self.resume {
+            return;
+        }
+
+        match mpv.get_property("time-pos") {
+            Ok(Value::Number(n)) => self.state.set_position(&station.url, n.as_f64()),
+            other => debug!("Unknown position in {}: {:?}", station.station, other),
+        }
+    }
+
+    /// Station `step` places away from this one, in the playlist or the config
+    fn station_near(&self, station: &Station, step: isize) -> Option<Station> {
+        match &self.playlist {
+            Some(playlist) => {
+                let stations: Vec<&Station> = playlist.iter().collect();
+                station_near(&stations, &station.url, step)
+            }
+            None => self.config.station_near(&station.url, step),
+        }
+    }
+
+    /// Saves the station in the config file and in the list of this session
+    fn favorite(&mut self, station: Station) -> String {
+        if self.config.stations().any(|s| s.url == station.url) {
+            return format!("{} is already in your stations", station.station);
+        }
+
+        if let Err(e) = self.config.add_station(&station) {
+            debug!("{:?}", e);
+            return format!("Could not save {}: {}", station.station, e);
+        }
+
+        let text = format!("Added {} to your stations", station.station);
+
+        let mut config = (*self.config).clone();
+        config.insert_station(station);
+        self.config = Rc::new(config);
+
+        text
     }
+}
+
+/// Plays a station with mpv, supervising it and handling the keyboard
+/// until it exits, the user moves on or the sleep timer runs out.
+fn play(
+    station: Station,
+    options: &PlayerOptions,
+    session: &mut Session,
+    mut ramp: Option<VolumeRamp>,
+) -> Playback {
+    let options = PlayerOptions {
+        // The alarm ramp starts from silence
+        volume: Some(match ramp {
+            Some(_) => 0.0,
+            None => station.adjusted_volume(session.volume),
+        }),
+        ..options.clone()
+    };
+
+    // Radio Browser knows the current URL of its stations
+    let stream = session.directory.report_click(station.clone());
+
+    let started = Instant::now();
+    let mut mpv = match Mpv::spawn(&stream, &options) {
+        Ok(m) => m,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            error!("Failed to execute mpv. Is it installed?");
+            debug!("{}", e);
+
+            std::process::exit(2);
+        }
+        Err(e) => mpv_failed(format!("{}", e).as_str()),
+    };
+
+    // A second connection to the stream, just to know what's playing
+    let titles = match &session.title_log {
+        Some(_) if stream.is_local() => None,
+        Some(_) => TitleWatcher::new(&stream.url)
+            .map_err(|e| warn!("Could not follow the song titles: {}", e))
+            .ok(),
+        None => None,
+    };
+
+    let keyboard = Keyboard::new();
+    let say = |text: &str| keyboard.print(&text.italic().bright_black().to_string());
+
+    // Volume at the moment the fade-out started, to scale it down from there
+    let mut fade_from: Option<f64> = None;
 
-    output.status
+    loop {
+        match mpv.try_wait() {
+            Ok(Some(status)) => {
+                // Played to the end, the next time starts from the beginning
+                if session.resume && status.success() {
+                    session.state.set_position(&station.url, None);
+                }
+
+                return Playback::Finished(status);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!("Lost track of mpv: {}", e);
+                std::process::exit(2);
+            }
+        }
+
+        if let Some(control) = keyboard.poll(POLL_INTERVAL) {
+            match control {
+                Control::Next | Control::Previous => {
+                    let step = if control == Control::Next { 1 } else { -1 };
+
+                    match session.station_near(&station, step) {
+                        Some(s) => {
+                            session.remember_position(&station, &mut mpv);
+                            stop(&mut mpv);
+                            return Playback::Switch(s);
+                        }
+                        None => say("There are no stations in your config"),
+                    }
+                }
+                Control::VolumeUp | Control::VolumeDown => {
+                    let step = if control == Control::VolumeUp {
+                        VOLUME_STEP
+                    } else {
+                        -VOLUME_STEP
+                    };
+
+                    // Pressing a key stops the alarm ramp
+                    ramp = None;
+                    fade_from = None;
+
+                    if let Ok(volume) = mpv.volume() {
+                        let volume = (volume + step).clamp(0.0, 100.0);
+                        if mpv.set_volume(volume).is_ok() {
+                            session.set_volume(&station, volume);
+                            say(&format!("Volume: {:.0}%", volume));
+                        }
+                    }
+                }
+                Control::Mute => {
+                    if let Ok(muted) = mpv.muted()
+                        && mpv.set_muted(!muted).is_ok()
+                    {
+                        say(if muted { "Unmuted" } else { "Muted" });
+                    }
+                }
+                Control::Pause => {
+                    if let Ok(paused) = mpv.paused()
+                        && mpv.set_paused(!paused).is_ok()
+                    {
+                        say(if paused { "Playing" } else { "Paused" });
+                    }
+                }
+                Control::Favorite => {
+                    let mut favorite = station.clone();
+
+                    // A bare URL is better saved with the name the stream announces
+                    if favorite.station == "URL" {
+                        favorite.station = match mpv.get_property("metadata/by-key/icy-name") {
+                            Ok(Value::String(name)) if !name.trim().is_empty() => {
+                                name.trim().to_string()
+                            }
+                            _ => favorite.url.clone(),
+                        };
+                    }
+
+                    say(&session.favorite(favorite));
+                }
+                Control::Upvote => match session.directory.vote(&station) {
+                    Ok(_) => say(&format!("Voted for {}", station.station)),
+                    Err(e) => say(&format!("Could not vote: {}", e)),
+                },
+                Control::Info => {
+                    say(&format!("{} ({})", station.station, station.url));
+
+                    if let Some(title) = mpv.title() {
+                        say(&format!("Now playing: {}", title));
+                    }
+
+                    let mut state = match mpv.volume() {
+                        Ok(v) => format!("Volume: {:.0}%", v),
+                        Err(_) => String::from("Volume: unknown"),
+                    };
+                    if let Ok(true) = mpv.muted() {
+                        state.push_str(", muted");
+                    }
+                    if let Ok(true) = mpv.paused() {
+                        state.push_str(", paused");
+                    }
+                    say(&state);
+
+                    if let Some(preset) = options.preset_for(&station) {
+                        say(&format!("Preset: {}", preset));
+                    }
+                    if let Some(device) = options.audio_device_for(&station) {
+                        say(&format!("Output: {}", device));
+                    }
+
+                    if let Some(t) = session.sleep_timer {
+                        say(&format!(
+                            "Sleep timer: {}",
+                            format_duration(t.remaining(Instant::now()))
+                        ));
+                    }
+                }
+                Control::Sleep => {
+                    session.sleep_timer = SleepTimer::cycle(session.sleep_timer, session.fade);
+                    fade_from = None;
+
+                    let text = match session.sleep_timer {
+                        Some(t) => format!(
+                            "Sleep timer: {}",
+                            format_duration(t.remaining(Instant::now()))
+                        ),
+                        None => String::from("Sleep timer off"),
+                    };
+
+                    say(&text);
+                    let _ = mpv.show_text(&text);
+                }
+                Control::Menu => {
+                    session.remember_position(&station, &mut mpv);
+                    stop(&mut mpv);
+                    return Playback::Menu;
+                }
+                Control::Quit => {
+                    session.remember_position(&station, &mut mpv);
+                    stop(&mut mpv);
+                    return Playback::Quit;
+                }
+            }
+        }
+
+        if let (Some(watcher), Some(log)) = (&titles, &session.title_log) {
+            while let Some(title) = watcher.try_next() {
+                if log_enabled!(log::Level::Info) {
+                    say(&format!("Now playing: {}", title));
+                }
+
+                if let Err(e) = log.append(&station.station, &title) {
+                    warn!("Could not log the song title: {}", e);
+                }
+            }
+        }
+
+        if let Some(r) = ramp {
+            let elapsed = started.elapsed();
+            let _ = mpv.set_volume(r.volume_at(elapsed));
+
+            if r.is_done(elapsed) {
+                ramp = None;
+            }
+        }
+
+        if let Some(timer) = session.sleep_timer {
+            match timer.state(Instant::now()) {
+                SleepState::Running => {}
+                SleepState::Fading(factor) => {
+                    if fade_from.is_none() {
+                        fade_from = mpv.volume().ok();
+                    }
+
+                    if let Some(volume) = fade_from {
+                        let _ = mpv.set_volume(volume * factor);
+                    }
+                }
+                SleepState::Expired => {
+                    session.remember_position(&station, &mut mpv);
+                    stop(&mut mpv);
+                    return Playback::Slept;
+                }
+            }
+        }
+    }
+}
+
+fn stop(player: &mut impl Player) {
+    if let Err(e) = player.stop() {
+        debug!("{}", e);
+    }
 }
 
 fn get_station(
     station: Option<String>,
     config: Rc<Config>,
+    directory: &Directory,
     cached_stations: Option<StationCache>,
 ) -> (Station, bool, Option<StationCache>) {
     let mut internet = false;
@@ -203,8 +1457,8 @@ fn get_station(
     match station {
         // If the station name is passed as an argument:
         Some(x) => {
-            let (url, updated_cached_stations) = match config.get_url_for(&x) {
-                Some(u) => (u, None),
+            let (station, updated_cached_stations) = match config.get_station(&x) {
+                Some(s) => (s, None),
                 None => {
                     println!(
                         "{}",
@@ -215,20 +1469,20 @@ fn get_station(
 
                     internet = true;
 
-                    let (brows, updated_cached_stations) =
-                        match Browser::new(config, cached_stations) {
-                            Ok(b) => b,
-                            Err(e) => {
-                                error!("Could not connect with the API");
+                    let (brows, updated_cached_stations) = match directory.browser(cached_stations)
+                    {
+                        Ok(b) => b,
+                        Err(e) => {
+                            error!("Could not connect with the API");
 
-                                debug!("{}", e);
+                            debug!("{}", e);
 
-                                std::process::exit(1);
-                            }
-                        };
+                            std::process::exit(1);
+                        }
+                    };
 
-                    match brows.get_station(x.clone()) {
-                        Ok(s) => (s.url, Some(updated_cached_stations)),
+                    match brows.choose_station(x.clone()) {
+                        Ok(s) => (Station { station: x, ..s }, Some(updated_cached_stations)),
                         Err(e) => {
                             error!("This station was not found :(");
                             debug!("{}", e);
@@ -239,17 +1493,13 @@ fn get_station(
                 }
             };
 
-            (
-                Station { station: x, url },
-                internet,
-                updated_cached_stations,
-            )
+            (station, internet, updated_cached_stations)
         }
 
         // Otherwise
         None => {
             // And let the user choose one
-            match prompt(config, cached_stations) {
+            match prompt(config, directory, cached_stations) {
                 Ok((s, b, cached)) => (s, b, cached),
                 Err(error) => {
                     println!("\n\t{}", "Bye!".bold().green());
@@ -267,6 +1517,7 @@ fn get_station(
 /// Returns a station and if the station was taken from the internet.
 pub fn prompt(
     config: Rc<Config>,
+    directory: &Directory,
     cached_stations: Option<StationCache>,
 ) -> Result<(Station, bool, Option<StationCache>), InquireError> {
     let max_lines: usize = match config.max_lines {
@@ -274,16 +1525,25 @@ pub fn prompt(
         None => Select::<Station>::DEFAULT_PAGE_SIZE,
     };
 
-    let res = Select::new(&"Select a station to play:".bold(), config.data.clone())
+    let mut choices = config.data.clone();
+    choices.push(Station {
+        station: String::from("Browse"),
+        ..Default::default()
+    });
+
+    let res = Select::new(&"Select a station to play:".bold(), choices)
         .with_page_size(max_lines)
         .prompt();
 
     let internet: bool;
     let (station, updated_cached_stations) = match res {
         Ok(s) => {
-            if s.station.eq("Other") {
+            if s.station.eq("Browse") {
+                internet = true;
+                (browse(config, directory, None, None)?, None)
+            } else if s.station.eq("Other") {
                 internet = true;
-                let result = Browser::new(config, cached_stations);
+                let result = directory.browser(cached_stations);
 
                 let (brow, updated_cached_stations) = match result {
                     Ok((b, updated_cached_stations)) => (b, updated_cached_stations),
@@ -304,3 +1564,76 @@ pub fn prompt(
 
     Ok((station, internet, updated_cached_stations))
 }
+
+/// Lets the user choose a tag, language, codec or region (unless given), and then one of its stations
+fn browse(
+    config: Rc<Config>,
+    directory: &Directory,
+    category: Option<Category>,
+    name: Option<String>,
+) -> Result<Station, InquireError> {
+    let max_lines = config
+        .max_lines
+        .unwrap_or(Select::<Station>::DEFAULT_PAGE_SIZE);
+
+    let category = match category {
+        Some(c) => c,
+        None => Select::new(&"Browse the stations by:".bold(), Category::ALL.to_vec()).prompt()?,
+    };
+
+    let connection_error = |e: Box<dyn std::error::Error>| {
+        error!("Could not connect with the API");
+        debug!("{}", e);
+        InquireError::OperationInterrupted
+    };
+
+    let (mut browser, _) = directory
+        .browser(Some(StationCache::default()))
+        .map_err(connection_error)?;
+    let groups = browser.groups(category).map_err(connection_error)?;
+
+    let group = match name {
+        Some(n) => match groups.into_iter().find(|g| g.name.eq_ignore_ascii_case(&n)) {
+            Some(g) => g,
+            None => {
+                error!("Nothing called \"{}\" in the {}", n, category);
+                return Err(InquireError::InvalidConfiguration(n));
+            }
+        },
+        None => Select::new(&format!("{}:", category).bold(), groups)
+            .with_page_size(max_lines)
+            .prompt()?,
+    };
+
+    browser
+        .list_group(category, &group)
+        .map_err(connection_error)?;
+
+    browser.prompt()
+}
+
+/// Prints the tags, languages, codecs or regions with their number of stations
+fn list_groups(category: Category, config: Rc<Config>) -> ! {
+    let groups = Directory::new(config)
+        .browser(Some(StationCache::default()))
+        .and_then(|(mut browser, _)| browser.groups(category));
+
+    match groups {
+        Ok(groups) => {
+            for group in groups {
+                println!(
+                    "{}: {}",
+                    group.name.bold(),
+                    format!("{} stations", group.stationcount).bright_black()
+                );
+            }
+
+            std::process::exit(0);
+        }
+        Err(e) => {
+            debug!("{}", e);
+            error!("Could not connect to the server, please check your connection.");
+            std::process::exit(1);
+        }
+    }
+}